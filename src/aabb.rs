@@ -0,0 +1,127 @@
+use crate::point::Point;
+use crate::ray::Ray;
+
+// Axis-aligned bounding box, described by its two opposite corners
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Aabb {
+    pub minimum: Point,
+    pub maximum: Point,
+}
+
+impl Aabb {
+    pub fn new(minimum: Point, maximum: Point) -> Self {
+        Self { minimum, maximum }
+    }
+
+    // Slab test
+    // For each axis, find the t values at which the ray enters and leaves the pair of planes
+    // bounding the box on that axis.
+    // The ray hits the box if the overlap of all three [t_enter, t_exit] ranges is non-empty.
+    pub fn hit(&self, ray: Ray, t_min: f64, t_max: f64) -> bool {
+        self.hit_range(ray, t_min, t_max).is_some()
+    }
+
+    // The part of (t_min, t_max) the ray spends inside the box, if any
+    pub fn hit_range(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<(f64, f64)> {
+        let mut t_min = t_min;
+        let mut t_max = t_max;
+        for axis in 0..3 {
+            let inverse_direction = 1.0 / ray.direction[axis];
+            let mut t0 = (self.minimum[axis] - ray.origin[axis]) * inverse_direction;
+            let mut t1 = (self.maximum[axis] - ray.origin[axis]) * inverse_direction;
+            if inverse_direction < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            // NaN (ray lies on a slab boundary) is treated as not narrowing the range
+            if t0 > t_min { t_min = t0; }
+            if t1 < t_max { t_max = t1; }
+            if t_max < t_min {
+                return None;
+            }
+        }
+        Some((t_min, t_max))
+    }
+
+    // Smallest box enclosing both boxes
+    pub fn surrounding_box(box0: Aabb, box1: Aabb) -> Aabb {
+        let minimum = Point::new(
+            f64::min(box0.minimum.x, box1.minimum.x),
+            f64::min(box0.minimum.y, box1.minimum.y),
+            f64::min(box0.minimum.z, box1.minimum.z),
+        );
+        let maximum = Point::new(
+            f64::max(box0.maximum.x, box1.maximum.x),
+            f64::max(box0.maximum.y, box1.maximum.y),
+            f64::max(box0.maximum.z, box1.maximum.z),
+        );
+        Aabb::new(minimum, maximum)
+    }
+
+    // Widen any axis thinner than min_extent, so flat objects (e.g. axis-aligned triangles)
+    // still get a box rays can hit
+    pub fn padded(self, min_extent: f64) -> Aabb {
+        let mut minimum = self.minimum;
+        let mut maximum = self.maximum;
+        for axis in 0..3 {
+            if maximum[axis] - minimum[axis] < min_extent {
+                let center = (minimum[axis] + maximum[axis]) / 2.0;
+                minimum[axis] = center - min_extent / 2.0;
+                maximum[axis] = center + min_extent / 2.0;
+            }
+        }
+        Aabb::new(minimum, maximum)
+    }
+
+    pub fn centroid(&self) -> Point {
+        (self.minimum + self.maximum) / 2.0
+    }
+
+    // Axis (0, 1, 2 for x, y, z) along which the box is widest
+    pub fn longest_axis(&self) -> usize {
+        let extent = self.maximum - self.minimum;
+        if extent.x >= extent.y && extent.x >= extent.z {
+            0
+        } else if extent.y >= extent.z {
+            1
+        } else {
+            2
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vec3::Vec3;
+
+    fn unit_box() -> Aabb {
+        Aabb::new(Point::new(-1.0, -1.0, -1.0), Point::new(1.0, 1.0, 1.0))
+    }
+
+    #[test]
+    fn ray_should_hit_box() {
+        let ray = Ray::new(Point::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(unit_box().hit(ray, 0.001, f64::INFINITY));
+    }
+
+    #[test]
+    fn ray_should_miss_box() {
+        let ray = Ray::new(Point::new(0.0, 2.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(!unit_box().hit(ray, 0.001, f64::INFINITY));
+    }
+
+    #[test]
+    fn box_behind_ray_should_not_be_hit() {
+        let ray = Ray::new(Point::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(!unit_box().hit(ray, 0.001, f64::INFINITY));
+    }
+
+    #[test]
+    fn surrounding_box_should_enclose_both() {
+        let box0 = Aabb::new(Point::new(0.0, 0.0, 0.0), Point::new(1.0, 1.0, 1.0));
+        let box1 = Aabb::new(Point::new(-2.0, 0.5, 0.5), Point::new(0.5, 3.0, 0.5));
+        let surrounding = Aabb::surrounding_box(box0, box1);
+        assert_eq!(surrounding.minimum, Point::new(-2.0, 0.0, 0.0));
+        assert_eq!(surrounding.maximum, Point::new(1.0, 3.0, 1.0));
+    }
+}
@@ -0,0 +1,176 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::ray::Ray;
+
+// Bounding volume hierarchy
+// Each node owns up to two children and the box enclosing both of them.
+// A ray which misses a node's box cannot hit anything below it,
+// so whole subtrees are skipped instead of testing every object.
+pub struct BvhNode {
+    left: Arc<dyn Hittable>,
+    right: Option<Arc<dyn Hittable>>,
+    bbox: Aabb,
+}
+
+impl BvhNode {
+    // Objects must all have bounding boxes, and there must be at least one object
+    pub fn new(mut objects: Vec<Arc<dyn Hittable>>) -> Self {
+        assert!(!objects.is_empty(), "BvhNode requires at least one object");
+
+        if objects.len() == 1 {
+            let left = objects.remove(0);
+            let bbox = bounding_box_of(left.as_ref());
+            return BvhNode { left, right: None, bbox };
+        }
+
+        // Split along the axis where the object centroids are most spread out
+        let centroid_bounds = objects
+            .iter()
+            .map(|object| {
+                let centroid = bounding_box_of(object.as_ref()).centroid();
+                Aabb::new(centroid, centroid)
+            })
+            .reduce(Aabb::surrounding_box)
+            .unwrap();
+        let axis = centroid_bounds.longest_axis();
+
+        objects.sort_by(|a, b| {
+            let a_centroid = bounding_box_of(a.as_ref()).centroid()[axis];
+            let b_centroid = bounding_box_of(b.as_ref()).centroid()[axis];
+            a_centroid.partial_cmp(&b_centroid).unwrap_or(std::cmp::Ordering::Equal)
+        });
+
+        let right_objects = objects.split_off(objects.len() / 2);
+        let left = BvhNode::new_child(objects);
+        let right = BvhNode::new_child(right_objects);
+        let bbox = Aabb::surrounding_box(
+            bounding_box_of(left.as_ref()),
+            bounding_box_of(right.as_ref()),
+        );
+        BvhNode { left, right: Some(right), bbox }
+    }
+
+    // Leaves hold the object directly rather than a node wrapping a single object
+    fn new_child(mut objects: Vec<Arc<dyn Hittable>>) -> Arc<dyn Hittable> {
+        if objects.len() == 1 {
+            return objects.remove(0);
+        }
+        Arc::new(BvhNode::new(objects))
+    }
+}
+
+impl Hittable for BvhNode {
+    // Only descend into children whose boxes the ray passes through
+    // The right child only needs to beat the left child's hit
+    fn hit(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        if !self.bbox.hit(ray, t_min, t_max) {
+            return None;
+        }
+        let left_hit = self.left.hit(ray, t_min, t_max);
+        let closest_so_far = left_hit.as_ref().map_or(t_max, |hit| hit.t);
+        let right_hit = match &self.right {
+            Some(right) => right.hit(ray, t_min, closest_so_far),
+            None => None,
+        };
+        right_hit.or(left_hit)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bbox)
+    }
+}
+
+fn bounding_box_of(object: &dyn Hittable) -> Aabb {
+    object
+        .bounding_box()
+        .expect("Objects in a BvhNode must have a bounding box")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    use crate::color::Color;
+    use crate::material::{Lambertian, Material};
+    use crate::point::Point;
+    use crate::sphere::Sphere;
+    use crate::vec3::Vec3;
+    use crate::world::World;
+
+    fn random_spheres(rng: &mut impl Rng, material: Arc<dyn Material>) -> Vec<Sphere> {
+        (0..500)
+            .map(|_| {
+                let center = Point::new(
+                    rng.gen_range(-20.0, 20.0),
+                    rng.gen_range(-20.0, 20.0),
+                    rng.gen_range(-20.0, 20.0),
+                );
+                Sphere::new(center, rng.gen_range(0.1, 1.5), material.clone())
+            })
+            .collect()
+    }
+
+    // Linear scan over every object, which the BVH must agree with
+    fn brute_force_nearest(spheres: &[Sphere], ray: Ray) -> Option<HitRecord> {
+        let mut nearest: Option<HitRecord> = None;
+        for sphere in spheres.iter() {
+            let closest_so_far = nearest.as_ref().map_or(f64::INFINITY, |hit| hit.t);
+            if let Some(hit) = sphere.hit(ray, 0.001, closest_so_far) {
+                nearest = Some(hit);
+            }
+        }
+        nearest
+    }
+
+    #[test]
+    fn bvh_should_match_brute_force() {
+        let mut rng = rand::thread_rng();
+        let material: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let spheres = random_spheres(&mut rng, material.clone());
+        let objects: Vec<Arc<dyn Hittable>> = spheres
+            .iter()
+            .map(|sphere| Arc::new(sphere.clone()) as Arc<dyn Hittable>)
+            .collect();
+        let world = World::new(objects);
+
+        let mut hits = 0;
+        for _ in 0..2000 {
+            let origin = Vec3::bound_random(-30.0, 30.0);
+            let direction = Vec3::bound_random(-1.0, 1.0);
+            let ray = Ray::new(origin, direction);
+
+            let expected = brute_force_nearest(&spheres, ray)
+                .map(|hit| (hit.t, hit.point, hit.normal, hit.front_face));
+            let actual = world
+                .hit(ray, 0.001, f64::INFINITY)
+                .map(|hit| (hit.t, hit.point, hit.normal, hit.front_face));
+            assert_eq!(expected, actual);
+            if actual.is_some() {
+                hits += 1;
+            }
+        }
+        // Make sure the comparison was not vacuous
+        assert!(hits > 0);
+    }
+
+    #[test]
+    fn bvh_bounding_box_should_enclose_all_objects() {
+        let mut rng = rand::thread_rng();
+        let material: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let spheres = random_spheres(&mut rng, material);
+        let bvh = BvhNode::new(
+            spheres
+                .iter()
+                .map(|sphere| Arc::new(sphere.clone()) as Arc<dyn Hittable>)
+                .collect(),
+        );
+        let bbox = bvh.bounding_box().unwrap();
+        for sphere in spheres.iter() {
+            let sphere_box = sphere.bounding_box().unwrap();
+            assert_eq!(Aabb::surrounding_box(bbox, sphere_box), bbox);
+        }
+    }
+}
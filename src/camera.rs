@@ -1,6 +1,10 @@
+extern crate rand;
+use rand::Rng;
+
 use crate::point::Point;
 use crate::vec3::Vec3;
 use crate::ray::Ray;
+use crate::utils;
 
 pub struct Camera {
     // Camera location
@@ -17,6 +21,10 @@ pub struct Camera {
 
     // Camera image location
     lower_left_corner: Vec3,
+
+    // Rays are cast at random times between the shutter opening and closing
+    shutter_open: f64,
+    shutter_close: f64,
 }
 
 impl Camera {
@@ -52,15 +60,28 @@ impl Camera {
             lens_radius,
             u,
             v,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
         }
     }
 
+    // Exposure from time open to close, instead of an instant at time 0
+    pub fn with_shutter(self, open: f64, close: f64) -> Self {
+        Self { shutter_open: open, shutter_close: close, ..self }
+    }
+
     pub fn get_ray(&self, s: f64, t: f64) -> Ray {
         let ray_direction = self.lens_radius * Vec3::random_in_unit_disk();
         let offset = self.u * ray_direction.x + self.v * ray_direction.y;
-        return Ray::new(
+        let time = if self.shutter_close > self.shutter_open {
+            utils::rng().gen_range(self.shutter_open, self.shutter_close)
+        } else {
+            self.shutter_open
+        };
+        Ray::with_time(
             self.origin + offset,
-            self.lower_left_corner + s * self.horizontal + t* self.vertical - self.origin - offset
-        );
+            self.lower_left_corner + s * self.horizontal + t* self.vertical - self.origin - offset,
+            time
+        )
     }
 }
@@ -0,0 +1,434 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::encoder::ImageFormat;
+use crate::point::Point;
+use crate::render::Background;
+use crate::vec3::Vec3;
+
+pub const USAGE: &str = "\
+Usage: raytracer [OPTIONS]
+
+Image:
+    --width <PIXELS>         Image width (default: height * 16 / 9)
+    --height <PIXELS>        Image height (default: width * 9 / 16, or 216)
+    --spp <N>                Samples per pixel (default: 100)
+    --max-depth <N>          Maximum number of bounces per ray (default: 100)
+    --scene <NAME|FILE>      Built-in scene name or JSON scene file (default: random)
+    --output <PATH>          Write the image to PATH (.ppm or .png) instead of stdout,
+                             may be given more than once
+    --format <FORMAT>        ppm, ppm-binary or png, instead of following the extension
+    --seed <N>               Seed for a reproducible render
+    --threads <N>            Number of render threads (default: all cores)
+    --background <COLOR>     'sky' for the default gradient, or R,G,B for a solid color
+
+Camera:
+    --look-from <X,Y,Z>      Camera location (default: 13,2,3)
+    --look-at <X,Y,Z>        Point the camera faces (default: 0,0,0)
+    --vup <X,Y,Z>            Camera up direction (default: 0,1,0)
+    --vfov <DEGREES>         Vertical field of view (default: 20)
+    --aperture <N>           Lens aperture, 0 for a pinhole (default: 0.1)
+    --focus-dist <N>         Distance to the plane in focus (default: 10)
+    --shutter <OPEN,CLOSE>   Exposure times for motion blur (default: 0,0, no blur)
+
+    -h, --help               Print this message";
+
+const DEFAULT_ASPECT_RATIO: f64 = 16.0 / 9.0; // width / height
+const DEFAULT_IMAGE_HEIGHT: i32 = 216;
+
+// Largest width or height, so that counts of pixels and samples stay well within i32
+pub const MAX_IMAGE_SIZE: i32 = 16384;
+
+pub struct CameraOptions {
+    pub look_from: Point,
+    pub look_at: Point,
+    pub vup: Vec3,
+    pub vertical_fov: f64, // Degrees
+    pub aperture: f64,
+    pub focus_dist: f64,
+    pub shutter: (f64, f64), // Open and close times
+}
+
+impl CameraOptions {
+    // The camera basis is built from these, so they must not be degenerate
+    pub fn check_basis(&self) -> Result<(), &'static str> {
+        let view_direction = self.look_at - self.look_from;
+        if view_direction.length_squared() == 0.0 {
+            return Err("look_at must differ from look_from");
+        }
+        if self.vup.cross(view_direction).length_squared() == 0.0 {
+            return Err("vup must not be zero or parallel to the view direction");
+        }
+        Ok(())
+    }
+}
+
+impl Default for CameraOptions {
+    fn default() -> Self {
+        CameraOptions {
+            look_from: Point::new(13.0, 2.0, 3.0),
+            look_at: Point::new(0.0, 0.0, 0.0),
+            vup: Vec3::new(0.0, 1.0, 0.0),
+            vertical_fov: 20.0,
+            aperture: 0.1,
+            focus_dist: 10.0,
+            shutter: (0.0, 0.0),
+        }
+    }
+}
+
+pub struct Options {
+    pub image_width: i32,
+    pub image_height: i32,
+    pub samples_per_pixel: i32,
+    pub max_depth: i32,
+    pub scene: String,
+    pub outputs: Vec<PathBuf>, // Empty writes to stdout
+    pub format: Option<ImageFormat>, // None follows each output's extension
+    pub seed: Option<u64>,
+    pub threads: usize,
+    pub camera: CameraOptions,
+    pub background: Background,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            image_width: (DEFAULT_IMAGE_HEIGHT as f64 * DEFAULT_ASPECT_RATIO) as i32,
+            image_height: DEFAULT_IMAGE_HEIGHT,
+            samples_per_pixel: 100,
+            max_depth: 100,
+            scene: String::from("random"),
+            outputs: vec![],
+            format: None,
+            seed: None,
+            threads: std::thread::available_parallelism().map_or(1, |threads| threads.get()),
+            camera: CameraOptions::default(),
+            background: Background::Sky,
+        }
+    }
+}
+
+impl Options {
+    pub fn aspect_ratio(&self) -> f64 {
+        self.image_width as f64 / self.image_height as f64
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CliError {
+    // Not a failure, the caller should print USAGE and exit
+    HelpRequested,
+    UnknownFlag(String),
+    MissingValue(String),
+    InvalidValue { flag: String, value: String, reason: String },
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CliError::HelpRequested => write!(f, "help requested"),
+            CliError::UnknownFlag(flag) => write!(f, "unknown option '{}'", flag),
+            CliError::MissingValue(flag) => write!(f, "option '{}' requires a value", flag),
+            CliError::InvalidValue { flag, value, reason } => {
+                write!(f, "invalid value '{}' for '{}': {}", value, flag, reason)
+            }
+        }
+    }
+}
+
+// Parses the arguments following the program name
+pub fn parse_args<I: IntoIterator<Item = String>>(args: I) -> Result<Options, CliError> {
+    parse_args_onto(Options::default(), args)
+}
+
+// Arguments override the given options, e.g. those read from a scene file
+pub fn parse_args_onto<I: IntoIterator<Item = String>>(base: Options, args: I) -> Result<Options, CliError> {
+    let mut options = base;
+    let mut width = None;
+    let mut height = None;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "-h" || arg == "--help" {
+            return Err(CliError::HelpRequested);
+        }
+
+        // Accept both "--flag value" and "--flag=value"
+        let (flag, inline_value) = match arg.find('=') {
+            Some(index) if arg.starts_with("--") => (arg[..index].to_string(), Some(arg[index + 1..].to_string())),
+            _ => (arg, None),
+        };
+        let known = [
+            "--width", "--height", "--spp", "--max-depth", "--scene", "--output", "--format", "--seed",
+            "--threads", "--background", "--look-from", "--look-at", "--vup", "--vfov", "--aperture", "--focus-dist",
+            "--shutter",
+        ];
+        if !known.contains(&flag.as_str()) {
+            return Err(CliError::UnknownFlag(flag));
+        }
+        let value = match inline_value.or_else(|| args.next()) {
+            Some(value) => value,
+            None => return Err(CliError::MissingValue(flag)),
+        };
+
+        match flag.as_str() {
+            "--width" => width = Some(parse_image_size(&flag, &value)?),
+            "--height" => height = Some(parse_image_size(&flag, &value)?),
+            "--spp" => options.samples_per_pixel = parse_at_least(&flag, &value, 1)?,
+            "--max-depth" => options.max_depth = parse_at_least(&flag, &value, 1)?,
+            "--scene" => options.scene = value,
+            "--output" => options.outputs.push(PathBuf::from(value)),
+            "--format" => {
+                let format = ImageFormat::from_name(&value).map_err(|reason| invalid(&flag, &value, &reason))?;
+                options.format = Some(format);
+            }
+            "--seed" => options.seed = Some(parse_value(&flag, &value)?),
+            "--threads" => options.threads = parse_at_least(&flag, &value, 1)? as usize,
+            "--background" => options.background = parse_background(&flag, &value)?,
+            "--look-from" => options.camera.look_from = parse_vector(&flag, &value)?,
+            "--look-at" => options.camera.look_at = parse_vector(&flag, &value)?,
+            "--vup" => options.camera.vup = parse_vector(&flag, &value)?,
+            "--vfov" => {
+                let vertical_fov = parse_finite(&flag, &value)?;
+                if vertical_fov <= 0.0 || vertical_fov >= 180.0 {
+                    return Err(invalid(&flag, &value, "must be between 0 and 180 degrees"));
+                }
+                options.camera.vertical_fov = vertical_fov;
+            }
+            "--aperture" => {
+                let aperture = parse_finite(&flag, &value)?;
+                if aperture < 0.0 {
+                    return Err(invalid(&flag, &value, "must not be negative"));
+                }
+                options.camera.aperture = aperture;
+            }
+            "--focus-dist" => {
+                let focus_dist = parse_finite(&flag, &value)?;
+                if focus_dist <= 0.0 {
+                    return Err(invalid(&flag, &value, "must be positive"));
+                }
+                options.camera.focus_dist = focus_dist;
+            }
+            "--shutter" => options.camera.shutter = parse_shutter(&flag, &value)?,
+            _ => unreachable!(),
+        }
+    }
+
+    // Without --format each output's extension chooses its format
+    if options.format.is_none() {
+        for output in options.outputs.iter() {
+            ImageFormat::from_path(output).map_err(|reason| invalid("--output", &output.to_string_lossy(), &reason))?;
+        }
+    }
+
+    // Keep the previous aspect ratio for whichever dimension is not given
+    let aspect_ratio = options.aspect_ratio();
+    let (image_width, image_height) = match (width, height) {
+        (Some(width), Some(height)) => (width, height),
+        (Some(width), None) => (width, i32::max(2, (width as f64 / aspect_ratio).round() as i32)),
+        (None, Some(height)) => (i32::max(2, (height as f64 * aspect_ratio).round() as i32), height),
+        (None, None) => (options.image_width, options.image_height),
+    };
+    // The dimension kept from the aspect ratio can still be too large
+    if image_width > MAX_IMAGE_SIZE || image_height > MAX_IMAGE_SIZE {
+        let (flag, value) = match (width, height) {
+            (None, Some(height)) => ("--height", height),
+            (Some(width), _) => ("--width", width),
+            (None, None) => unreachable!(),
+        };
+        return Err(invalid(flag, &value.to_string(), &format!("the image would be over {} pixels across", MAX_IMAGE_SIZE)));
+    }
+    options.image_width = image_width;
+    options.image_height = image_height;
+
+    let camera = &options.camera;
+    if let Err(reason) = camera.check_basis() {
+        let (flag, value) = if camera.look_at == camera.look_from {
+            ("--look-at", camera.look_at)
+        } else {
+            ("--vup", camera.vup)
+        };
+        return Err(invalid(flag, &format_vector(value), reason));
+    }
+
+    Ok(options)
+}
+
+fn invalid(flag: &str, value: &str, reason: &str) -> CliError {
+    CliError::InvalidValue {
+        flag: flag.to_string(),
+        value: value.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+fn parse_value<T: FromStr>(flag: &str, value: &str) -> Result<T, CliError> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| invalid(flag, value, "not a valid number"))
+}
+
+// Rejects NaN and infinities, which parse as valid f64
+fn parse_finite(flag: &str, value: &str) -> Result<f64, CliError> {
+    let parsed: f64 = parse_value(flag, value)?;
+    if !parsed.is_finite() {
+        return Err(invalid(flag, value, "not a valid number"));
+    }
+    Ok(parsed)
+}
+
+fn parse_at_least(flag: &str, value: &str, min: i32) -> Result<i32, CliError> {
+    let parsed: i32 = parse_value(flag, value)?;
+    if parsed < min {
+        return Err(invalid(flag, value, &format!("must be at least {}", min)));
+    }
+    Ok(parsed)
+}
+
+fn parse_image_size(flag: &str, value: &str) -> Result<i32, CliError> {
+    let size = parse_at_least(flag, value, 2)?;
+    if size > MAX_IMAGE_SIZE {
+        return Err(invalid(flag, value, &format!("must be at most {}", MAX_IMAGE_SIZE)));
+    }
+    Ok(size)
+}
+
+// "x,y,z"
+fn parse_vector(flag: &str, value: &str) -> Result<Vec3, CliError> {
+    let components = value
+        .split(',')
+        .map(|component| parse_finite(flag, component))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| invalid(flag, value, "expected three numbers as X,Y,Z"))?;
+    match components[..] {
+        [x, y, z] => Ok(Vec3::new(x, y, z)),
+        _ => Err(invalid(flag, value, "expected three numbers as X,Y,Z")),
+    }
+}
+
+// "open,close", closing no earlier than it opens
+fn parse_shutter(flag: &str, value: &str) -> Result<(f64, f64), CliError> {
+    let times = value
+        .split(',')
+        .map(|time| parse_finite(flag, time))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| invalid(flag, value, "expected two times as OPEN,CLOSE"))?;
+    match times[..] {
+        [open, close] if open <= close => Ok((open, close)),
+        [_, _] => Err(invalid(flag, value, "the shutter must close after it opens")),
+        _ => Err(invalid(flag, value, "expected two times as OPEN,CLOSE")),
+    }
+}
+
+// "sky" or "r,g,b", components may exceed 1 for a bright background
+fn parse_background(flag: &str, value: &str) -> Result<Background, CliError> {
+    if value == "sky" {
+        return Ok(Background::Sky);
+    }
+    let color = parse_vector(flag, value).map_err(|_| invalid(flag, value, "expected 'sky' or R,G,B"))?;
+    if color.x < 0.0 || color.y < 0.0 || color.z < 0.0 {
+        return Err(invalid(flag, value, "color components must not be negative"));
+    }
+    Ok(Background::Solid(color))
+}
+
+fn format_vector(vector: Vec3) -> String {
+    format!("{},{},{}", vector.x, vector.y, vector.z)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+
+    fn parse(args: &[&str]) -> Result<Options, CliError> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn no_arguments_should_give_defaults() {
+        let options = parse(&[]).unwrap();
+        assert_eq!(options.image_width, 384);
+        assert_eq!(options.image_height, 216);
+        assert_eq!(options.samples_per_pixel, 100);
+        assert_eq!(options.scene, "random");
+        assert!(options.outputs.is_empty());
+    }
+
+    #[test]
+    fn arguments_should_be_parsed() {
+        let options = parse(&[
+            "--width", "200", "--height=100", "--spp", "8", "--max-depth", "5",
+            "--output", "out.ppm", "--output", "out.png", "--seed", "42", "--look-from", "1,2,3", "--vfov", "45",
+            "--background", "0,0,0",
+        ])
+        .unwrap();
+        assert_eq!(options.image_width, 200);
+        assert_eq!(options.image_height, 100);
+        assert_eq!(options.samples_per_pixel, 8);
+        assert_eq!(options.max_depth, 5);
+        assert_eq!(options.outputs, vec![PathBuf::from("out.ppm"), PathBuf::from("out.png")]);
+        assert_eq!(options.seed, Some(42));
+        assert_eq!(options.camera.look_from, Point::new(1.0, 2.0, 3.0));
+        assert_eq!(options.camera.vertical_fov, 45.0);
+        assert_eq!(options.background, Background::Solid(Color::new(0.0, 0.0, 0.0)));
+        assert_eq!(parse(&["--background", "sky"]).unwrap().background, Background::Sky);
+        assert_eq!(parse(&["--shutter", "0,0.5"]).unwrap().camera.shutter, (0.0, 0.5));
+        // The format replaces the extension, in either order
+        let options = parse(&["--output", "out.img", "--format", "png"]).unwrap();
+        assert_eq!((options.outputs, options.format), (vec![PathBuf::from("out.img")], Some(ImageFormat::Png)));
+        assert!(parse(&["--format", "ppm-binary", "--output", "out"]).is_ok());
+    }
+
+    #[test]
+    fn single_dimension_should_keep_aspect_ratio() {
+        let options = parse(&["--height", "90"]).unwrap();
+        assert_eq!(options.image_width, 160);
+        let options = parse(&["--width", "160"]).unwrap();
+        assert_eq!(options.image_height, 90);
+    }
+
+    #[test]
+    fn arguments_should_override_base_options() {
+        let base = parse(&["--width", "100", "--height", "100", "--spp", "3"]).unwrap();
+        let options = parse_args_onto(base, vec![String::from("--height"), String::from("50")]).unwrap();
+        assert_eq!(options.image_width, 50);
+        assert_eq!(options.image_height, 50);
+        assert_eq!(options.samples_per_pixel, 3);
+    }
+
+    #[test]
+    fn nonsensical_values_should_be_rejected() {
+        assert!(matches!(parse(&["--width", "0"]), Err(CliError::InvalidValue { .. })));
+        assert!(matches!(parse(&["--width", "100000"]), Err(CliError::InvalidValue { .. })));
+        assert!(matches!(parse(&["--height", "99999999999"]), Err(CliError::InvalidValue { .. })));
+        // 16384 high would make the default aspect ratio 29127 wide
+        assert!(matches!(parse(&["--height", "16384"]), Err(CliError::InvalidValue { .. })));
+        assert!(matches!(parse(&["--spp", "-3"]), Err(CliError::InvalidValue { .. })));
+        assert!(matches!(parse(&["--spp", "lots"]), Err(CliError::InvalidValue { .. })));
+        assert!(matches!(parse(&["--vfov", "180"]), Err(CliError::InvalidValue { .. })));
+        assert!(matches!(parse(&["--vfov", "NaN"]), Err(CliError::InvalidValue { .. })));
+        assert!(matches!(parse(&["--aperture", "-1"]), Err(CliError::InvalidValue { .. })));
+        assert!(matches!(parse(&["--focus-dist", "0"]), Err(CliError::InvalidValue { .. })));
+        assert!(matches!(parse(&["--shutter", "1,0"]), Err(CliError::InvalidValue { .. })));
+        assert!(matches!(parse(&["--shutter", "1"]), Err(CliError::InvalidValue { .. })));
+        assert!(matches!(parse(&["--look-at", "1,2"]), Err(CliError::InvalidValue { .. })));
+        assert!(matches!(parse(&["--output", "image.bmp"]), Err(CliError::InvalidValue { .. })));
+        assert!(matches!(parse(&["--output", "image.img", "--format", "bmp"]), Err(CliError::InvalidValue { .. })));
+        assert!(matches!(parse(&["--format", "bmp"]), Err(CliError::InvalidValue { .. })));
+        assert!(matches!(parse(&["--background", "-1,0,0"]), Err(CliError::InvalidValue { .. })));
+        assert!(matches!(parse(&["--background", "black"]), Err(CliError::InvalidValue { .. })));
+        assert!(matches!(parse(&["--look-at", "13,2,3"]), Err(CliError::InvalidValue { .. })));
+        assert!(matches!(parse(&["--vup", "13,2,3"]), Err(CliError::InvalidValue { .. })));
+    }
+
+    #[test]
+    fn malformed_arguments_should_be_rejected() {
+        assert_eq!(parse(&["--wdith", "10"]).err(), Some(CliError::UnknownFlag(String::from("--wdith"))));
+        assert_eq!(parse(&["--spp"]).err(), Some(CliError::MissingValue(String::from("--spp"))));
+        assert_eq!(parse(&["--help"]).err(), Some(CliError::HelpRequested));
+    }
+}
@@ -0,0 +1,249 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable, Interval};
+use crate::ray::Ray;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Operation {
+    Union,        // Inside either
+    Intersection, // Inside both
+    Difference,   // Inside the left but not the right
+}
+
+impl Operation {
+    fn contains(self, in_left: bool, in_right: bool) -> bool {
+        match self {
+            Operation::Union => in_left || in_right,
+            Operation::Intersection => in_left && in_right,
+            Operation::Difference => in_left && !in_right,
+        }
+    }
+}
+
+// Constructive solid geometry, two solids combined into one
+// The surface of the result is made of the parts of each surface where the ray goes in or
+// out of the result, keeping their materials. Where the right solid is cut out of the left,
+// its surface faces the other way, into the hole.
+pub struct Csg {
+    operation: Operation,
+    left: Arc<dyn Hittable>,
+    right: Arc<dyn Hittable>,
+    bbox: Option<Aabb>,
+}
+
+impl Csg {
+    pub fn new(operation: Operation, left: Arc<dyn Hittable>, right: Arc<dyn Hittable>) -> Self {
+        let (left_box, right_box) = (left.bounding_box(), right.bounding_box());
+        let bbox = match operation {
+            Operation::Union => match (left_box, right_box) {
+                (Some(left_box), Some(right_box)) => Some(Aabb::surrounding_box(left_box, right_box)),
+                _ => None,
+            },
+            // An unbounded solid (e.g. the half space behind a plane) leaves the other's box
+            Operation::Intersection => match (left_box, right_box) {
+                (Some(left_box), Some(right_box)) => Some(overlap(left_box, right_box)),
+                (Some(bbox), None) | (None, Some(bbox)) => Some(bbox),
+                (None, None) => None,
+            },
+            Operation::Difference => left_box,
+        };
+        Csg { operation, left, right, bbox }
+    }
+}
+
+// Common part of two boxes, collapsed onto a corner when they do not overlap
+fn overlap(a: Aabb, b: Aabb) -> Aabb {
+    let mut minimum = a.minimum;
+    let mut maximum = a.maximum;
+    for axis in 0..3 {
+        minimum[axis] = a.minimum[axis].max(b.minimum[axis]);
+        maximum[axis] = a.maximum[axis].min(b.maximum[axis]).max(minimum[axis]);
+    }
+    Aabb::new(minimum, maximum)
+}
+
+// Where the ray crosses the surface of one of the solids
+struct Crossing {
+    hit: HitRecord,
+    right: bool,
+    entering: bool,
+}
+
+// Adds the crossings of one solid, and returns whether the ray starts inside it
+fn add_crossings(intervals: Vec<Interval>, right: bool, crossings: &mut Vec<Crossing>) -> bool {
+    let mut starts_inside = false;
+    for interval in intervals {
+        match interval.enter {
+            Some(hit) => crossings.push(Crossing { hit, right, entering: true }),
+            None => starts_inside = true,
+        }
+        if let Some(hit) = interval.exit {
+            crossings.push(Crossing { hit, right, entering: false });
+        }
+    }
+    starts_inside
+}
+
+impl Hittable for Csg {
+    // The first crossing in or out of the result
+    fn hit(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        self.intervals(ray, t_min, t_max)
+            .into_iter()
+            .flat_map(|interval| interval.enter.into_iter().chain(interval.exit))
+            .next()
+    }
+
+    // Merges the crossings of both solids in order along the ray, keeping those where being
+    // inside the result changes
+    fn intervals(&self, ray: Ray, t_min: f64, t_max: f64) -> Vec<Interval> {
+        if let Some(bbox) = self.bbox {
+            if !bbox.hit(ray, t_min, t_max) {
+                return Vec::new();
+            }
+        }
+        let mut crossings = Vec::new();
+        let mut in_left = add_crossings(self.left.intervals(ray, t_min, t_max), false, &mut crossings);
+        let mut in_right = add_crossings(self.right.intervals(ray, t_min, t_max), true, &mut crossings);
+        crossings.sort_by(|a, b| a.hit.t.partial_cmp(&b.hit.t).unwrap());
+
+        let mut inside = self.operation.contains(in_left, in_right);
+        let mut enter = None;
+        let mut intervals = Vec::new();
+        for crossing in crossings {
+            if crossing.right {
+                in_right = crossing.entering;
+            } else {
+                in_left = crossing.entering;
+            }
+            if self.operation.contains(in_left, in_right) == inside {
+                continue;
+            }
+            inside = !inside;
+            let mut hit = crossing.hit;
+            if crossing.right && self.operation == Operation::Difference {
+                hit.normal = -hit.normal;
+                hit.front_face = !hit.front_face;
+            }
+            if inside {
+                enter = Some(hit);
+            } else {
+                intervals.push(Interval { enter: enter.take(), exit: Some(hit) });
+            }
+        }
+        if inside {
+            intervals.push(Interval { enter, exit: None });
+        }
+        intervals
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bbox
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::Lambertian;
+    use crate::plane::Plane;
+    use crate::point::Point;
+    use crate::quad::Cuboid;
+    use crate::sphere::Sphere;
+    use crate::vec3::Vec3;
+
+    fn sphere(x: f64, radius: f64) -> Arc<dyn Hittable> {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        Arc::new(Sphere::new(Point::new(x, 0.0, 0.0), radius, material))
+    }
+
+    fn spans(object: &dyn Hittable, ray: Ray) -> Vec<(f64, f64)> {
+        let t = |hit: Option<HitRecord>, default: f64| hit.map_or(default, |hit| hit.t);
+        object
+            .intervals(ray, 0.001, f64::INFINITY)
+            .into_iter()
+            .map(|interval| (t(interval.enter, 0.001), t(interval.exit, f64::INFINITY)))
+            .collect()
+    }
+
+    #[test]
+    fn operations_should_combine_intervals() {
+        // Along the x axis, the left sphere covers x in [-2, 0] and the right [-1, 1]
+        let ray = Ray::new(Point::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let union = Csg::new(Operation::Union, sphere(-1.0, 1.0), sphere(0.0, 1.0));
+        assert_eq!(spans(&union, ray), vec![(3.0, 6.0)]);
+        let intersection = Csg::new(Operation::Intersection, sphere(-1.0, 1.0), sphere(0.0, 1.0));
+        assert_eq!(spans(&intersection, ray), vec![(4.0, 5.0)]);
+        let difference = Csg::new(Operation::Difference, sphere(-1.0, 1.0), sphere(0.0, 1.0));
+        assert_eq!(spans(&difference, ray), vec![(3.0, 4.0)]);
+        // Apart, the union is two pieces and the intersection empty
+        let apart = Csg::new(Operation::Union, sphere(-3.0, 1.0), sphere(3.0, 1.0));
+        assert_eq!(spans(&apart, ray), vec![(1.0, 3.0), (7.0, 9.0)]);
+        let nothing = Csg::new(Operation::Intersection, sphere(-3.0, 1.0), sphere(3.0, 1.0));
+        assert!(nothing.hit(ray, 0.001, f64::INFINITY).is_none());
+        // A shell, hollowed out by a smaller sphere
+        let shell = Csg::new(Operation::Difference, sphere(0.0, 2.0), sphere(0.0, 1.0));
+        assert_eq!(spans(&shell, ray), vec![(3.0, 4.0), (6.0, 7.0)]);
+    }
+
+    #[test]
+    fn lens_should_have_both_caps() {
+        // Two spheres of radius 5 overlapping across x in [-1, 1]
+        let lens = Csg::new(Operation::Intersection, sphere(4.0, 5.0), sphere(-4.0, 5.0));
+        let ray = Ray::new(Point::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let hit = lens.hit(ray, 0.001, f64::INFINITY).unwrap();
+        assert_eq!(hit.t, 4.0);
+        assert_eq!(hit.normal, Vec3::new(-1.0, 0.0, 0.0));
+        assert!(hit.front_face);
+        // From inside the lens, out through the other cap
+        let hit = lens.hit(ray, 5.0, f64::INFINITY).unwrap();
+        assert_eq!(hit.t, 6.0);
+        assert_eq!(hit.normal, Vec3::new(1.0, 0.0, 0.0));
+        assert!(!hit.front_face);
+        let bbox = lens.bounding_box().unwrap();
+        assert_eq!((bbox.minimum.x, bbox.maximum.x), (-1.0, 1.0));
+        assert_eq!((bbox.minimum.y, bbox.maximum.y), (-5.0, 5.0));
+    }
+
+    #[test]
+    fn cut_surfaces_should_face_out_of_the_result() {
+        // A cube with a sphere scooped out of its +x face
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let cube = Arc::new(Cuboid::new(Point::new(-1.0, -1.0, -1.0), Point::new(1.0, 1.0, 1.0), material));
+        let scooped = Csg::new(Operation::Difference, cube, sphere(1.0, 0.5));
+        let ray = Ray::new(Point::new(5.0, 0.0, 0.0), Vec3::new(-1.0, 0.0, 0.0));
+        let hit = scooped.hit(ray, 0.001, f64::INFINITY).unwrap();
+        assert_eq!(hit.t, 4.5);
+        // The inside of the sphere, facing back towards the ray
+        assert_eq!(hit.normal, Vec3::new(1.0, 0.0, 0.0));
+        assert!(hit.front_face);
+        // Beside the scoop, the cube's own face
+        let beside = Ray::new(Point::new(5.0, 0.8, 0.0), Vec3::new(-1.0, 0.0, 0.0));
+        assert_eq!(scooped.hit(beside, 0.001, f64::INFINITY).unwrap().t, 4.0);
+    }
+
+    #[test]
+    fn plane_should_cut_like_a_half_space() {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let floor: Arc<dyn Hittable> = Arc::new(Plane::new(Point::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), material));
+        let dome = Csg::new(Operation::Difference, sphere(0.0, 1.0), floor.clone());
+        let down = Vec3::new(0.0, -1.0, 0.0);
+        // Over the top of the dome, down to the flat cut
+        assert_eq!(spans(&dome, Ray::new(Point::new(0.0, 5.0, 0.0), down)), vec![(4.0, 5.0)]);
+        // A ray along the cut plane, just below, never crosses the plane but is always behind it
+        let below = Ray::new(Point::new(-5.0, -0.1, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!(dome.hit(below, 0.001, f64::INFINITY).is_none());
+        let bowl = Csg::new(Operation::Intersection, sphere(0.0, 1.0), floor);
+        assert_eq!(spans(&bowl, below).len(), 1);
+        assert!(bowl.bounding_box().is_some());
+    }
+
+    #[test]
+    fn nested_operations_should_combine() {
+        let lens = Arc::new(Csg::new(Operation::Intersection, sphere(4.0, 5.0), sphere(-4.0, 5.0)));
+        let pierced = Csg::new(Operation::Difference, lens, sphere(0.0, 0.5));
+        let ray = Ray::new(Point::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(spans(&pierced, ray), vec![(4.0, 4.5), (5.5, 6.0)]);
+    }
+}
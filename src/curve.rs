@@ -0,0 +1,355 @@
+// Bezier curves, for hair, fur and grass
+//
+// A cubic Bezier curve through four control points, swept into a thin strip whose width
+// changes steadily from one end to the other. As in pbrt, the strip always faces the ray: the
+// curve is split in halves until each piece is nearly straight, pieces whose bounds the ray
+// misses are skipped, and the ray is tested against the straight pieces left.
+//
+// u runs along the curve and v across it. The tangent is the direction of the curve, which
+// hair shading needs more than the normal.
+
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::aabb::Aabb;
+use crate::cylinder::Frame;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::plane::plane_basis;
+use crate::point::Point;
+use crate::ray::Ray;
+use crate::utils;
+use crate::vec3::Vec3;
+
+// Most halvings of a curve, 2^10 pieces
+const MAX_DEPTH: u32 = 10;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CurveShape {
+    Flat,     // A ribbon, facing the ray
+    Cylinder, // Shaded as a round tube, with the normal turning across the width
+}
+
+pub struct Curve {
+    points: [Point; 4],
+    widths: (f64, f64), // At the start and at the end
+    shape: CurveShape,
+    // Halvings needed for the pieces to be straight to within a twentieth of the width
+    depth: u32,
+    material: Arc<dyn Material>,
+}
+
+impl Curve {
+    // The control points must not all be the same
+    pub fn new(points: [Point; 4], widths: (f64, f64), shape: CurveShape, material: Arc<dyn Material>) -> Self {
+        // Each halving divides how far the control points stray from a straight line by 4
+        let bend = (0..2)
+            .map(|i| points[i] - 2.0 * points[i + 1] + points[i + 2])
+            .map(|second| second.x.abs().max(second.y.abs()).max(second.z.abs()))
+            .fold(0.0, f64::max);
+        let tolerance = widths.0.max(widths.1) / 20.0;
+        let depth = if bend == 0.0 {
+            0
+        } else {
+            utils::clamp((2f64.sqrt() * 6.0 * bend / (8.0 * tolerance)).log2() / 2.0, 0.0, MAX_DEPTH as f64) as u32
+        };
+        Curve { points, widths, shape, depth, material }
+    }
+
+    fn width(&self, u: f64) -> f64 {
+        (1.0 - u) * self.widths.0 + u * self.widths.1
+    }
+
+    // Unit direction of the curve at u
+    fn tangent(&self, u: f64) -> Vec3 {
+        let derivative = bezier_derivative(&self.points, u);
+        if derivative.length_squared() >= 1e-24 {
+            return derivative.unit_vector();
+        }
+        // Control points on top of each other stop the curve at its ends, where it heads for
+        // the nearest control point that differs. They cannot all be the same.
+        let p = &self.points;
+        let chords = if u < 0.5 {
+            [p[1] - p[0], p[2] - p[0], p[3] - p[0]]
+        } else {
+            [p[3] - p[2], p[3] - p[1], p[3] - p[0]]
+        };
+        chords
+            .iter()
+            .find(|chord| chord.length_squared() >= 1e-24)
+            .map_or(Vec3::new(1.0, 0.0, 0.0), |chord| chord.unit_vector())
+    }
+
+    // Nearest hit on the piece of the curve between u_range, with control points in the ray's
+    // frame, where the ray runs along y from the origin: its distance along the ray and u
+    fn subdivide(&self, points: [Vec3; 4], u_range: (f64, f64), depth: u32, near: f64, far: f64) -> Option<(f64, f64)> {
+        let half_width = 0.5 * self.width(u_range.0).max(self.width(u_range.1));
+        let bounds = points.iter().fold(Aabb::new(points[0], points[0]), |bbox, &p| Aabb::surrounding_box(bbox, Aabb::new(p, p)));
+        if bounds.minimum.x - half_width > 0.0
+            || bounds.maximum.x + half_width < 0.0
+            || bounds.minimum.z - half_width > 0.0
+            || bounds.maximum.z + half_width < 0.0
+            || bounds.maximum.y + half_width < near
+            || bounds.minimum.y - half_width > far
+        {
+            return None;
+        }
+
+        if depth > 0 {
+            let (first, second) = split(&points);
+            let middle = 0.5 * (u_range.0 + u_range.1);
+            let first_hit = self.subdivide(first, (u_range.0, middle), depth - 1, near, far);
+            let far = first_hit.map_or(far, |(distance, _)| distance);
+            return self.subdivide(second, (middle, u_range.1), depth - 1, near, far).or(first_hit);
+        }
+
+        // The ray must pass between the lines across the piece at its ends
+        let across = |v: Vec3| Vec3::new(v.x, 0.0, v.z);
+        let (start, end) = (across(points[0]), across(points[3]));
+        if (across(points[1]) - start).dot(-start) < 0.0 || (across(points[2]) - end).dot(-end) < 0.0 {
+            return None;
+        }
+        // Nearest point to the ray of the piece, taken as straight
+        let segment = end - start;
+        let w = utils::clamp((-start).dot(segment) / segment.length_squared(), 0.0, 1.0);
+        let u = (1.0 - w) * u_range.0 + w * u_range.1;
+        let center = bezier(&points, w);
+        let width = self.width(u);
+        if center.x * center.x + center.z * center.z > 0.25 * width * width {
+            return None;
+        }
+        // Hits closer than half the width are the curve itself, seen again by a ray leaving it
+        if center.y <= near.max(0.5 * width) || center.y >= far {
+            return None;
+        }
+        Some((center.y, u))
+    }
+}
+
+// Point at u on the cubic Bezier curve with control points p
+fn bezier(p: &[Vec3; 4], u: f64) -> Vec3 {
+    let s = 1.0 - u;
+    s * s * s * p[0] + 3.0 * s * s * u * p[1] + 3.0 * s * u * u * p[2] + u * u * u * p[3]
+}
+
+fn bezier_derivative(p: &[Vec3; 4], u: f64) -> Vec3 {
+    let s = 1.0 - u;
+    3.0 * s * s * (p[1] - p[0]) + 6.0 * s * u * (p[2] - p[1]) + 3.0 * u * u * (p[3] - p[2])
+}
+
+// Control points of the two halves of the curve (de Casteljau)
+fn split(p: &[Vec3; 4]) -> ([Vec3; 4], [Vec3; 4]) {
+    let half = |a: Vec3, b: Vec3| 0.5 * (a + b);
+    let (a, b, c) = (half(p[0], p[1]), half(p[1], p[2]), half(p[2], p[3]));
+    let (d, e) = (half(a, b), half(b, c));
+    let middle = half(d, e);
+    ([p[0], a, d, middle], [middle, e, c, p[3]])
+}
+
+impl Hittable for Curve {
+    fn hit(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let length = ray.direction.length();
+        let frame = Frame::new(ray.origin, ray.direction);
+        let local = self.points.map(|p| frame.local_vector(p - ray.origin));
+        let (distance, u) = self.subdivide(local, (0.0, 1.0), self.depth, t_min * length, t_max * length)?;
+        let t = distance / length;
+
+        // Facing the ray, square to the curve
+        let tangent = self.tangent(u);
+        let facing = -ray.direction.unit_vector();
+        let facing = facing - facing.dot(tangent) * tangent;
+        // A ray along the curve, e.g. looking straight down on the foot of a blade of grass,
+        // sees it end on, any way across will do
+        let facing = if facing.length_squared() < 1e-12 { plane_basis(tangent).0 } else { facing.unit_vector() };
+        let side = tangent.cross(facing);
+        // From -1 to 1 across the width
+        let across = (ray.at(t) - bezier(&self.points, u)).dot(side) / (0.5 * self.width(u));
+        let across = utils::clamp(across, -1.0, 1.0);
+        let normal = match self.shape {
+            CurveShape::Flat => facing,
+            CurveShape::Cylinder => (1.0 - across * across).sqrt() * facing + across * side,
+        };
+        let mut hit = HitRecord::new(ray, t, normal, (u, 0.5 * (across + 1.0)), self.material.clone());
+        hit.tangent = Some(tangent);
+        Some(hit)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // The curve lies within the hull of its control points
+        let half_width = 0.5 * self.widths.0.max(self.widths.1);
+        let pad = Vec3::new(half_width, half_width, half_width);
+        let minimum = self.points.iter().fold(self.points[0], |m, p| Vec3::new(m.x.min(p.x), m.y.min(p.y), m.z.min(p.z)));
+        let maximum = self.points.iter().fold(self.points[0], |m, p| Vec3::new(m.x.max(p.x), m.y.max(p.y), m.z.max(p.z)));
+        Some(Aabb::new(minimum - pad, maximum + pad))
+    }
+}
+
+// Blades of grass scattered over the rectangle from corner to corner + (size.0, 0, size.1),
+// flat curves up to height tall, bending over in random directions
+pub fn grass(
+    corner: Point,
+    size: (f64, f64),
+    count: usize,
+    height: f64,
+    widths: (f64, f64),
+    seed: u64,
+    material: Arc<dyn Material>,
+) -> Vec<Arc<dyn Hittable>> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    (0..count)
+        .map(|_| {
+            let base = corner + Vec3::new(rng.gen_range(0.0, size.0), 0.0, rng.gen_range(0.0, size.1));
+            let tall = height * rng.gen_range(0.5, 1.0);
+            let angle = rng.gen_range(0.0, 2.0 * PI);
+            let lean = tall * rng.gen_range(0.1, 0.5) * Vec3::new(angle.cos(), 0.0, angle.sin());
+            let up = Vec3::new(0.0, tall, 0.0);
+            let points = [base, base + up / 3.0, base + 2.0 * up / 3.0 + 0.5 * lean, base + 0.9 * up + lean];
+            Arc::new(Curve::new(points, widths, CurveShape::Flat, material.clone())) as Arc<dyn Hittable>
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::{Hair, Lambertian};
+    use crate::texture::SolidColor;
+
+    fn curve(points: [Point; 4], widths: (f64, f64), shape: CurveShape) -> Curve {
+        Curve::new(points, widths, shape, Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))))
+    }
+
+    fn hair(roughness: f64) -> Arc<dyn Material> {
+        Arc::new(Hair::textured(Arc::new(SolidColor::new(Color::new(0.8, 0.6, 0.3))), roughness))
+    }
+
+    fn straight(widths: (f64, f64), shape: CurveShape) -> Curve {
+        let points = [0.0, 1.0, 2.0, 3.0].map(|x| Point::new(x, 0.0, 0.0));
+        curve(points, widths, shape)
+    }
+
+    #[test]
+    fn straight_curve_should_be_a_strip() {
+        let flat = straight((0.2, 0.2), CurveShape::Flat);
+        let down = Vec3::new(0.0, -1.0, 0.0);
+        let hit = flat.hit(Ray::new(Point::new(1.5, 5.0, 0.0), down), 0.001, f64::INFINITY).unwrap();
+        assert!((hit.t - 5.0).abs() < 1e-9);
+        assert!((hit.u - 0.5).abs() < 1e-9 && (hit.v - 0.5).abs() < 1e-9);
+        assert!((hit.normal - Vec3::new(0.0, 1.0, 0.0)).length() < 1e-9);
+        assert!((hit.tangent.unwrap() - Vec3::new(1.0, 0.0, 0.0)).length() < 1e-9);
+        assert!(flat.hit(Ray::new(Point::new(1.5, 5.0, 0.09), down), 0.001, f64::INFINITY).is_some());
+        assert!(flat.hit(Ray::new(Point::new(1.5, 5.0, 0.11), down), 0.001, f64::INFINITY).is_none());
+        // Past the ends
+        assert!(flat.hit(Ray::new(Point::new(-0.05, 5.0, 0.0), down), 0.001, f64::INFINITY).is_none());
+        assert!(flat.hit(Ray::new(Point::new(3.05, 5.0, 0.0), down), 0.001, f64::INFINITY).is_none());
+
+        // Halfway to the side of a tube, the normal is turned 30 degrees that way
+        let tube = straight((0.2, 0.2), CurveShape::Cylinder);
+        let hit = tube.hit(Ray::new(Point::new(1.5, 5.0, 0.05), down), 0.001, f64::INFINITY).unwrap();
+        assert!((hit.normal - Vec3::new(0.0, 0.75f64.sqrt(), 0.5)).length() < 1e-9, "{:?}", hit.normal);
+        assert!((hit.v - 0.75).abs() < 1e-9);
+        assert!(hit.front_face);
+    }
+
+    #[test]
+    fn rays_along_the_curve_should_have_a_normal() {
+        // Looking straight down on the foot of a blade, along its tangent there
+        let points = [Point::new(0.0, 0.0, 0.0), Point::new(0.0, 1.0, 0.0), Point::new(0.0, 2.0, 0.0), Point::new(1.0, 3.0, 0.0)];
+        let blade = curve(points, (0.1, 0.1), CurveShape::Cylinder);
+        let hit = blade.hit(Ray::new(Point::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0)), 0.001, f64::INFINITY).unwrap();
+        assert!((hit.normal.length() - 1.0).abs() < 1e-9, "{:?}", hit.normal);
+        // A foot with its first control points together
+        let points = [Point::new(0.0, 0.0, 0.0), Point::new(0.0, 0.0, 0.0), Point::new(0.0, 1.0, 0.0), Point::new(0.0, 1.0, 0.0)];
+        let stub = curve(points, (0.1, 0.1), CurveShape::Flat);
+        assert_eq!(stub.tangent(0.0), Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(stub.tangent(1.0), Vec3::new(0.0, 1.0, 0.0));
+        let hit = stub.hit(Ray::new(Point::new(0.0, 0.5, 5.0), Vec3::new(0.0, 0.0, -1.0)), 0.001, f64::INFINITY).unwrap();
+        assert!((hit.normal - Vec3::new(0.0, 0.0, 1.0)).length() < 1e-9);
+    }
+
+    #[test]
+    fn width_should_taper_along_the_curve() {
+        let tapered = straight((0.4, 0.0), CurveShape::Flat);
+        let down = Vec3::new(0.0, -1.0, 0.0);
+        // 0.35 wide a quarter of the way along, 0.1 at the far end
+        assert!(tapered.hit(Ray::new(Point::new(0.375, 5.0, 0.15), down), 0.001, f64::INFINITY).is_some());
+        assert!(tapered.hit(Ray::new(Point::new(2.625, 5.0, 0.15), down), 0.001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn bent_curve_should_be_hit_along_it() {
+        let points = [Point::new(0.0, 0.0, 0.0), Point::new(0.0, 2.0, 0.0), Point::new(1.0, 3.0, 0.5), Point::new(3.0, 3.0, 1.0)];
+        let bent = curve(points, (0.05, 0.05), CurveShape::Cylinder);
+        assert!(bent.depth > 0);
+        let bbox = bent.bounding_box().unwrap();
+        for &u in &[0.1, 0.5, 0.9] {
+            let on = bezier(&points, u);
+            assert!(bbox.hit(Ray::new(on, Vec3::new(0.0, 0.0, 1.0)), 0.0, 1e-9));
+            let ray = Ray::new(on + Vec3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+            let hit = bent.hit(ray, 0.001, f64::INFINITY).unwrap();
+            assert!((hit.point - on).length() < 0.025, "{:?} {:?}", hit.point, on);
+            assert!((hit.u - u).abs() < 0.01);
+            assert!(hit.tangent.unwrap().dot(bezier_derivative(&points, u).unit_vector()) > 0.999);
+            // A ray leaving the hit does not find the curve again straight away
+            for &direction in &[Vec3::new(0.3, 0.2, 1.0), Vec3::new(-0.3, 0.5, 1.0), Vec3::new(1.0, 0.0, 0.1)] {
+                assert!(bent.hit(Ray::new(hit.point, direction), 0.001, 0.1).is_none());
+            }
+        }
+    }
+
+    #[test]
+    fn hair_should_reflect_around_the_fiber() {
+        let fiber = Curve::new(
+            [0.0, 1.0, 2.0, 3.0].map(|x| Point::new(x, 0.0, 0.0)),
+            (0.1, 0.1),
+            CurveShape::Flat,
+            hair(0.0),
+        );
+        // Coming down and along the fiber, light keeps going along it, around it on any side
+        let ray = Ray::new(Point::new(0.0, 5.0, 0.0), Vec3::new(1.5, -5.0, 0.0));
+        let hit = fiber.hit(ray, 0.001, f64::INFINITY).unwrap();
+        let along = ray.direction.unit_vector().x;
+        let mut sides = Vec::new();
+        for _ in 0..20 {
+            let scattered = hit.material.scatter(ray, &hit).unwrap().direction.unit_vector();
+            assert!((scattered.x - along).abs() < 1e-9, "{:?}", scattered);
+            sides.push(scattered.z);
+        }
+        // Spread across the fiber rather than mirrored off the ribbon
+        assert!(sides.iter().any(|&z| z > 0.1) && sides.iter().any(|&z| z < -0.1));
+    }
+
+    #[test]
+    fn rough_hair_should_scatter_every_ray() {
+        let fiber = Curve::new(
+            [0.0, 1.0, 2.0, 3.0].map(|x| Point::new(x, 0.0, 0.0)),
+            (0.1, 0.1),
+            CurveShape::Flat,
+            hair(1.0),
+        );
+        let ray = Ray::new(Point::new(0.0, 5.0, 0.0), Vec3::new(1.5, -5.0, 0.0));
+        let hit = fiber.hit(ray, 0.001, f64::INFINITY).unwrap();
+        // Nothing is absorbed, and light leaves on both sides of the ribbon
+        let below = (0..200)
+            .map(|_| hit.material.scatter(ray, &hit).expect("rough hair absorbed a ray").direction)
+            .filter(|direction| direction.y < 0.0)
+            .count();
+        assert!(below > 0 && below < 200, "{}", below);
+    }
+
+    #[test]
+    fn grass_should_grow_over_the_rectangle() {
+        let material = Arc::new(Lambertian::new(Color::new(0.2, 0.6, 0.1)));
+        let blades = grass(Point::new(0.0, 0.0, 0.0), (2.0, 1.0), 50, 0.5, (0.02, 0.0), 1, material);
+        assert_eq!(blades.len(), 50);
+        for blade in blades {
+            let bbox = blade.bounding_box().unwrap();
+            assert!(bbox.minimum.y > -0.02 && bbox.maximum.y < 0.5);
+            assert!(bbox.minimum.x > -0.3 && bbox.maximum.x < 2.3 && bbox.minimum.z > -0.3 && bbox.maximum.z < 1.3);
+        }
+    }
+}
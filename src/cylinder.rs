@@ -0,0 +1,426 @@
+// Shapes of revolution around a segment: cylinders, cones and capsules
+//
+// Each is intersected in a local frame where the segment runs up the y axis from the origin,
+// so the side is a quadric in x, y and z. The frame is orthonormal, so distances along the ray
+// are the same in both frames.
+//
+// On the sides, u is the angle around the axis (as for spheres, with the local x and z axes)
+// and v goes from 0 at the base to 1 at the top. Caps are disks with their own uvs.
+
+use crate::aabb::Aabb;
+use crate::point::Point;
+use crate::ray::Ray;
+use crate::utils;
+use crate::hittable::{HitRecord, Hittable};
+use crate::vec3::Vec3;
+use crate::material::Material;
+use crate::plane::{plane_basis, Disk};
+use crate::sphere::{sphere_tangent, sphere_uv};
+
+use std::sync::Arc;
+
+// Orthonormal frame with y along the axis of a shape
+#[derive(Copy, Clone)]
+pub struct Frame {
+    pub origin: Point,
+    pub x: Vec3,
+    pub y: Vec3,
+    pub z: Vec3,
+}
+
+impl Frame {
+    pub fn new(origin: Point, axis: Vec3) -> Self {
+        let y = axis.unit_vector();
+        let (x, z) = plane_basis(y);
+        Frame { origin, x, y, z }
+    }
+
+    pub fn local_vector(&self, v: Vec3) -> Vec3 {
+        Vec3::new(v.dot(self.x), v.dot(self.y), v.dot(self.z))
+    }
+
+    pub fn local_ray(&self, ray: Ray) -> Ray {
+        Ray::with_time(self.local_vector(ray.origin - self.origin), self.local_vector(ray.direction), ray.time)
+    }
+
+    pub fn world_vector(&self, v: Vec3) -> Vec3 {
+        v.x * self.x + v.y * self.y + v.z * self.z
+    }
+}
+
+// Nearest root of a t^2 + b t + c = 0 within (t_min, t_max) whose point passes accept
+// A ray parallel to the surface has a = 0, leaving b t + c = 0.
+fn nearest_root(
+    a: f64,
+    b: f64,
+    c: f64,
+    ray: Ray,
+    t_min: f64,
+    t_max: f64,
+    accept: impl Fn(Point) -> bool,
+) -> Option<f64> {
+    let roots = if a.abs() < 1e-12 * ray.direction.length_squared() {
+        if b == 0.0 {
+            return None;
+        }
+        (-c / b, -c / b)
+    } else {
+        utils::quadratic_solver(a, b, c)?
+    };
+    let (near, far) = if roots.0 <= roots.1 { roots } else { (roots.1, roots.0) };
+    [near, far]
+        .iter()
+        .copied()
+        .find(|&t| t > t_min && t < t_max && accept(ray.at(t)))
+}
+
+// Nearest of two optional hits
+fn nearer(a: Option<HitRecord>, b: Option<HitRecord>) -> Option<HitRecord> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(if a.t <= b.t { a } else { b }),
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
+
+// Hit on a side at local point p with local outward normal, v running along the axis
+fn side_hit(
+    frame: &Frame,
+    ray: Ray,
+    t: f64,
+    p: Point,
+    normal: Vec3,
+    v: f64,
+    material: &Arc<dyn Material>,
+) -> HitRecord {
+    let around = Vec3::new(p.x, 0.0, p.z);
+    let (u, _) = sphere_uv(around);
+    let mut hit = HitRecord::new(ray, t, frame.world_vector(normal.unit_vector()), (u, v), material.clone());
+    hit.tangent = Some(frame.world_vector(sphere_tangent(around)));
+    hit
+}
+
+// Box around a disk of radius centered on point, perpendicular to axis
+pub fn disk_box(center: Point, axis: Vec3, radius: f64) -> Aabb {
+    let axis = axis.unit_vector();
+    let mut extent = Vec3::new(0.0, 0.0, 0.0);
+    for i in 0..3 {
+        extent[i] = radius * (1.0 - axis[i] * axis[i]).max(0.0).sqrt();
+    }
+    Aabb::new(center - extent, center + extent)
+}
+
+pub struct Cylinder {
+    frame: Frame,
+    height: f64,
+    radius: f64,
+    // Bottom and top, if capped
+    caps: Option<(Disk, Disk)>,
+    material: Arc<dyn Material>,
+}
+
+impl Cylinder {
+    // Cylinder around the segment from base to top
+    // Without caps it is an open tube, seen from inside through the ends.
+    pub fn new(base: Point, top: Point, radius: f64, capped: bool, material: Arc<dyn Material>) -> Self {
+        let axis = top - base;
+        let caps = if capped {
+            Some((
+                Disk::new(base, -axis, radius, material.clone()),
+                Disk::new(top, axis, radius, material.clone()),
+            ))
+        } else {
+            None
+        };
+        Cylinder { frame: Frame::new(base, axis), height: axis.length(), radius, caps, material }
+    }
+}
+
+impl Hittable for Cylinder {
+    // Side: x^2 + z^2 = r^2 for 0 <= y <= height
+    fn hit(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let local = self.frame.local_ray(ray);
+        let (o, d) = (local.origin, local.direction);
+        let a = d.x * d.x + d.z * d.z;
+        let b = 2.0 * (o.x * d.x + o.z * d.z);
+        let c = o.x * o.x + o.z * o.z - self.radius * self.radius;
+        let height = self.height;
+        let side = nearest_root(a, b, c, local, t_min, t_max, |p| p.y >= 0.0 && p.y <= height).map(|t| {
+            let p = local.at(t);
+            let normal = Vec3::new(p.x, 0.0, p.z);
+            side_hit(&self.frame, ray, t, p, normal, p.y / height, &self.material)
+        });
+
+        let (bottom, top) = match &self.caps {
+            Some(caps) => caps,
+            None => return side,
+        };
+        let closest_so_far = side.as_ref().map_or(t_max, |hit| hit.t);
+        let cap = nearer(bottom.hit(ray, t_min, closest_so_far), top.hit(ray, t_min, closest_so_far));
+        nearer(side, cap)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let top = self.frame.origin + self.height * self.frame.y;
+        let bottom = disk_box(self.frame.origin, self.frame.y, self.radius);
+        Some(Aabb::surrounding_box(bottom, disk_box(top, self.frame.y, self.radius)).padded(1e-4))
+    }
+}
+
+pub struct Cone {
+    frame: Frame,
+    height: f64,
+    radius: f64,
+    base: Option<Disk>,
+    material: Arc<dyn Material>,
+}
+
+impl Cone {
+    // Cone narrowing from a disk of radius around base to a point at apex
+    pub fn new(base: Point, apex: Point, radius: f64, capped: bool, material: Arc<dyn Material>) -> Self {
+        let axis = apex - base;
+        let cap = if capped { Some(Disk::new(base, -axis, radius, material.clone())) } else { None };
+        Cone { frame: Frame::new(base, axis), height: axis.length(), radius, base: cap, material }
+    }
+}
+
+impl Hittable for Cone {
+    // Side: x^2 + z^2 = k^2 (height - y)^2 for 0 <= y <= height, with slope k = radius / height
+    // Substituting the ray, with w = height - o.y:
+    // a = d.x^2 + d.z^2 - k^2 d.y^2
+    // b = 2 (o.x d.x + o.z d.z + k^2 w d.y)
+    // c = o.x^2 + o.z^2 - k^2 w^2
+    fn hit(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let local = self.frame.local_ray(ray);
+        let (o, d) = (local.origin, local.direction);
+        let k2 = (self.radius / self.height).powi(2);
+        let w = self.height - o.y;
+        let a = d.x * d.x + d.z * d.z - k2 * d.y * d.y;
+        let b = 2.0 * (o.x * d.x + o.z * d.z + k2 * w * d.y);
+        let c = o.x * o.x + o.z * o.z - k2 * w * w;
+        let height = self.height;
+        let side = nearest_root(a, b, c, local, t_min, t_max, |p| p.y >= 0.0 && p.y <= height).map(|t| {
+            let p = local.at(t);
+            // Gradient of x^2 + z^2 - k^2 (height - y)^2, which vanishes at the apex
+            let mut normal = Vec3::new(p.x, k2 * (height - p.y), p.z);
+            if normal.length_squared() < 1e-24 {
+                normal = Vec3::new(0.0, 1.0, 0.0);
+            }
+            side_hit(&self.frame, ray, t, p, normal, p.y / height, &self.material)
+        });
+
+        let base = match &self.base {
+            Some(base) => base,
+            None => return side,
+        };
+        let closest_so_far = side.as_ref().map_or(t_max, |hit| hit.t);
+        nearer(side, base.hit(ray, t_min, closest_so_far))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let apex = self.frame.origin + self.height * self.frame.y;
+        let base = disk_box(self.frame.origin, self.frame.y, self.radius);
+        Some(Aabb::surrounding_box(base, Aabb::new(apex, apex)).padded(1e-4))
+    }
+}
+
+// Every point within radius of the segment from a to b: a cylinder closed by two hemispheres
+pub struct Capsule {
+    frame: Frame,
+    height: f64,
+    radius: f64,
+    material: Arc<dyn Material>,
+}
+
+impl Capsule {
+    pub fn new(a: Point, b: Point, radius: f64, material: Arc<dyn Material>) -> Self {
+        let axis = b - a;
+        // A capsule over a single point is a sphere, any axis will do
+        let direction = if axis.length_squared() > 0.0 { axis } else { Vec3::new(0.0, 1.0, 0.0) };
+        Capsule { frame: Frame::new(a, direction), height: axis.length(), radius, material }
+    }
+}
+
+impl Hittable for Capsule {
+    // The side between 0 <= y <= height, the sphere around the base below it and the one around
+    // the top above it. v runs along the whole length including the hemispheres.
+    fn hit(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let local = self.frame.local_ray(ray);
+        let (o, d) = (local.origin, local.direction);
+        let (height, radius) = (self.height, self.radius);
+        let v = |y: f64| (y + radius) / (height + 2.0 * radius);
+
+        let a = d.x * d.x + d.z * d.z;
+        let b = 2.0 * (o.x * d.x + o.z * d.z);
+        let c = o.x * o.x + o.z * o.z - radius * radius;
+        let side = nearest_root(a, b, c, local, t_min, t_max, |p| p.y >= 0.0 && p.y <= height).map(|t| {
+            let p = local.at(t);
+            side_hit(&self.frame, ray, t, p, Vec3::new(p.x, 0.0, p.z), v(p.y), &self.material)
+        });
+
+        // Ends: |p - center|^2 = r^2, keeping the half beyond the segment
+        let end = |center_y: f64, below: bool| {
+            let oc = o - Vec3::new(0.0, center_y, 0.0);
+            let (a, b, c) = (d.dot(d), 2.0 * oc.dot(d), oc.dot(oc) - radius * radius);
+            nearest_root(a, b, c, local, t_min, t_max, |p| if below { p.y < 0.0 } else { p.y > height }).map(|t| {
+                let p = local.at(t);
+                let normal = p - Vec3::new(0.0, center_y, 0.0);
+                side_hit(&self.frame, ray, t, p, normal, v(p.y), &self.material)
+            })
+        };
+        nearer(side, nearer(end(0.0, true), end(height, false)))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = Vec3::new(self.radius, self.radius, self.radius);
+        let (a, b) = (self.frame.origin, self.frame.origin + self.height * self.frame.y);
+        Some(Aabb::surrounding_box(Aabb::new(a - r, a + r), Aabb::new(b - r, b + r)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::Lambertian;
+
+    fn material() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))
+    }
+
+    fn assert_close(actual: Vec3, expected: Vec3) {
+        assert!((actual - expected).length() < 1e-9, "{:?} != {:?}", actual, expected);
+    }
+
+    // Upright unit cylinder from y = 0 to 2
+    fn cylinder(capped: bool) -> Cylinder {
+        Cylinder::new(Point::new(0.0, 0.0, 0.0), Point::new(0.0, 2.0, 0.0), 1.0, capped, material())
+    }
+
+    #[test]
+    fn cylinder_should_hit_side_and_caps() {
+        let capped = cylinder(true);
+        let ray = Ray::new(Point::new(-3.0, 1.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let hit = capped.hit(ray, 0.001, f64::INFINITY).unwrap();
+        assert_eq!(hit.t, 2.0);
+        assert_close(hit.normal, Vec3::new(-1.0, 0.0, 0.0));
+        assert!((hit.v - 0.5).abs() < 1e-12);
+
+        let down = Ray::new(Point::new(0.5, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let hit = capped.hit(down, 0.001, f64::INFINITY).unwrap();
+        assert_eq!(hit.t, 3.0);
+        assert_close(hit.normal, Vec3::new(0.0, 1.0, 0.0));
+        // Open tubes are seen through
+        assert!(cylinder(false).hit(down, 0.001, f64::INFINITY).is_none());
+        // From inside the open tube, the far wall from behind
+        let across = Ray::new(Point::new(0.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 1.0));
+        let hit = cylinder(false).hit(across, 0.001, f64::INFINITY).unwrap();
+        assert_eq!(hit.t, 1.0);
+        assert!(!hit.front_face);
+        // Above the top, past the side
+        assert!(capped.hit(Ray::new(Point::new(-3.0, 2.5, 0.0), Vec3::new(1.0, 0.0, 0.0)), 0.001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn grazing_rays_should_touch_the_side() {
+        let tube = cylinder(false);
+        // Tangent to the side at x = 1
+        let ray = Ray::new(Point::new(1.0, 1.0, -3.0), Vec3::new(0.0, 0.0, 1.0));
+        let hit = tube.hit(ray, 0.001, f64::INFINITY).unwrap();
+        assert!((hit.t - 3.0).abs() < 1e-9);
+        assert_close(hit.normal, Vec3::new(1.0, 0.0, 0.0));
+        // Just past it
+        let ray = Ray::new(Point::new(1.0 + 1e-9, 1.0, -3.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(tube.hit(ray, 0.001, f64::INFINITY).is_none());
+        // Along the side, parallel to the axis
+        let ray = Ray::new(Point::new(1.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(tube.hit(ray, 0.001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn cap_edge_rays_should_not_leak() {
+        let capped = cylinder(true);
+        // Straight down on the rim
+        let ray = Ray::new(Point::new(1.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let hit = capped.hit(ray, 0.001, f64::INFINITY).unwrap();
+        assert_eq!(hit.t, 3.0);
+        // Diagonally through the top rim: the side and the cap meet, so rays either side of it
+        // land on one or the other at the rim
+        for offset in [-1e-7, 0.0, 1e-7].iter() {
+            let origin = Point::new(-2.0, 3.0 + offset, 0.0);
+            let ray = Ray::new(origin, Vec3::new(1.0, -1.0, 0.0));
+            let hit = capped.hit(ray, 0.001, f64::INFINITY).unwrap();
+            assert_close(hit.point, Point::new(-1.0 + offset.max(0.0), 2.0 + offset.min(0.0), 0.0));
+        }
+    }
+
+    #[test]
+    fn slanted_cylinder_should_be_bounded() {
+        let cylinder = Cylinder::new(Point::new(1.0, 1.0, 1.0), Point::new(3.0, 3.0, 1.0), 0.5, true, material());
+        let bbox = cylinder.bounding_box().unwrap();
+        for i in 0..100 {
+            let angle = i as f64 * 0.37;
+            let direction = Vec3::new(angle.cos(), angle.sin(), (angle * 0.7).sin());
+            let ray = Ray::new(Point::new(2.0, 2.0, 1.0) - 5.0 * direction, direction);
+            let hit = cylinder.hit(ray, 0.001, f64::INFINITY).unwrap();
+            for axis in 0..3 {
+                assert!(hit.point[axis] >= bbox.minimum[axis] && hit.point[axis] <= bbox.maximum[axis]);
+            }
+            // Every hit is on the surface: within radius of the axis, or on a cap
+            let along = (hit.point - Point::new(1.0, 1.0, 1.0)).dot(Vec3::new(1.0, 1.0, 0.0).unit_vector());
+            let distance = (hit.point - Point::new(1.0, 1.0, 1.0) - along * Vec3::new(1.0, 1.0, 0.0).unit_vector()).length();
+            assert!((distance - 0.5).abs() < 1e-9 || along.abs() < 1e-9 || (along - 8f64.sqrt()).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn cone_should_narrow_to_apex() {
+        let cone = Cone::new(Point::new(0.0, 0.0, 0.0), Point::new(0.0, 1.0, 0.0), 1.0, true, material());
+        // Halfway up the radius is 0.5, and the normal leans up at 45 degrees
+        let ray = Ray::new(Point::new(-3.0, 0.5, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let hit = cone.hit(ray, 0.001, f64::INFINITY).unwrap();
+        assert!((hit.t - 2.5).abs() < 1e-12);
+        assert_close(hit.normal, Vec3::new(-1.0, 1.0, 0.0).unit_vector());
+        assert!((hit.v - 0.5).abs() < 1e-12);
+        // Onto the apex
+        let down = Ray::new(Point::new(0.0, 3.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let hit = cone.hit(down, 0.001, f64::INFINITY).unwrap();
+        assert!((hit.t - 2.0).abs() < 1e-12);
+        assert_close(hit.normal, Vec3::new(0.0, 1.0, 0.0));
+        // Up into the base cap
+        let up = Ray::new(Point::new(0.5, -1.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let hit = cone.hit(up, 0.001, f64::INFINITY).unwrap();
+        assert_eq!(hit.t, 1.0);
+        assert_close(hit.normal, Vec3::new(0.0, -1.0, 0.0));
+        // Parallel to the slope: a = 0, a single root
+        let open = Cone::new(Point::new(0.0, 0.0, 0.0), Point::new(0.0, 1.0, 0.0), 1.0, false, material());
+        let slope = Ray::new(Point::new(-1.5, -1.0, 0.0), Vec3::new(1.0, 1.0, 0.0));
+        let hit = open.hit(slope, 0.001, f64::INFINITY).unwrap();
+        assert_close(hit.point, Point::new(0.25, 0.75, 0.0));
+        assert_eq!(cone.hit(slope, 0.001, f64::INFINITY).unwrap().t, 1.0);
+        // Above the apex, on the mirrored cone
+        assert!(cone.hit(Ray::new(Point::new(-3.0, 1.5, 0.0), Vec3::new(1.0, 0.0, 0.0)), 0.001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn capsule_should_be_rounded_at_the_ends() {
+        let capsule = Capsule::new(Point::new(0.0, 0.0, 0.0), Point::new(0.0, 2.0, 0.0), 1.0, material());
+        let down = Ray::new(Point::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let hit = capsule.hit(down, 0.001, f64::INFINITY).unwrap();
+        assert_eq!(hit.t, 2.0);
+        assert_close(hit.normal, Vec3::new(0.0, 1.0, 0.0));
+        assert!((hit.v - 1.0).abs() < 1e-12);
+        // Off center onto the top hemisphere
+        let ray = Ray::new(Point::new(0.6, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let hit = capsule.hit(ray, 0.001, f64::INFINITY).unwrap();
+        assert_close(hit.point, Point::new(0.6, 2.8, 0.0));
+        assert_close(hit.normal, Vec3::new(0.6, 0.8, 0.0));
+        // Side, and from inside the bottom hemisphere
+        let hit = capsule.hit(Ray::new(Point::new(-3.0, 1.0, 0.0), Vec3::new(1.0, 0.0, 0.0)), 0.001, f64::INFINITY).unwrap();
+        assert_eq!(hit.t, 2.0);
+        let hit = capsule.hit(Ray::new(Point::new(0.0, -0.5, 0.0), Vec3::new(0.0, -1.0, 0.0)), 0.001, f64::INFINITY).unwrap();
+        assert_eq!(hit.t, 0.5);
+        assert!(!hit.front_face);
+        assert_eq!(capsule.bounding_box().unwrap().maximum, Point::new(1.0, 3.0, 1.0));
+    }
+}
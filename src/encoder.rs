@@ -1,14 +1,109 @@
 // PPM specifications are here: http://davis.lbl.gov/Manuals/NETPBM/doc/ppm.html
 // PPM example: https://en.wikipedia.org/wiki/Netpbm#PPM_example
 
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::framebuffer::Framebuffer;
+use crate::png;
+
+// MAX COLOUR VALUE (8 bit)
+pub const MAX_COLOUR_VALUE: i32 = 255;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ImageFormat {
+    Ppm,       // P3, plain text
+    PpmBinary, // P6
+    Png,
+}
+
+impl ImageFormat {
+    // Chosen by file extension, .ppm files are written as plain text
+    pub fn from_path(path: &Path) -> Result<Self, String> {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("ppm") => Ok(ImageFormat::Ppm),
+            Some("png") => Ok(ImageFormat::Png),
+            _ => Err(String::from("unsupported image format, expected a .ppm or .png file")),
+        }
+    }
+
+    // Names accepted by --format
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name {
+            "ppm" => Ok(ImageFormat::Ppm),
+            "ppm-binary" => Ok(ImageFormat::PpmBinary),
+            "png" => Ok(ImageFormat::Png),
+            _ => Err(String::from("expected one of ppm, ppm-binary, png")),
+        }
+    }
+}
+
+pub fn write_image(out: &mut impl Write, format: ImageFormat, framebuffer: &Framebuffer) -> io::Result<()> {
+    let (width, height) = (framebuffer.width(), framebuffer.height());
+    let rgb = framebuffer.to_rgb8();
+    match format {
+        ImageFormat::Ppm => encode_ppm(out, width, height, &rgb),
+        ImageFormat::PpmBinary => encode_ppm_binary(out, width, height, &rgb),
+        ImageFormat::Png => png::encode_png(out, width, height, &rgb),
+    }
+}
+
+// rgb holds 3 bytes per pixel, rows from top to bottom
+pub fn encode_ppm(out: &mut impl Write, width: usize, height: usize, rgb: &[u8]) -> io::Result<()> {
+    ppm_headers(out, "P3", width, height, MAX_COLOUR_VALUE)?;
+    for pixel in rgb.chunks(3) {
+        writeln!(out, "{} {} {}", pixel[0], pixel[1], pixel[2])?;
+    }
+    Ok(())
+}
+
+// Same layout as P3, but the samples are raw bytes
+pub fn encode_ppm_binary(out: &mut impl Write, width: usize, height: usize, rgb: &[u8]) -> io::Result<()> {
+    ppm_headers(out, "P6", width, height, MAX_COLOUR_VALUE)?;
+    out.write_all(rgb)
+}
+
 // Encodes ppm headers
-pub fn ppm_headers(image_pixel_width: i32, image_pixel_height: i32, max_colour_value: i32) {
-    // Write "P3" header for PPM format
-    println!("P3");
+pub fn ppm_headers(out: &mut impl Write, magic_number: &str, image_pixel_width: usize, image_pixel_height: usize, max_colour_value: i32) -> io::Result<()> {
+    // Write "P3" or "P6" header for PPM format
+    writeln!(out, "{}", magic_number)?;
 
     // Write image width, height
-    println!("{} {}", image_pixel_width, image_pixel_height);
+    writeln!(out, "{} {}", image_pixel_width, image_pixel_height)?;
 
     // Write maximum color value
-    println!("{}", max_colour_value);
+    writeln!(out, "{}", max_colour_value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+
+    fn two_pixel_framebuffer() -> Framebuffer {
+        let mut framebuffer = Framebuffer::new(2, 1);
+        framebuffer.add_samples(0, 0, Color::new(1.0, 0.0, 0.25), 1);
+        framebuffer.add_samples(1, 0, Color::new(0.0, 1.0, 0.0), 1);
+        framebuffer
+    }
+
+    #[test]
+    fn ppm_should_encode_framebuffer() {
+        let mut out = vec![];
+        write_image(&mut out, ImageFormat::Ppm, &two_pixel_framebuffer()).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "P3\n2 1\n255\n255 0 128\n0 255 0\n");
+    }
+
+    #[test]
+    fn binary_ppm_should_encode_framebuffer() {
+        let mut out = vec![];
+        write_image(&mut out, ImageFormat::PpmBinary, &two_pixel_framebuffer()).unwrap();
+        let mut expected = b"P6\n2 1\n255\n".to_vec();
+        expected.extend_from_slice(&[255, 0, 128, 0, 255, 0]);
+        assert_eq!(out, expected);
+    }
 }
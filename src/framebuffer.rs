@@ -0,0 +1,88 @@
+use crate::color::Color;
+
+// Rendered image in linear radiance
+// Each pixel keeps the sum of its samples and how many there were,
+// so more samples can be added later and averaging happens only when reading.
+// Rows go from top to bottom, each row from left to right.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    radiance: Vec<Color>,
+    samples: Vec<u32>,
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Framebuffer {
+            width,
+            height,
+            radiance: vec![Color::new(0.0, 0.0, 0.0); width * height],
+            samples: vec![0; width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    fn index(&self, x: usize, y: usize) -> usize {
+        assert!(x < self.width && y < self.height, "pixel ({}, {}) outside the framebuffer", x, y);
+        y * self.width + x
+    }
+
+    // Add the sum of count samples to pixel (x, y), y counted from the top
+    pub fn add_samples(&mut self, x: usize, y: usize, radiance_sum: Color, count: u32) {
+        let index = self.index(x, y);
+        self.radiance[index] = self.radiance[index] + radiance_sum;
+        self.samples[index] += count;
+    }
+
+    // Average radiance of pixel (x, y), black if it has no samples yet
+    pub fn pixel(&self, x: usize, y: usize) -> Color {
+        let index = self.index(x, y);
+        if self.samples[index] == 0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        self.radiance[index] / self.samples[index] as f64
+    }
+
+    // 8-bit gamma corrected pixels, 3 bytes per pixel
+    pub fn to_rgb8(&self) -> Vec<u8> {
+        let mut rgb = Vec::with_capacity(self.width * self.height * 3);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                rgb.extend_from_slice(&self.pixel(x, y).to_rgb8());
+            }
+        }
+        rgb
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pixel_should_average_samples() {
+        let mut framebuffer = Framebuffer::new(2, 1);
+        framebuffer.add_samples(1, 0, Color::new(1.0, 0.0, 0.5), 1);
+        framebuffer.add_samples(1, 0, Color::new(0.0, 2.0, 0.5), 3);
+        assert_eq!(framebuffer.samples[1], 4);
+        assert_eq!(framebuffer.pixel(1, 0), Color::new(0.25, 0.5, 0.25));
+        assert_eq!(framebuffer.pixel(0, 0), Color::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn rgb8_should_be_gamma_corrected_and_clamped() {
+        let mut framebuffer = Framebuffer::new(1, 2);
+        framebuffer.add_samples(0, 0, Color::new(0.25, 1.0, 0.0), 1);
+        framebuffer.add_samples(0, 1, Color::new(4.0, 0.0, f64::NAN), 1);
+        // Gamma 2: 0.25 -> 0.5 -> 128
+        assert_eq!(framebuffer.to_rgb8(), vec![128, 255, 0, 255, 0, 255]);
+    }
+}
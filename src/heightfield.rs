@@ -0,0 +1,281 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::png::RgbImage;
+use crate::point::Point;
+use crate::ray::Ray;
+use crate::triangle::triangle_hit_record;
+use crate::vec3::Vec3;
+
+// Slack on the stretch of ray over a cell, for hits right on the edge between two cells
+const CELL_EPSILON: f64 = 1e-9;
+
+// Terrain: a grid of heights over the xz plane, facing up
+// Samples are spaced evenly over the rectangle from corner to corner + (size.x, 0, size.z),
+// heights in [0, 1] rising to size.y above the corner. Each cell between four samples is
+// two triangles, shaded with normals interpolated from the slopes at the samples. Rays are
+// followed from cell to cell across the grid (as in Amanatides and Woo's voxel traversal),
+// so only the cells under the ray are tested.
+//
+// u runs along x and v against z, so an image seen from above with its top towards -z
+// maps onto the terrain it is made from.
+pub struct Heightfield {
+    columns: usize, // Samples along x
+    rows: usize,    // Samples along z
+    points: Vec<Point>,
+    normals: Vec<Vec3>,
+    // Lowest and highest point of each cell, to skip cells the ray passes over
+    cell_ranges: Vec<(f64, f64)>,
+    corner: Point,
+    spacing: (f64, f64),
+    bbox: Aabb,
+    material: Arc<dyn Material>,
+}
+
+impl Heightfield {
+    // heights holds rows of samples from -z to +z, each along x, at least 2 by 2
+    pub fn new(heights: &[f64], columns: usize, corner: Point, size: Vec3, material: Arc<dyn Material>) -> Self {
+        let rows = heights.len() / columns;
+        assert!(columns >= 2 && rows >= 2 && rows * columns == heights.len());
+        let spacing = (size.x / (columns - 1) as f64, size.z / (rows - 1) as f64);
+        let height = |i: usize, j: usize| heights[j * columns + i] * size.y;
+
+        let mut points = Vec::with_capacity(heights.len());
+        let mut normals = Vec::with_capacity(heights.len());
+        for j in 0..rows {
+            for i in 0..columns {
+                points.push(corner + Vec3::new(i as f64 * spacing.0, height(i, j), j as f64 * spacing.1));
+                // Slopes by central differences, one-sided at the edges
+                let (left, right) = (i.saturating_sub(1), (i + 1).min(columns - 1));
+                let (back, front) = (j.saturating_sub(1), (j + 1).min(rows - 1));
+                let slope_x = (height(right, j) - height(left, j)) / ((right - left) as f64 * spacing.0);
+                let slope_z = (height(i, front) - height(i, back)) / ((front - back) as f64 * spacing.1);
+                normals.push(Vec3::new(-slope_x, 1.0, -slope_z).unit_vector());
+            }
+        }
+
+        let mut cell_ranges = Vec::with_capacity((columns - 1) * (rows - 1));
+        for j in 0..rows - 1 {
+            for i in 0..columns - 1 {
+                let corners = [height(i, j), height(i + 1, j), height(i, j + 1), height(i + 1, j + 1)];
+                let low = corners.iter().cloned().fold(f64::INFINITY, f64::min);
+                let high = corners.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+                cell_ranges.push((corner.y + low, corner.y + high));
+            }
+        }
+
+        let low = cell_ranges.iter().fold(f64::INFINITY, |low, range| low.min(range.0));
+        let high = cell_ranges.iter().fold(f64::NEG_INFINITY, |high, range| high.max(range.1));
+        let bbox = Aabb::new(
+            Point::new(corner.x, low, corner.z),
+            Point::new(corner.x + size.x, high, corner.z + size.z),
+        )
+        .padded(1e-4);
+        Heightfield { columns, rows, points, normals, cell_ranges, corner, spacing, bbox, material }
+    }
+
+    // Brightness of each pixel as the height, white highest
+    pub fn from_image(image: &RgbImage, corner: Point, size: Vec3, material: Arc<dyn Material>) -> Self {
+        let heights: Vec<f64> = image
+            .pixels
+            .chunks(3)
+            .map(|rgb| (rgb[0] as f64 + rgb[1] as f64 + rgb[2] as f64) / (3.0 * 255.0))
+            .collect();
+        Self::new(&heights, image.width, corner, size, material)
+    }
+
+    // Heights sampled from a function of (u, v) on a grid of columns by rows, then stretched
+    // to span [0, 1]
+    pub fn from_function(
+        columns: usize,
+        rows: usize,
+        function: impl Fn(f64, f64) -> f64,
+        corner: Point,
+        size: Vec3,
+        material: Arc<dyn Material>,
+    ) -> Self {
+        let mut heights = Vec::with_capacity(columns * rows);
+        for j in 0..rows {
+            for i in 0..columns {
+                let u = i as f64 / (columns - 1) as f64;
+                let v = 1.0 - j as f64 / (rows - 1) as f64;
+                heights.push(function(u, v));
+            }
+        }
+        let low = heights.iter().cloned().fold(f64::INFINITY, f64::min);
+        let high = heights.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        if high > low {
+            for height in heights.iter_mut() {
+                *height = (*height - low) / (high - low);
+            }
+        }
+        Self::new(&heights, columns, corner, size, material)
+    }
+
+    // Texture coordinates of the sample at index
+    fn uv(&self, index: usize) -> (f64, f64) {
+        let (i, j) = (index % self.columns, index / self.columns);
+        (i as f64 / (self.columns - 1) as f64, 1.0 - j as f64 / (self.rows - 1) as f64)
+    }
+
+    // Nearest hit on the two triangles of cell (i, j)
+    fn hit_cell(&self, i: usize, j: usize, ray: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let index = |i: usize, j: usize| j * self.columns + i;
+        let (a, b, c, d) = (index(i, j), index(i, j + 1), index(i + 1, j + 1), index(i + 1, j));
+        // Both wound to face up, split along the diagonal from (i, j) to (i + 1, j + 1)
+        let mut nearest: Option<HitRecord> = None;
+        for triangle in [[a, b, c], [a, c, d]].iter() {
+            let limit = nearest.as_ref().map_or(t_max, |hit| hit.t);
+            let [p, q, r] = *triangle;
+            let hit = triangle_hit_record(
+                [self.points[p], self.points[q], self.points[r]],
+                Some([self.normals[p], self.normals[q], self.normals[r]]),
+                [self.uv(p), self.uv(q), self.uv(r)],
+                &self.material,
+                ray,
+                t_min,
+                limit,
+            );
+            if hit.is_some() {
+                nearest = hit;
+            }
+        }
+        nearest
+    }
+}
+
+impl Hittable for Heightfield {
+    fn hit(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (start, end) = self.bbox.hit_range(ray, t_min, t_max)?;
+        let (dx, dz) = self.spacing;
+        let last = (self.columns as i64 - 2, self.rows as i64 - 2);
+        let entry = ray.at(start);
+        let mut i = (((entry.x - self.corner.x) / dx).floor() as i64).max(0).min(last.0);
+        let mut j = (((entry.z - self.corner.z) / dz).floor() as i64).max(0).min(last.1);
+
+        // t of the next cell boundary along x and z, and between boundaries
+        let step = |direction: f64| if direction > 0.0 { 1 } else { -1 };
+        let next_boundary = |cell: i64, origin: f64, direction: f64, corner: f64, spacing: f64| {
+            if direction == 0.0 {
+                return f64::INFINITY;
+            }
+            let boundary = corner + (cell + if direction > 0.0 { 1 } else { 0 }) as f64 * spacing;
+            (boundary - origin) / direction
+        };
+        let (step_x, step_z) = (step(ray.direction.x), step(ray.direction.z));
+        let delta_x = (dx / ray.direction.x).abs();
+        let delta_z = (dz / ray.direction.z).abs();
+        let mut next_x = next_boundary(i, ray.origin.x, ray.direction.x, self.corner.x, dx);
+        let mut next_z = next_boundary(j, ray.origin.z, ray.direction.z, self.corner.z, dz);
+
+        let mut enter = start;
+        while enter <= end {
+            let exit = next_x.min(next_z).min(end);
+            let (low, high) = self.cell_ranges[j as usize * (self.columns - 1) + i as usize];
+            let (y0, y1) = (ray.at(enter).y, ray.at(exit).y);
+            if y0.min(y1) <= high + CELL_EPSILON && y0.max(y1) >= low - CELL_EPSILON {
+                let range = ((enter - CELL_EPSILON).max(t_min), (exit + CELL_EPSILON).min(t_max));
+                if let Some(hit) = self.hit_cell(i as usize, j as usize, ray, range.0, range.1) {
+                    return Some(hit);
+                }
+            }
+            if next_x < next_z {
+                i += step_x;
+                enter = next_x;
+                next_x += delta_x;
+            } else {
+                j += step_z;
+                enter = next_z;
+                next_z += delta_z;
+            }
+            if i < 0 || j < 0 || i > last.0 || j > last.1 {
+                return None;
+            }
+        }
+        None
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bbox)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::Lambertian;
+
+    fn material() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))
+    }
+
+    // A ramp rising along x, from 0 at x = 0 to 1 at x = 4, over z from 0 to 4
+    fn ramp() -> Heightfield {
+        let heights: Vec<f64> = (0..5).flat_map(|_| (0..5).map(|i| i as f64 / 4.0)).collect();
+        Heightfield::new(&heights, 5, Point::new(0.0, 0.0, 0.0), Vec3::new(4.0, 1.0, 4.0), material())
+    }
+
+    #[test]
+    fn ray_should_hit_the_slope() {
+        let ramp = ramp();
+        let down = Vec3::new(0.0, -1.0, 0.0);
+        let hit = ramp.hit(Ray::new(Point::new(2.5, 5.0, 1.3), down), 0.001, f64::INFINITY).unwrap();
+        assert!((hit.point.y - 0.625).abs() < 1e-9);
+        let expected = Vec3::new(-0.25, 1.0, 0.0).unit_vector();
+        assert!((hit.normal - expected).length() < 1e-9);
+        assert!(hit.front_face);
+        assert!((hit.u - 0.625).abs() < 1e-9 && (hit.v - (1.0 - 1.3 / 4.0)).abs() < 1e-9);
+        // Off the edge
+        assert!(ramp.hit(Ray::new(Point::new(4.5, 5.0, 1.0), down), 0.001, f64::INFINITY).is_none());
+
+        // Skimming along z, over many cells, until the ramp rises into the ray's path
+        let ray = Ray::new(Point::new(-1.0, 0.6, 2.2), Vec3::new(1.0, 0.0, 0.1));
+        let hit = ramp.hit(ray, 0.001, f64::INFINITY).unwrap();
+        assert!((hit.point.x - 2.4).abs() < 1e-9, "{:?}", hit.point);
+        // From underneath, the back face
+        let hit = ramp.hit(Ray::new(Point::new(1.0, -1.0, 1.0), Vec3::new(0.0, 1.0, 0.0)), 0.001, f64::INFINITY).unwrap();
+        assert!(!hit.front_face);
+    }
+
+    #[test]
+    fn traversal_should_find_the_nearest_peak() {
+        // Two peaks on a 9 x 9 grid, the ray passes the first one first
+        let mut heights = vec![0.0; 81];
+        heights[4 * 9 + 2] = 1.0;
+        heights[4 * 9 + 6] = 1.0;
+        let field = Heightfield::new(&heights, 9, Point::new(0.0, 0.0, 0.0), Vec3::new(8.0, 4.0, 8.0), material());
+        let right = Ray::new(Point::new(-2.0, 1.0, 4.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!((field.hit(right, 0.001, f64::INFINITY).unwrap().point.x - 1.25).abs() < 1e-9);
+        let left = Ray::new(Point::new(10.0, 1.0, 4.0), Vec3::new(-1.0, 0.0, 0.0));
+        assert!((field.hit(left, 0.001, f64::INFINITY).unwrap().point.x - 6.75).abs() < 1e-9);
+        // Diagonally, agreeing with testing every cell
+        for k in 0..50 {
+            let origin = Point::new(-1.0 + 0.2 * k as f64, 3.0, -1.0);
+            let ray = Ray::new(origin, Vec3::new(0.3, -0.25, 1.0));
+            let brute = (0..8)
+                .flat_map(|j| (0..8).map(move |i| (i, j)))
+                .filter_map(|(i, j)| field.hit_cell(i, j, ray, 0.001, f64::INFINITY))
+                .map(|hit| hit.t)
+                .fold(f64::INFINITY, f64::min);
+            match field.hit(ray, 0.001, f64::INFINITY) {
+                Some(hit) => assert!((hit.t - brute).abs() < 1e-9),
+                None => assert!(brute.is_infinite()),
+            }
+        }
+    }
+
+    #[test]
+    fn image_and_function_heights_should_match() {
+        // 2 x 2 image, white on the right
+        let image = RgbImage { width: 2, height: 2, pixels: vec![0, 0, 0, 255, 255, 255, 0, 0, 0, 255, 255, 255] };
+        let field = Heightfield::from_image(&image, Point::new(0.0, 0.0, 0.0), Vec3::new(1.0, 2.0, 1.0), material());
+        let function = Heightfield::from_function(2, 2, |u, _| 5.0 * u + 3.0, Point::new(0.0, 0.0, 0.0), Vec3::new(1.0, 2.0, 1.0), material());
+        let ray = Ray::new(Point::new(0.75, 5.0, 0.5), Vec3::new(0.0, -1.0, 0.0));
+        assert!((field.hit(ray, 0.001, f64::INFINITY).unwrap().point.y - 1.5).abs() < 1e-9);
+        assert!((function.hit(ray, 0.001, f64::INFINITY).unwrap().point.y - 1.5).abs() < 1e-9);
+        assert_eq!(field.bounding_box(), function.bounding_box());
+    }
+}
@@ -1,12 +1,114 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
+use crate::aabb::Aabb;
+use crate::point::Point;
 use crate::ray::Ray;
 use crate::vec3::Vec3;
 use crate::material::Material;
 
-pub trait Hittable {
+// Where and how a ray met the surface of an object
+#[derive(Clone)]
+pub struct HitRecord {
+    pub point: Point,
+    pub t: f64,
+
+    // Unit normal pointing out of the object, regardless of which side the ray came from
+    pub normal: Vec3,
+
+    // Whether the ray hit the outside of the surface (ray and outward normal are opposed)
+    pub front_face: bool,
+
+    // Surface coordinates, each in [0, 1]
+    pub u: f64,
+    pub v: f64,
+
+    // Unit vector along which u increases on the surface, for surfaces which define one
+    // Together with the normal it gives the bitangent, normal x tangent. Materials that scatter
+    // light along a direction on the surface, as hair does along its fibers, shade with it.
+    pub tangent: Option<Vec3>,
+
+    pub material: Arc<dyn Material>,
+}
+
+impl HitRecord {
+    pub fn new(
+        ray: Ray,
+        t: f64,
+        outward_normal: Vec3,
+        (u, v): (f64, f64),
+        material: Arc<dyn Material>,
+    ) -> Self {
+        let front_face = ray.direction.dot(outward_normal) < 0.0;
+        Self {
+            point: ray.at(t),
+            t,
+            normal: outward_normal,
+            front_face,
+            u,
+            v,
+            tangent: None,
+            material,
+        }
+    }
+
+    // Normal on the same side of the surface as the incident ray
+    pub fn facing_normal(&self) -> Vec3 {
+        if self.front_face { self.normal } else { -self.normal }
+    }
+}
+
+// Crossings of surfaces a walk along a ray stops at, against shapes whose hits never move on
+const MAX_CROSSINGS: usize = 256;
+
+// A stretch of a ray inside a solid, from crossing its surface inwards to crossing it outwards
+// enter is None when the ray is already inside at the start of the range, and exit is None
+// when it is still inside at the end.
+#[derive(Clone)]
+pub struct Interval {
+    pub enter: Option<HitRecord>,
+    pub exit: Option<HitRecord>,
+}
+
+pub trait Hittable: Send + Sync {
     // When a ray is projected on the surface on the object
-    // It returns the array of t-values for which the ray intersects with the surface of the object
-    // and the corresponding normals
-    fn hit (&self, ray: Ray) -> Vec<(f64, Vec3, Rc<dyn Material>)>;
+    // It returns the nearest intersection with t in the open interval (t_min, t_max), if any
+    fn hit (&self, ray: Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
+
+    // The stretches of the ray within (t_min, t_max) inside the object, in order, treating it
+    // as a solid bounded by its surface (for constructive solid geometry)
+    // By default, found by following the ray from hit to hit: the ray enters where it hits
+    // the front face and leaves where it hits the back. Surfaces which enclose nothing,
+    // such as a lone quad, are crossed without being inside of anything.
+    fn intervals (&self, ray: Ray, t_min: f64, t_max: f64) -> Vec<Interval> {
+        let mut intervals = Vec::new();
+        let mut enter: Option<HitRecord> = None;
+        let mut inside = false;
+        let mut start = t_min;
+        for crossing in 0..MAX_CROSSINGS {
+            let hit = match self.hit(ray, start, t_max) {
+                Some(hit) => hit,
+                None => break,
+            };
+            start = hit.t;
+            if hit.front_face {
+                // Entering twice is one surface inside another, still inside since the first
+                if !inside {
+                    enter = Some(hit);
+                    inside = true;
+                }
+            } else if inside || crossing == 0 {
+                // Leaving first means the ray started inside
+                intervals.push(Interval { enter: enter.take(), exit: Some(hit) });
+                inside = false;
+            }
+        }
+        if inside {
+            intervals.push(Interval { enter, exit: None });
+        }
+        intervals
+    }
+
+    // Box enclosing the whole object, used to build the bounding volume hierarchy
+    // Unbounded objects (e.g. infinite planes) return None
+    fn bounding_box (&self) -> Option<Aabb>;
 }
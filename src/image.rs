@@ -0,0 +1,169 @@
+// Reading image files, for textures
+// The format is chosen by extension: .png, or .ppm / .pgm / .pnm for Netpbm images
+// (http://netpbm.sourceforge.net/doc/ppm.html), plain or binary.
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::png::{self, RgbImage};
+
+#[derive(Debug)]
+pub struct ImageError {
+    pub file: String,
+    pub message: String,
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.file, self.message)
+    }
+}
+
+pub fn load_image(path: &Path) -> Result<RgbImage, ImageError> {
+    let file = path.display().to_string();
+    let error = |message: String| ImageError { file: file.clone(), message };
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_ascii_lowercase());
+    let bytes = fs::read(path).map_err(|io_error| error(io_error.to_string()))?;
+    match extension.as_deref() {
+        Some("png") => png::decode_png(&bytes).map_err(|png_error| error(png_error.to_string())),
+        Some("ppm") | Some("pgm") | Some("pnm") => decode_pnm(&bytes).map_err(error),
+        _ => Err(error(String::from("unsupported image format, expected .png, .ppm or .pgm"))),
+    }
+}
+
+// P2 / P5 (grayscale) and P3 / P6 (RGB), samples are scaled to 8 bits
+pub fn decode_pnm(bytes: &[u8]) -> Result<RgbImage, String> {
+    let mut reader = PnmReader { bytes, position: 0 };
+    let magic = reader.token()?;
+    let (channels, binary) = match magic.as_str() {
+        "P2" => (1, false),
+        "P3" => (3, false),
+        "P5" => (1, true),
+        "P6" => (3, true),
+        _ => return Err(format!("unsupported Netpbm type '{}', expected P2, P3, P5 or P6", magic)),
+    };
+    let width = reader.number()?;
+    let height = reader.number()?;
+    let max_value = reader.number()?;
+    if width == 0 || height == 0 {
+        return Err(String::from("image has no pixels"));
+    }
+    if max_value == 0 || max_value > 65535 {
+        return Err(format!("maximum value {} out of range", max_value));
+    }
+
+    // Header values are untrusted, so sizes computed from them are checked
+    let too_large = || String::from("image too large");
+    let pixel_count = width.checked_mul(height).ok_or_else(too_large)?;
+    let byte_count = pixel_count.checked_mul(3).ok_or_else(too_large)?;
+    let sample_count = pixel_count * channels;
+    let samples: Vec<usize> = if binary {
+        // A single whitespace character separates the header from the data
+        reader.position += 1;
+        let sample_size = if max_value < 256 { 1 } else { 2 };
+        let end = sample_count
+            .checked_mul(sample_size)
+            .and_then(|size| size.checked_add(reader.position))
+            .ok_or_else(too_large)?;
+        let data = reader
+            .bytes
+            .get(reader.position..end)
+            .ok_or_else(|| String::from("truncated image data"))?;
+        if sample_size == 1 {
+            data.iter().map(|&sample| sample as usize).collect()
+        } else {
+            data.chunks(2).map(|sample| (sample[0] as usize) << 8 | sample[1] as usize).collect()
+        }
+    } else {
+        (0..sample_count).map(|_| reader.number()).collect::<Result<_, _>>()?
+    };
+
+    let mut pixels = Vec::with_capacity(byte_count);
+    for pixel in samples.chunks(channels) {
+        for &sample in pixel.iter().cycle().take(3) {
+            if sample > max_value {
+                return Err(format!("sample {} above the maximum value {}", sample, max_value));
+            }
+            pixels.push(((sample * 255 + max_value / 2) / max_value) as u8);
+        }
+    }
+    Ok(RgbImage { width, height, pixels })
+}
+
+struct PnmReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> PnmReader<'a> {
+    // Next whitespace separated word, skipping comments
+    fn token(&mut self) -> Result<String, String> {
+        loop {
+            match self.bytes.get(self.position) {
+                Some(byte) if byte.is_ascii_whitespace() => self.position += 1,
+                Some(b'#') => {
+                    while !matches!(self.bytes.get(self.position), Some(b'\n') | None) {
+                        self.position += 1;
+                    }
+                }
+                Some(_) => break,
+                None => return Err(String::from("unexpected end of file")),
+            }
+        }
+        let start = self.position;
+        while matches!(self.bytes.get(self.position), Some(byte) if !byte.is_ascii_whitespace()) {
+            self.position += 1;
+        }
+        Ok(String::from_utf8_lossy(&self.bytes[start..self.position]).into_owned())
+    }
+
+    fn number(&mut self) -> Result<usize, String> {
+        let token = self.token()?;
+        token.parse().map_err(|_| format!("expected a number but found '{}'", token))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoder;
+
+    #[test]
+    fn ppm_written_by_encoder_should_decode() {
+        let pixels = vec![255, 0, 0, 0, 128, 255];
+        let mut plain = vec![];
+        encoder::encode_ppm(&mut plain, 2, 1, &pixels).unwrap();
+        let mut binary = vec![];
+        encoder::encode_ppm_binary(&mut binary, 2, 1, &pixels).unwrap();
+        let expected = RgbImage { width: 2, height: 1, pixels };
+        assert_eq!(decode_pnm(&plain).unwrap(), expected);
+        assert_eq!(decode_pnm(&binary).unwrap(), expected);
+    }
+
+    #[test]
+    fn grayscale_with_comments_should_decode() {
+        let text = b"P2\n# made by hand\n2 1 # width height\n15\n0 15\n";
+        let image = decode_pnm(text).unwrap();
+        assert_eq!(image.pixels, vec![0, 0, 0, 255, 255, 255]);
+
+        // 16-bit binary samples, most significant byte first
+        let mut binary = b"P5 1 1 65535\n".to_vec();
+        binary.extend_from_slice(&[0x80, 0x00]);
+        assert_eq!(decode_pnm(&binary).unwrap().pixels, vec![128, 128, 128]);
+    }
+
+    #[test]
+    fn malformed_images_should_be_rejected() {
+        assert!(decode_pnm(b"P4 1 1\n").is_err());
+        assert!(decode_pnm(b"P3 2 1 255\n1 2 3\n").is_err());
+        assert!(decode_pnm(b"P6 2 1 255\n\x01\x02").is_err());
+        assert!(decode_pnm(b"P2 1 1 10\n11\n").is_err());
+        // Sizes that overflow, rather than wrap round to a short slice of data
+        assert_eq!(decode_pnm(b"P6 4294967296 4294967296 255\n\x01\x02").err().unwrap(), "image too large");
+        assert_eq!(decode_pnm(b"P5 4294967296 2147483648 255\n\x01\x02").err().unwrap(), "image too large");
+    }
+}
@@ -0,0 +1,257 @@
+// Blobby implicit surfaces, from fields summed around points (metaballs)
+//
+// Each ball spreads a field around its center, falling smoothly from its strength there to 0
+// at its radius as strength * (1 - r^2 / R^2)^3 (Wyvill's soft objects). The surface is where
+// the fields of all the balls add up to a threshold, so balls close together swell towards
+// each other and merge. A ball with a negative strength pushes the surface away instead.
+
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::point::Point;
+use crate::ray::Ray;
+use crate::sphere::sphere_uv;
+use crate::utils;
+use crate::vec3::Vec3;
+
+pub struct Metaball {
+    center: Point,
+    radius: f64,
+    strength: f64,
+}
+
+impl Metaball {
+    pub fn new(center: Point, radius: f64, strength: f64) -> Self {
+        Metaball { center, radius, strength }
+    }
+
+    // 1 - r^2 / R^2, positive within the radius
+    fn falloff(&self, p: Point) -> f64 {
+        1.0 - (p - self.center).length_squared() / (self.radius * self.radius)
+    }
+
+    #[cfg(test)]
+    fn field(&self, p: Point) -> f64 {
+        let falloff = self.falloff(p);
+        if falloff <= 0.0 {
+            return 0.0;
+        }
+        self.strength * falloff * falloff * falloff
+    }
+
+    // -6 strength (1 - r^2 / R^2)^2 (p - center) / R^2
+    fn gradient(&self, p: Point) -> Vec3 {
+        let falloff = self.falloff(p);
+        if falloff <= 0.0 {
+            return Vec3::new(0.0, 0.0, 0.0);
+        }
+        let scale = -6.0 * self.strength * falloff * falloff / (self.radius * self.radius);
+        (p - self.center) * scale
+    }
+
+    // Stretch of the ray from o along the unit direction d within the radius, in distances from o
+    fn reach(&self, o: Point, d: Vec3) -> Option<(f64, f64)> {
+        let oc = o - self.center;
+        let half_b = oc.dot(d);
+        let discriminant = half_b * half_b - (oc.length_squared() - self.radius * self.radius);
+        if discriminant <= 0.0 {
+            return None;
+        }
+        let root = discriminant.sqrt();
+        Some((-half_b - root, -half_b + root))
+    }
+
+    // Most the field can be between distances low and high from o along the unit direction d,
+    // where the ray comes closest to the center
+    fn peak(&self, o: Point, d: Vec3, low: f64, high: f64) -> f64 {
+        if self.strength <= 0.0 {
+            return 0.0;
+        }
+        let closest = utils::clamp((self.center - o).dot(d), low, high);
+        let falloff = self.falloff(o + closest * d).max(0.0);
+        self.strength * falloff * falloff * falloff
+    }
+
+    // The field at distance x from o along the unit direction d, within the radius, as a
+    // polynomial in x, highest power first
+    fn polynomial(&self, o: Point, d: Vec3) -> Vec<f64> {
+        let oc = o - self.center;
+        let r2 = self.radius * self.radius;
+        // 1 - |oc + x d|^2 / R^2
+        let falloff = [-1.0 / r2, -2.0 * oc.dot(d) / r2, 1.0 - oc.length_squared() / r2];
+        let cube = multiply(&multiply(&falloff, &falloff), &falloff);
+        cube.into_iter().map(|c| c * self.strength).collect()
+    }
+}
+
+fn multiply(a: &[f64], b: &[f64]) -> Vec<f64> {
+    let mut product = vec![0.0; a.len() + b.len() - 1];
+    for (i, x) in a.iter().enumerate() {
+        for (j, y) in b.iter().enumerate() {
+            product[i + j] += x * y;
+        }
+    }
+    product
+}
+
+// The surface where the balls' fields add up to the threshold, enclosing the points where
+// they add up to more
+pub struct ImplicitSurface {
+    balls: Vec<Metaball>,
+    threshold: f64,
+    bbox: Aabb,
+    material: Arc<dyn Material>,
+}
+
+impl ImplicitSurface {
+    // The threshold must be positive, and at least one ball must have a positive strength
+    pub fn new(balls: Vec<Metaball>, threshold: f64, material: Arc<dyn Material>) -> Self {
+        assert!(threshold > 0.0);
+        // The field only reaches the threshold within the radius of a ball adding to it
+        let bbox = balls
+            .iter()
+            .filter(|ball| ball.strength > 0.0)
+            .map(|ball| {
+                let radius = Vec3::new(ball.radius, ball.radius, ball.radius);
+                Aabb::new(ball.center - radius, ball.center + radius)
+            })
+            .reduce(Aabb::surrounding_box)
+            .expect("no ball with a positive strength");
+        ImplicitSurface { balls, threshold, bbox, material }
+    }
+
+    #[cfg(test)]
+    fn field(&self, p: Point) -> f64 {
+        self.balls.iter().map(|ball| ball.field(p)).sum()
+    }
+
+    // Points into the surface, where the field grows
+    fn gradient(&self, p: Point) -> Vec3 {
+        self.balls.iter().fold(Vec3::new(0.0, 0.0, 0.0), |sum, ball| sum + ball.gradient(p))
+    }
+}
+
+impl Hittable for ImplicitSurface {
+    // Between the points where the ray comes within or leaves the radius of a ball, the field
+    // is the sum of the same balls' fields, along the ray a polynomial of degree 6 in the
+    // distance travelled. The first root of field - threshold in any of these stretches is
+    // the hit, found wherever the ray starts, so rays inside a transparent blob find the way out.
+    fn hit(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (start, end) = self.bbox.hit_range(ray, t_min, t_max)?;
+        let length = ray.direction.length();
+        let d = ray.direction / length;
+        // Measured from where the ray enters the box, where the coefficients are precise
+        let o = ray.at(start);
+        let span = (end - start) * length;
+
+        let reaches: Vec<Option<(f64, f64)>> = self.balls.iter().map(|ball| ball.reach(o, d)).collect();
+        let mut breaks = vec![0.0, span];
+        for &(enter, exit) in reaches.iter().flatten() {
+            breaks.extend([enter, exit].iter().filter(|&&x| x > 0.0 && x < span));
+        }
+        breaks.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        for pair in breaks.windows(2) {
+            let (low, high) = (pair[0], pair[1]);
+            let active: Vec<&Metaball> = self
+                .balls
+                .iter()
+                .zip(&reaches)
+                .filter(|(_, reach)| matches!(reach, Some((enter, exit)) if *enter < high && *exit > low))
+                .map(|(ball, _)| ball)
+                .collect();
+            // The field cannot reach the threshold here, nor can the ray be inside
+            let peak: f64 = active.iter().map(|ball| ball.peak(o, d, low, high)).sum();
+            if peak < self.threshold {
+                continue;
+            }
+            let mut polynomial = vec![0.0; 7];
+            for ball in active {
+                for (sum, c) in polynomial.iter_mut().zip(ball.polynomial(o + low * d, d)) {
+                    *sum += c;
+                }
+            }
+            polynomial[6] -= self.threshold;
+            let hit_t = utils::polynomial_roots(&polynomial)
+                .into_iter()
+                .filter(|&x| x >= 0.0 && x <= high - low)
+                .map(|x| start + (low + x) / length)
+                .find(|&t| t > t_min && t < t_max);
+            if let Some(t) = hit_t {
+                let outward_normal = -self.gradient(ray.at(t)).unit_vector();
+                return Some(HitRecord::new(ray, t, outward_normal, sphere_uv(outward_normal), self.material.clone()));
+            }
+        }
+        None
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bbox)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::Lambertian;
+
+    fn surface(balls: Vec<Metaball>, threshold: f64) -> ImplicitSurface {
+        ImplicitSurface::new(balls, threshold, Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))))
+    }
+
+    #[test]
+    fn single_ball_should_be_a_sphere() {
+        // strength (1 - r^2 / R^2)^3 = threshold at r = R sqrt(1 - cbrt(threshold / strength))
+        let ball = surface(vec![Metaball::new(Point::new(0.0, 0.0, 0.0), 2.0, 1.0)], 0.5);
+        let radius = 2.0 * (1.0 - 0.5f64.cbrt()).sqrt();
+        let ray = Ray::new(Point::new(-5.0, 0.0, 0.0), Vec3::new(2.0, 0.0, 0.0));
+        let hit = ball.hit(ray, 0.001, f64::INFINITY).unwrap();
+        assert!((hit.t - (5.0 - radius) / 2.0).abs() < 1e-9, "{}", hit.t);
+        assert!((hit.normal - Vec3::new(-1.0, 0.0, 0.0)).length() < 1e-9);
+        assert!(hit.front_face);
+        // From the center, out the far side
+        let inside = Ray::new(Point::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let hit = ball.hit(inside, 0.001, f64::INFINITY).unwrap();
+        assert!((hit.t - radius).abs() < 1e-9);
+        assert!((hit.normal - Vec3::new(0.0, 1.0, 0.0)).length() < 1e-9);
+        assert!(!hit.front_face);
+        assert!(ball.hit(Ray::new(Point::new(-5.0, 1.0, 0.0), Vec3::new(1.0, 0.0, 0.0)), 0.001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn close_balls_should_merge() {
+        let pair = |x: f64| {
+            surface(vec![Metaball::new(Point::new(-x, 0.0, 0.0), 2.0, 1.0), Metaball::new(Point::new(x, 0.0, 0.0), 2.0, 1.0)], 0.5)
+        };
+        let down = Ray::new(Point::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        // Alone, each would reach 0.908 from its center, short of the middle, but together
+        // 2 (1 - (1 + y^2) / 4)^3 = 0.5 at the middle
+        let hit = pair(1.0).hit(down, 0.001, f64::INFINITY).unwrap();
+        let y = (3.0 - 4.0 * 0.25f64.cbrt()).sqrt();
+        assert!((hit.point.y - y).abs() < 1e-9, "{:?}", hit.point);
+        assert!((hit.normal - Vec3::new(0.0, 1.0, 0.0)).length() < 1e-9);
+        assert!(pair(3.0).hit(down, 0.001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn negative_balls_should_dent_the_surface() {
+        let center = Metaball::new(Point::new(0.0, 0.0, 0.0), 2.0, 1.0);
+        let ray = Ray::new(Point::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let plain = surface(vec![center], 0.5).hit(ray, 0.001, f64::INFINITY).unwrap();
+        let dented = surface(
+            vec![Metaball::new(Point::new(0.0, 0.0, 0.0), 2.0, 1.0), Metaball::new(Point::new(0.0, 1.5, 0.0), 1.0, -1.0)],
+            0.5,
+        );
+        let hit = dented.hit(ray, 0.001, f64::INFINITY).unwrap();
+        assert!(hit.t > plain.t + 0.01);
+        assert!((dented.field(hit.point) - 0.5).abs() < 1e-9);
+        // The analytic gradient agrees with the field's slope
+        let p = Point::new(0.3, 0.7, -0.2);
+        let h = 1e-6;
+        let slope = (dented.field(p + Vec3::new(0.0, h, 0.0)) - dented.field(p - Vec3::new(0.0, h, 0.0))) / (2.0 * h);
+        assert!((dented.gradient(p).y - slope).abs() < 1e-6);
+    }
+}
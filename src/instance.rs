@@ -0,0 +1,201 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable, Interval};
+use crate::point::Point;
+use crate::ray::Ray;
+use crate::transform::{Motion, Transform};
+use crate::vec3::Vec3;
+
+// Times a moving instance's bounding box is sampled at
+const BOX_SAMPLES: usize = 32;
+
+// An object placed with a transform, sharing the object's geometry with any other instances
+// Rays are brought into the object's space instead of moving the object. The ray direction
+// is not normalized, so distances along the ray are the same in both spaces.
+pub struct Instance {
+    object: Arc<dyn Hittable>,
+    transform: Transform,
+    motion: Option<Motion>, // Replacing transform for instances that move
+    bbox: Option<Aabb>,
+}
+
+impl Instance {
+    pub fn new(object: Arc<dyn Hittable>, transform: Transform) -> Self {
+        let bbox = object.bounding_box().map(|bbox| transform.bounding_box(bbox));
+        Instance { object, transform, motion: None, bbox }
+    }
+
+    // Placed by a transform that changes over time, according to each ray's time
+    pub fn moving(object: Arc<dyn Hittable>, motion: Motion) -> Self {
+        if let Some(transform) = motion.fixed() {
+            return Instance::new(object, transform);
+        }
+        let bbox = object.bounding_box().map(|bbox| moving_box(bbox, &motion));
+        Instance { object, transform: Transform::identity(), motion: Some(motion), bbox }
+    }
+}
+
+// Boxes around the object at times across the motion, padded by how far a corner moves
+// between two of them, since rotations carry the object outside the sampled boxes
+fn moving_box(bbox: Aabb, motion: &Motion) -> Aabb {
+    let (start, end) = motion.span();
+    let corners: Vec<Point> = (0..8)
+        .map(|corner| {
+            let pick = |bit: usize, axis: usize| if corner & bit == 0 { bbox.minimum[axis] } else { bbox.maximum[axis] };
+            Point::new(pick(1, 0), pick(2, 1), pick(4, 2))
+        })
+        .collect();
+    let mut result = motion.at(start).bounding_box(bbox);
+    let mut previous = motion.at(start);
+    let mut padding: f64 = 0.0;
+    for sample in 1..=BOX_SAMPLES {
+        let transform = motion.at(start + (end - start) * sample as f64 / BOX_SAMPLES as f64);
+        result = Aabb::surrounding_box(result, transform.bounding_box(bbox));
+        for corner in corners.iter() {
+            padding = padding.max((transform.point(*corner) - previous.point(*corner)).length());
+        }
+        previous = transform;
+    }
+    let pad = Vec3::new(padding, padding, padding);
+    Aabb::new(result.minimum - pad, result.maximum + pad)
+}
+
+impl Instance {
+    // The transform at the ray's time, and the ray in the object's space
+    fn local_ray(&self, ray: Ray) -> (Transform, Ray) {
+        let transform = match &self.motion {
+            Some(motion) => motion.at(ray.time),
+            None => self.transform,
+        };
+        let inverse = transform.inverse();
+        (transform, Ray::with_time(inverse.point(ray.origin), inverse.vector(ray.direction), ray.time))
+    }
+}
+
+// A hit on the object in its own space, brought back out
+// The side the ray came from is the same in both spaces.
+fn world_hit(mut hit: HitRecord, ray: Ray, transform: &Transform) -> HitRecord {
+    hit.point = ray.at(hit.t);
+    hit.normal = transform.normal(hit.normal).unit_vector();
+    hit.tangent = hit.tangent.map(|tangent| transform.vector(tangent).unit_vector());
+    hit
+}
+
+impl Hittable for Instance {
+    fn hit(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (transform, local) = self.local_ray(ray);
+        let hit = self.object.hit(local, t_min, t_max)?;
+        Some(world_hit(hit, ray, &transform))
+    }
+
+    fn intervals(&self, ray: Ray, t_min: f64, t_max: f64) -> Vec<Interval> {
+        let (transform, local) = self.local_ray(ray);
+        self.object
+            .intervals(local, t_min, t_max)
+            .into_iter()
+            .map(|interval| Interval {
+                enter: interval.enter.map(|hit| world_hit(hit, ray, &transform)),
+                exit: interval.exit.map(|hit| world_hit(hit, ray, &transform)),
+            })
+            .collect()
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bbox
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::Lambertian;
+    use crate::quad::Cuboid;
+    use crate::sphere::Sphere;
+    use crate::transform::Change;
+
+    fn assert_close(actual: Vec3, expected: Vec3) {
+        assert!((actual - expected).length() < 1e-9, "{:?} != {:?}", actual, expected);
+    }
+
+    #[test]
+    fn instances_should_share_one_object() {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let sphere: Arc<dyn Hittable> = Arc::new(Sphere::new(Point::new(0.0, 0.0, 0.0), 1.0, material));
+        let left = Instance::new(sphere.clone(), Transform::translation(Vec3::new(-5.0, 0.0, 0.0)));
+        // Stretched along y into an ellipsoid
+        let tall = Transform::scaling(Vec3::new(1.0, 3.0, 1.0)).unwrap();
+        let right = Instance::new(sphere.clone(), tall.then(&Transform::translation(Vec3::new(5.0, 0.0, 0.0))));
+
+        let down = Vec3::new(0.0, -1.0, 0.0);
+        let hit = left.hit(Ray::new(Point::new(-5.0, 10.0, 0.0), down), 0.001, f64::INFINITY).unwrap();
+        assert_eq!(hit.t, 9.0);
+        assert_close(hit.point, Point::new(-5.0, 1.0, 0.0));
+        let hit = right.hit(Ray::new(Point::new(5.0, 10.0, 0.0), down), 0.001, f64::INFINITY).unwrap();
+        assert!((hit.t - 7.0).abs() < 1e-9);
+        assert_close(hit.normal, Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(right.bounding_box().unwrap().maximum, Point::new(6.0, 3.0, 1.0));
+        assert!(left.hit(Ray::new(Point::new(0.0, 10.0, 0.0), down), 0.001, f64::INFINITY).is_none());
+        assert_eq!(Arc::strong_count(&sphere), 3);
+
+        // Off center on the ellipsoid, the normal is not along the stretched radius
+        let ray = Ray::new(Point::new(5.6, 0.0, 10.0), Vec3::new(0.0, 0.0, -1.0));
+        let hit = right.hit(ray, 0.001, f64::INFINITY).unwrap();
+        let local = Vec3::new(0.6, 0.0, 0.8);
+        assert_close(hit.normal, local);
+        let ray = Ray::new(Point::new(5.0, 1.5, 10.0), Vec3::new(0.0, 0.0, -1.0));
+        let hit = right.hit(ray, 0.001, f64::INFINITY).unwrap();
+        // Gradient of x^2 + (y / 3)^2 + z^2 at (0, 1.5, sqrt(0.75))
+        assert_close(hit.normal, Vec3::new(0.0, 1.5 / 9.0, 0.75f64.sqrt()).unit_vector());
+    }
+
+    #[test]
+    fn rotated_box_should_keep_outward_normals() {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let cube = Arc::new(Cuboid::new(Point::new(-1.0, -1.0, -1.0), Point::new(1.0, 1.0, 1.0), material));
+        let instance = Instance::new(cube, Transform::rotation(Vec3::new(0.0, 1.0, 0.0), 45.0));
+        // The +x face, turned to face between +x and -z
+        let ray = Ray::new(Point::new(5.0, 0.0, -0.1), Vec3::new(-1.0, 0.0, 0.0));
+        let hit = instance.hit(ray, 0.001, f64::INFINITY).unwrap();
+        assert!(hit.front_face);
+        assert_close(hit.normal, Vec3::new(1.0, 0.0, -1.0).unit_vector());
+        // Mirrored instances still face outwards
+        let mirror = Transform::scaling(Vec3::new(-1.0, 1.0, 1.0)).unwrap();
+        let sphere = Arc::new(Sphere::new(Point::new(1.0, 0.0, 0.0), 0.5, Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))));
+        let mirrored = Instance::new(sphere, mirror);
+        let hit = mirrored.hit(Ray::new(Point::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0)), 0.001, f64::INFINITY).unwrap();
+        assert_close(hit.point, Point::new(-1.5, 0.0, 0.0));
+        assert_close(hit.normal, Vec3::new(-1.0, 0.0, 0.0));
+        assert!(hit.front_face);
+    }
+
+    #[test]
+    fn moving_instance_should_follow_the_ray_time() {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let sphere = Arc::new(Sphere::new(Point::new(2.0, 0.0, 0.0), 0.5, material));
+        // A quarter turn about y, taking the sphere from +x to -z
+        let motion = Motion::new()
+            .then_moving(Change::Rotate(Vec3::new(0.0, 1.0, 0.0), 0.0, 90.0), 0.0, 1.0)
+            .unwrap();
+        let instance = Instance::moving(sphere, motion);
+        let down = Vec3::new(0.0, -1.0, 0.0);
+        let at = |x: f64, z: f64, time: f64| instance.hit(Ray::with_time(Point::new(x, 5.0, z), down, time), 0.001, f64::INFINITY);
+        assert!(at(2.0, 0.0, 0.0).is_some());
+        assert!(at(2.0, 0.0, 1.0).is_none());
+        let hit = at(0.0, -2.0, 1.0).unwrap();
+        assert_close(hit.point, Point::new(0.0, 0.5, -2.0));
+        assert_close(hit.normal, Vec3::new(0.0, 1.0, 0.0));
+        let half = std::f64::consts::FRAC_1_SQRT_2;
+        assert!(at(2.0 * half, -2.0 * half, 0.5).is_some());
+
+        // The box covers the whole arc, including halfway where no sample may fall on the extreme
+        let bbox = instance.bounding_box().unwrap();
+        for step in 0..=100 {
+            let angle = step as f64 / 100.0 * std::f64::consts::FRAC_PI_2;
+            let (x, z) = (2.0 * angle.cos(), -2.0 * angle.sin());
+            assert!(bbox.minimum.x <= x - 0.5 && bbox.maximum.x >= x + 0.5);
+            assert!(bbox.minimum.z <= z - 0.5 && bbox.maximum.z >= z + 0.5);
+        }
+    }
+}
@@ -0,0 +1,318 @@
+// Minimal JSON parser (https://www.json.org) for scene files
+// Every value remembers where it started in the source, so that errors found
+// after parsing (e.g. an unknown material) can still point at a line and column.
+
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Json {
+    pub value: JsonValue,
+    pub line: usize,   // 1-based
+    pub column: usize, // 1-based, in characters
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    // Keys keep their source order
+    Object(Vec<(String, Json)>),
+}
+
+impl JsonValue {
+    // Name used in error messages
+    pub fn kind(&self) -> &'static str {
+        match self {
+            JsonValue::Null => "null",
+            JsonValue::Bool(_) => "a boolean",
+            JsonValue::Number(_) => "a number",
+            JsonValue::String(_) => "a string",
+            JsonValue::Array(_) => "an array",
+            JsonValue::Object(_) => "an object",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+pub fn parse(text: &str) -> Result<Json, ParseError> {
+    let mut parser = Parser { chars: text.chars().collect(), index: 0, line: 1, column: 1 };
+    parser.skip_whitespace();
+    let json = parser.parse_value()?;
+    parser.skip_whitespace();
+    if parser.peek().is_some() {
+        return Err(parser.error("unexpected characters after the end of the document"));
+    }
+    Ok(json)
+}
+
+struct Parser {
+    chars: Vec<char>,
+    index: usize,
+    line: usize,
+    column: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.index).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.index += 1;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn error(&self, message: &str) -> ParseError {
+        ParseError { line: self.line, column: self.column, message: message.to_string() }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), ParseError> {
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.next();
+                Ok(())
+            }
+            Some(c) => Err(self.error(&format!("expected '{}' but found '{}'", expected, c))),
+            None => Err(self.error(&format!("expected '{}' but reached the end of the file", expected))),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(' ') | Some('\t') | Some('\n') | Some('\r') = self.peek() {
+            self.next();
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Json, ParseError> {
+        let (line, column) = (self.line, self.column);
+        let value = match self.peek() {
+            Some('{') => self.parse_object()?,
+            Some('[') => self.parse_array()?,
+            Some('"') => JsonValue::String(self.parse_string()?),
+            Some('t') => self.parse_literal("true", JsonValue::Bool(true))?,
+            Some('f') => self.parse_literal("false", JsonValue::Bool(false))?,
+            Some('n') => self.parse_literal("null", JsonValue::Null)?,
+            Some(c) if c == '-' || c.is_ascii_digit() => self.parse_number()?,
+            Some(c) => return Err(self.error(&format!("unexpected character '{}'", c))),
+            None => return Err(self.error("unexpected end of file")),
+        };
+        Ok(Json { value, line, column })
+    }
+
+    fn parse_literal(&mut self, literal: &str, value: JsonValue) -> Result<JsonValue, ParseError> {
+        let error = self.error(&format!("invalid literal, expected '{}'", literal));
+        for expected in literal.chars() {
+            if self.next() != Some(expected) {
+                return Err(error);
+            }
+        }
+        Ok(value)
+    }
+
+    fn parse_number(&mut self) -> Result<JsonValue, ParseError> {
+        let error = self.error("invalid number");
+        let mut text = String::new();
+        while let Some(c) = self.peek() {
+            if c.is_ascii_digit() || c == '-' || c == '+' || c == '.' || c == 'e' || c == 'E' {
+                text.push(c);
+                self.next();
+            } else {
+                break;
+            }
+        }
+        // Rust accepts a few forms JSON does not ("1.", ".5", "+1"), reject those first
+        let digits = text.trim_start_matches('-');
+        let mantissa = digits.split(['e', 'E']).next().unwrap_or("");
+        if digits.starts_with('+')
+            || mantissa.starts_with('.')
+            || mantissa.ends_with('.')
+            || (mantissa.len() > 1 && mantissa.starts_with('0') && !mantissa.starts_with("0."))
+        {
+            return Err(error);
+        }
+        text.parse().map(JsonValue::Number).map_err(|_| error)
+    }
+
+    fn parse_string(&mut self) -> Result<String, ParseError> {
+        self.expect('"')?;
+        let mut string = String::new();
+        loop {
+            let c = match self.next() {
+                Some(c) => c,
+                None => return Err(self.error("unterminated string")),
+            };
+            match c {
+                '"' => return Ok(string),
+                '\n' => return Err(self.error("unterminated string")),
+                '\\' => {
+                    let escaped = match self.next() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => self.parse_unicode_escape()?,
+                        _ => return Err(self.error("invalid escape sequence")),
+                    };
+                    string.push(escaped);
+                }
+                c => string.push(c),
+            }
+        }
+    }
+
+    // After "\u", including UTF-16 surrogate pairs ("\ud83d\ude00")
+    fn parse_unicode_escape(&mut self) -> Result<char, ParseError> {
+        let high = self.parse_hex4()?;
+        let code = if (0xD800..0xDC00).contains(&high) {
+            if self.next() != Some('\\') || self.next() != Some('u') {
+                return Err(self.error("unpaired surrogate in unicode escape"));
+            }
+            let low = self.parse_hex4()?;
+            if !(0xDC00..0xE000).contains(&low) {
+                return Err(self.error("unpaired surrogate in unicode escape"));
+            }
+            0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+        } else {
+            high
+        };
+        std::char::from_u32(code).ok_or_else(|| self.error("invalid unicode escape"))
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32, ParseError> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let digit = self.next().and_then(|c| c.to_digit(16));
+            match digit {
+                Some(digit) => code = code * 16 + digit,
+                None => return Err(self.error("invalid unicode escape")),
+            }
+        }
+        Ok(code)
+    }
+
+    fn parse_array(&mut self) -> Result<JsonValue, ParseError> {
+        self.expect('[')?;
+        let mut elements = vec![];
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.next();
+            return Ok(JsonValue::Array(elements));
+        }
+        loop {
+            self.skip_whitespace();
+            elements.push(self.parse_value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => { self.next(); }
+                Some(']') => {
+                    self.next();
+                    return Ok(JsonValue::Array(elements));
+                }
+                _ => return Err(self.error("expected ',' or ']' in array")),
+            }
+        }
+    }
+
+    fn parse_object(&mut self) -> Result<JsonValue, ParseError> {
+        self.expect('{')?;
+        let mut members: Vec<(String, Json)> = vec![];
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.next();
+            return Ok(JsonValue::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some('"') {
+                return Err(self.error("expected a string key in object"));
+            }
+            let key_error = self.error("duplicate key in object");
+            let key = self.parse_string()?;
+            if members.iter().any(|(existing, _)| *existing == key) {
+                return Err(ParseError { message: format!("{} '{}'", key_error.message, key), ..key_error });
+            }
+            self.skip_whitespace();
+            self.expect(':')?;
+            self.skip_whitespace();
+            let value = self.parse_value()?;
+            members.push((key, value));
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => { self.next(); }
+                Some('}') => {
+                    self.next();
+                    return Ok(JsonValue::Object(members));
+                }
+                _ => return Err(self.error("expected ',' or '}' in object")),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn document_should_parse() {
+        let json = parse("{\n  \"a\": [1, -2.5e1, true, null],\n  \"b\": \"x\\n\\u00e9\"\n}").unwrap();
+        let members = match json.value {
+            JsonValue::Object(members) => members,
+            _ => panic!("expected object"),
+        };
+        assert_eq!(members[0].0, "a");
+        let elements = match &members[0].1.value {
+            JsonValue::Array(elements) => elements.clone(),
+            _ => panic!("expected array"),
+        };
+        assert_eq!(elements[1].value, JsonValue::Number(-25.0));
+        assert_eq!(elements[2].value, JsonValue::Bool(true));
+        assert_eq!(elements[3].value, JsonValue::Null);
+        assert_eq!(members[1].1.value, JsonValue::String(String::from("x\n\u{e9}")));
+        // Positions of values are remembered
+        assert_eq!((members[1].1.line, members[1].1.column), (3, 8));
+    }
+
+    #[test]
+    fn errors_should_report_position() {
+        let error = parse("{\n  \"a\": 1,\n  \"b\" 2\n}").unwrap_err();
+        assert_eq!((error.line, error.column), (3, 7));
+        let error = parse("[1, 2,]").unwrap_err();
+        assert_eq!((error.line, error.column), (1, 7));
+        assert!(parse("{\"a\": 1, \"a\": 2}").unwrap_err().message.contains("duplicate key"));
+    }
+
+    #[test]
+    fn invalid_numbers_should_be_rejected() {
+        for text in ["01", "1.", ".5", "+1", "1e", "--1"].iter() {
+            assert!(parse(text).is_err(), "{} should not parse", text);
+        }
+        assert_eq!(parse("0.5").unwrap().value, JsonValue::Number(0.5));
+    }
+}
@@ -1,14 +1,32 @@
 // To run this: `cargo run > image.ppm` from project root
+// Options: `cargo run -- --help`
 
-#[macro_use]
-extern crate lazy_static;
 extern crate rand;
 
+mod aabb;
+mod bvh;
+mod cli;
 mod color;
 mod encoder;
+mod framebuffer;
 mod point;
 mod ray;
 mod sphere;
+mod triangle;
+mod quad;
+mod plane;
+mod cylinder;
+mod torus;
+mod transform;
+mod instance;
+mod medium;
+mod csg;
+mod sdf;
+mod heightfield;
+mod implicit;
+mod curve;
+mod mesh;
+mod obj;
 mod vec3;
 mod utils;
 mod hittable;
@@ -16,14 +34,24 @@ mod world;
 mod camera;
 mod material;
 mod scene;
+mod render;
+mod json;
+mod scene_file;
+mod png;
+mod zlib;
+mod texture;
+mod noise;
+mod image;
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::process;
 
-use color::Color;
-use point::Point;
-use ray::Ray;
-use vec3::Vec3;
-use world::World;
 use camera::Camera;
-use scene::{random_scene};
+use cli::{CliError, Options};
+use encoder::ImageFormat;
+use render::RenderSettings;
 
 // NOTE
 // Convention for coordinates is such that towards the image from camera is -ve
@@ -35,112 +63,91 @@ use scene::{random_scene};
 //                IMAGE
 //
 
-/* ==========
- * = CAMERA =
- * ==========
- */
-
-const ORIGIN: Point = Point { x: 0.0, y: 0.0, z: 0.0 };
-
-/* =========
- * = IMAGE =
- * =========
- */
-
-// IMAGE DIMENSIONS in NUMBER OF PIXELS
-const ASPECT_RATIO: f64 = 16.0 / 9.0; // width / height
-const IMAGE_PIXEL_HEIGHT: i32 = 216;
-const IMAGE_PIXEL_WIDTH: i32 = (IMAGE_PIXEL_HEIGHT as f64 * ASPECT_RATIO) as i32;
-const SAMPLES_PER_PIXEL: i32 = 100;
-
-// IMAGE DIMENSIONS in CARTESIAN
-const VIEWPORT_HEIGHT: f64 = 2.0;
-const VIEWPORT_WIDTH: f64 = VIEWPORT_HEIGHT * ASPECT_RATIO;
-const FOCAL_LENGTH: f64 = 1.0; // Orthogonal distance from the camera to the screen
-
-// MAX COLOUR VALUE (8 bit)
-const MAX_COLOUR_VALUE: i32 = 255;
-
-// IMAGE DIRECTION VECTORS
-const HORIZONTAL_DIRECTION_VECTOR: Vec3 = Vec3 { x: VIEWPORT_WIDTH, y: 0.0, z: 0.0 };
-const VERTICAL_DIRECTION_VECTOR: Vec3 = Vec3 { x: 0.0, y: VIEWPORT_HEIGHT, z: 0.0 };
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let options = match cli::parse_args(args.clone()) {
+        Ok(options) => options,
+        Err(CliError::HelpRequested) => {
+            println!("{}", cli::USAGE);
+            return;
+        }
+        Err(error) => {
+            eprintln!("error: {}\n\n{}", error, cli::USAGE);
+            process::exit(2);
+        }
+    };
 
-// IMAGE POINTS
-lazy_static! {
-    pub static ref IMAGE_CENTER: Point = ORIGIN - Vec3 { x: 0.0, y: 0.0, z: FOCAL_LENGTH };
-    pub static ref IMAGE_LOWER_LEFT_CORNER: Point = *IMAGE_CENTER
-        - HORIZONTAL_DIRECTION_VECTOR / 2.0
-        - VERTICAL_DIRECTION_VECTOR / 2.0;
+    if let Err(error) = run(options, &args) {
+        eprintln!("error: {}", error);
+        process::exit(1);
+    }
 }
 
-// RAY CONSTANTS
-const MAX_DEPTH: i32 = 100; // Maximum number of times rays can diffuse
-fn main() {
+fn run(options: Options, args: &[String]) -> Result<(), String> {
+    // Seed before building the scene, so that random scenes are reproducible too
+    if let Some(seed) = options.seed {
+        utils::seed_rng(seed);
+    }
 
-    // prints to stdout the header encoding for ppm
-    encoder::ppm_headers(IMAGE_PIXEL_WIDTH, IMAGE_PIXEL_HEIGHT, MAX_COLOUR_VALUE);
-    let world = random_scene();
+    // Anything other than a built-in scene name is a scene file,
+    // whose settings sit between the defaults and the command-line options
+    let (world, options) = match scene::named_scene(&options.scene) {
+        Some(world) => (world, options),
+        None => {
+            let (world, file_options) = scene_file::load_scene(Path::new(&options.scene), Options::default())
+                .map_err(|error| error.to_string())?;
+            let options = cli::parse_args_onto(file_options, args.to_vec())
+                .map_err(|error| error.to_string())?;
+            (world, options)
+        }
+    };
 
     // Initialize camera
-    let look_from = Point::new(13.0, 2.0, 3.0);
-    let look_at = Point::new(0.0, 0.0, 0.0);
-    let vup = Vec3::new(0.0, 1.0, 0.0);
-    let dist_to_focus = 10.0;
-    let aperture = 0.1;
-    let vertical_fov = 20.0;
+    let camera_options = &options.camera;
     let camera = Camera::new(
-        look_from,
-        look_at,
-        vup,
-        vertical_fov,
-        ASPECT_RATIO,
-        aperture,
-        dist_to_focus,
-    );
-
-    // Write the pixels from top to bottom row
-    for height in (0..IMAGE_PIXEL_HEIGHT).rev() {
-        eprintln!("\rScanlines remaining: {}", height);
-        // Write the pixels for each row from left to right
-        for width in 0..IMAGE_PIXEL_WIDTH {
-            let mut pixel_color = Color::new(0.0, 0.0, 0.0);
-            for _samples in 0..SAMPLES_PER_PIXEL {
-                // Horizontal direction vector
-                let u = (width as f64 + rand::random::<f64>()) / (IMAGE_PIXEL_WIDTH - 1) as f64;
-
-                // Vertical direction vector
-                let v = (height as f64 + rand::random::<f64>()) / (IMAGE_PIXEL_HEIGHT - 1) as f64;
-                let ray = camera.get_ray(u, v);
-                pixel_color = pixel_color + ray_color(ray, &world, MAX_DEPTH);
-            }
-            pixel_color.encode_as_ppm_pixel(SAMPLES_PER_PIXEL);
-        }
+        camera_options.look_from,
+        camera_options.look_at,
+        camera_options.vup,
+        camera_options.vertical_fov,
+        options.aspect_ratio(),
+        camera_options.aperture,
+        camera_options.focus_dist,
+    )
+    .with_shutter(camera_options.shutter.0, camera_options.shutter.1);
+
+    let settings = RenderSettings {
+        image_width: options.image_width,
+        image_height: options.image_height,
+        samples_per_pixel: options.samples_per_pixel,
+        max_depth: options.max_depth,
+        seed: options.seed,
+        threads: options.threads,
+        background: options.background,
+    };
+
+    // Open the outputs before rendering, so a bad path fails fast
+    let mut outputs: Vec<(ImageFormat, Box<dyn Write>)> = vec![];
+    for path in &options.outputs {
+        let format = match options.format {
+            Some(format) => format,
+            None => ImageFormat::from_path(path)?,
+        };
+        let file = File::create(path)
+            .map_err(|error| format!("cannot create '{}': {}", path.display(), error))?;
+        outputs.push((format, Box::new(BufWriter::new(file))));
     }
-    eprintln!("\nDone.\n")
-}
-
-fn ray_color(ray: Ray, world: &World, depth: i32) -> Vec3 {
-    let unit_color = Color { x: 1.0, y: 1.0, z: 1.0 };
-
-    if depth <= 0 {
-        return Color::new(0.0, 0.0, 0.0);
+    if outputs.is_empty() {
+        outputs.push((options.format.unwrap_or(ImageFormat::Ppm), Box::new(BufWriter::new(io::stdout()))));
     }
 
-    // TODO Use more efficient way to find nearest point
-    // TODO Refactor this to be a ray event, on hitting world object
-    match world.nearest_point(ray) {
-        Some ((t, normal, material)) => {
-            if let Some (scattered_ray) = material.scatter(ray, t, normal) {
-                return material.get_albedo() * ray_color(scattered_ray, world, depth - 1);
-            }
-            return Color::new(0.0, 0.0, 0.0);
-        },
+    let framebuffer = render::render(&world, &camera, &settings);
 
-        None => {
-            let unit_direction = ray.direction.unit_vector();
-            let t = 0.5 * (unit_direction.y + 1.0);
-            return (1.0 - t) * unit_color
-                + t * Color { x: 0.5, y: 0.7, z: 1.0 };
-        }
+    for (format, out) in outputs.iter_mut() {
+        encoder::write_image(out, *format, &framebuffer)
+            .and_then(|_| out.flush())
+            .map_err(|error| format!("cannot write image: {}", error))?;
     }
+
+    eprintln!("\nDone.\n");
+    Ok(())
 }
@@ -1,52 +1,69 @@
 extern crate rand;
 use rand::Rng;
+use std::sync::Arc;
 
 use crate::color::Color;
+use crate::hittable::HitRecord;
+use crate::point::Point;
 use crate::ray::Ray;
+use crate::texture::{SolidColor, Texture};
 use crate::vec3::Vec3;
+use crate::utils;
 
-pub trait Material {
-    // Amount of light reflected
-    fn get_albedo (&self) -> Color;
+pub trait Material: Send + Sync {
+    // Amount of light reflected at the hit
+    fn get_albedo (&self, hit: &HitRecord) -> Color;
 
     // Ray absorbed -> None
     // Ray scattered -> Some (Scattered Ray)
-    fn scatter (&self, ray: Ray, t: f64, normal: Vec3) -> Option<Ray>;
+    fn scatter (&self, ray: Ray, hit: &HitRecord) -> Option<Ray>;
+
+    // Light given off at surface coordinates (u, v) and point p, nothing for most materials
+    fn emitted (&self, _u: f64, _v: f64, _p: Point) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
 }
 
 pub struct Lambertian {
-    albedo: Color
+    albedo: Arc<dyn Texture>,
 }
 
 impl Lambertian {
     pub fn new(albedo: Color) -> Self {
+        Self::textured(Arc::new(SolidColor::new(albedo)))
+    }
+
+    pub fn textured(albedo: Arc<dyn Texture>) -> Self {
         Self { albedo }
     }
 }
 
 impl Material for Lambertian {
-    fn get_albedo (&self) -> Color {
-        self.albedo
+    fn get_albedo (&self, hit: &HitRecord) -> Color {
+        self.albedo.value(hit.u, hit.v, hit.point)
     }
 
     fn scatter(&self,
                ray: Ray,
-               t: f64,
-               normal: Vec3)
+               hit: &HitRecord)
                -> Option<Ray> {
-        let scatter_direction = normal + Vec3::random_unit_vector();
-        let scattered_ray = Ray::new(ray.at(t), scatter_direction);
-        return Some(scattered_ray);
+        let scatter_direction = hit.facing_normal() + Vec3::random_unit_vector();
+        let scattered_ray = Ray::with_time(hit.point, scatter_direction, ray.time);
+        Some(scattered_ray)
     }
 }
 
 pub struct Metal {
-    albedo: Color,
+    albedo: Arc<dyn Texture>,
     fuzz: f64,
 }
 
 impl Metal {
     pub fn new(albedo: Color, fuzz: f64) -> Self {
+        Self::textured(Arc::new(SolidColor::new(albedo)), fuzz)
+    }
+
+    pub fn textured(albedo: Arc<dyn Texture>, fuzz: f64) -> Self {
         let corrected_fuzz = if fuzz < 1.0 {
             fuzz
         } else {
@@ -57,18 +74,18 @@ impl Metal {
 }
 
 impl Material for Metal {
-    fn get_albedo (&self) -> Color {
-        self.albedo
+    fn get_albedo (&self, hit: &HitRecord) -> Color {
+        self.albedo.value(hit.u, hit.v, hit.point)
     }
 
     fn scatter(&self,
                ray: Ray,
-               t: f64,
-               normal: Vec3)
+               hit: &HitRecord)
                -> Option<Ray> {
+        let normal = hit.facing_normal();
         let reflected_ray_direction = ray.direction.unit_vector().reflect(normal);
         let scattered_ray_direction = reflected_ray_direction + self.fuzz * Vec3::random_point_in_unit_sphere();
-        let reflected_ray = Ray::new(ray.at(t), scattered_ray_direction);
+        let reflected_ray = Ray::with_time(hit.point, scattered_ray_direction, ray.time);
         if reflected_ray_direction.dot(normal) > 0.0 {
             return Some(reflected_ray);
         }
@@ -89,54 +106,152 @@ impl Dielectric {
 }
 
 impl Material for Dielectric {
-    fn get_albedo (&self) -> Color {
+    fn get_albedo (&self, _hit: &HitRecord) -> Color {
         self.albedo
     }
 
     fn scatter(&self,
                ray: Ray,
-               t: f64,
-               normal: Vec3)
+               hit: &HitRecord)
                -> Option<Ray> {
         let unit_direction = ray.direction.unit_vector();
 
-        // normal is always outward
-        // Check if the ray is inside or outside the sphere
-        let is_inside = unit_direction.dot(normal) > 0.0;
-
         // Ensure normal used is always against the incident ray
-        let opposite_normal = if is_inside { -normal } else { normal };
+        let opposite_normal = hit.facing_normal();
 
         // If the ray is coming from within, use the object's refractive index
-        let refractive_index = if is_inside { self.refractive_index } else { 1.0 / self.refractive_index };
+        let refractive_index = if hit.front_face { 1.0 / self.refractive_index } else { self.refractive_index };
 
         let cos_theta = f64::min((-unit_direction).dot(opposite_normal), 1.0);
         let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
 
         if refractive_index * sin_theta > 1.0 {
             let reflected_ray_direction = unit_direction.reflect(opposite_normal);
-            let scattered_ray = Ray::new(ray.at(t), reflected_ray_direction);
+            let scattered_ray = Ray::with_time(hit.point, reflected_ray_direction, ray.time);
             return Some(scattered_ray);
         }
 
         // Schlick
         let reflect_probability = schlick(cos_theta, refractive_index);
-        let mut rng = rand::thread_rng();
+        let mut rng = utils::rng();
 
         if rng.gen_range(0.0, 1.0)< reflect_probability {
             let reflected_ray_direction = unit_direction.reflect(opposite_normal);
-            let scattered_ray = Ray::new(ray.at(t), reflected_ray_direction);
+            let scattered_ray = Ray::with_time(hit.point, reflected_ray_direction, ray.time);
             return Some(scattered_ray);
         }
 
         let refracted_direction_vector = unit_direction.refract(opposite_normal, refractive_index);
-        let scattered_ray = Ray::new(ray.at(t), refracted_direction_vector);
-        return Some(scattered_ray);
+        let scattered_ray = Ray::with_time(hit.point, refracted_direction_vector, ray.time);
+        Some(scattered_ray)
+    }
+}
+
+// Area light, emits the same light in every direction and reflects nothing
+pub struct DiffuseLight {
+    emit: Arc<dyn Texture>,
+}
+
+impl DiffuseLight {
+    pub fn textured(emit: Arc<dyn Texture>) -> Self {
+        Self { emit }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn get_albedo (&self, _hit: &HitRecord) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+
+    fn scatter(&self,
+               _ray: Ray,
+               _hit: &HitRecord)
+               -> Option<Ray> {
+        None
+    }
+
+    fn emitted(&self, u: f64, v: f64, p: Point) -> Color {
+        self.emit.value(u, v, p)
+    }
+}
+
+// Phase function of a participating medium, scattering equally in every direction
+pub struct Isotropic {
+    albedo: Arc<dyn Texture>,
+}
+
+impl Isotropic {
+    pub fn textured(albedo: Arc<dyn Texture>) -> Self {
+        Self { albedo }
+    }
+}
+
+impl Material for Isotropic {
+    fn get_albedo (&self, hit: &HitRecord) -> Color {
+        self.albedo.value(hit.u, hit.v, hit.point)
+    }
+
+    fn scatter(&self,
+               ray: Ray,
+               hit: &HitRecord)
+               -> Option<Ray> {
+        Some(Ray::with_time(hit.point, Vec3::random_unit_vector(), ray.time))
+    }
+}
+
+// Fibers of hair or fur, shading along the hit's tangent
+// A fiber is thinner than the detail a ray can resolve, so rather than where the ray met it,
+// the reflection picks a random offset across it, as if off a smooth round strand. Light
+// leaves on the cone around the fiber at the angle it came in, spread by roughness.
+// Surfaces without a tangent reflect it like a metal.
+pub struct Hair {
+    albedo: Arc<dyn Texture>,
+    roughness: f64,
+}
+
+impl Hair {
+    pub fn textured(albedo: Arc<dyn Texture>, roughness: f64) -> Self {
+        Self { albedo, roughness: roughness.min(1.0) }
+    }
+}
+
+impl Material for Hair {
+    fn get_albedo (&self, hit: &HitRecord) -> Color {
+        self.albedo.value(hit.u, hit.v, hit.point)
+    }
+
+    fn scatter(&self,
+               ray: Ray,
+               hit: &HitRecord)
+               -> Option<Ray> {
+        let facing = hit.facing_normal();
+        let normal = match hit.tangent {
+            Some(tangent) => {
+                // Square to the fiber, on the side the ray came from
+                let front = facing - facing.dot(tangent) * tangent;
+                if front.length_squared() < 1e-12 {
+                    facing
+                } else {
+                    let front = front.unit_vector();
+                    let offset: f64 = utils::rng().gen_range(-1.0, 1.0);
+                    (1.0 - offset * offset).sqrt() * front + offset * tangent.cross(front)
+                }
+            }
+            None => facing,
+        };
+        let reflected = ray.direction.unit_vector().reflect(normal);
+        let direction = reflected + self.roughness * Vec3::random_point_in_unit_sphere();
+        // Roughness pushing light into the strand is mirrored back out of it, so nothing is
+        // absorbed. Around a round strand light can leave on either side of the surface the
+        // ray hit, as long as it leaves the strand.
+        let into = direction.dot(normal);
+        let direction = if into < 0.0 { direction - 2.0 * into * normal } else { direction };
+        Some(Ray::with_time(hit.point, direction, ray.time))
     }
 }
 
 fn schlick(cosine: f64, refractive_index: f64) -> f64 {
     let r0 = (1.0 - refractive_index) / (1.0 + refractive_index);
     let r0_squared = r0 * r0;
-    return r0_squared + (1.0 - r0_squared) * (1.0 - cosine).powi(5);
+    r0_squared + (1.0 - r0_squared) * (1.0 - cosine).powi(5)
 }
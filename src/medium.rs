@@ -0,0 +1,154 @@
+extern crate rand;
+use rand::Rng;
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::utils;
+use crate::vec3::Vec3;
+
+// Most boundaries are entered and left once or twice, this only stops endless loops
+const MAX_SEGMENTS: usize = 64;
+
+// Fog, smoke or mist of the same density throughout the inside of a boundary object
+// A ray crossing it scatters after a random distance, exponentially distributed with mean
+// 1 / density, or passes straight through if that is further than the way out. The phase
+// material (e.g. Isotropic) picks the scattered direction.
+pub struct ConstantMedium {
+    boundary: Arc<dyn Hittable>,
+    density: f64,
+    phase: Arc<dyn Material>,
+}
+
+impl ConstantMedium {
+    pub fn new(boundary: Arc<dyn Hittable>, density: f64, phase: Arc<dyn Material>) -> Self {
+        ConstantMedium { boundary, density, phase }
+    }
+}
+
+impl Hittable for ConstantMedium {
+    // The ray is inside wherever its next crossing of the boundary leaves it, which tells rays
+    // starting inside (e.g. scattered by the medium itself) from rays starting outside
+    // without asking the boundary for hits behind the origin
+    fn hit(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let speed = ray.direction.length();
+        let mut start = t_min;
+        for _ in 0..MAX_SEGMENTS {
+            let crossing = self.boundary.hit(ray, start, f64::INFINITY)?;
+            let (enter, exit) = if crossing.front_face {
+                // Entering, the medium lasts until the ray leaves again
+                let exit = match self.boundary.hit(ray, crossing.t, f64::INFINITY) {
+                    Some(exit) => exit.t,
+                    None => f64::INFINITY,
+                };
+                (crossing.t, exit)
+            } else {
+                (start, crossing.t)
+            };
+            if enter >= t_max {
+                return None;
+            }
+
+            // Distances in the medium are memoryless, so each stretch inside is sampled afresh
+            let distance = -(1.0 - utils::rng().gen::<f64>()).ln() / self.density;
+            let t = enter + distance / speed;
+            if t < exit.min(t_max) {
+                // Scattering happens inside the volume, not on a surface, so the normal is arbitrary
+                let mut hit = HitRecord::new(ray, t, Vec3::new(1.0, 0.0, 0.0), (0.0, 0.0), self.phase.clone());
+                hit.front_face = true;
+                return Some(hit);
+            }
+            if exit >= t_max {
+                return None;
+            }
+            start = exit;
+        }
+        None
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.boundary.bounding_box()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::Isotropic;
+    use crate::texture::SolidColor;
+    use crate::point::Point;
+    use crate::quad::Cuboid;
+    use crate::sphere::Sphere;
+    use crate::world::World;
+
+    fn isotropic() -> Arc<dyn Material> {
+        Arc::new(Isotropic::textured(Arc::new(SolidColor::new(Color::new(0.8, 0.8, 0.8)))))
+    }
+
+    fn fog(boundary: Arc<dyn Hittable>, density: f64) -> ConstantMedium {
+        ConstantMedium::new(boundary, density, isotropic())
+    }
+
+    #[test]
+    fn dense_medium_should_scatter_just_inside() {
+        let material = isotropic();
+        let medium = fog(Arc::new(Sphere::new(Point::new(0.0, 0.0, 0.0), 1.0, material)), 1e6);
+        let ray = Ray::new(Point::new(-5.0, 0.0, 0.0), Vec3::new(2.0, 0.0, 0.0));
+        let hit = medium.hit(ray, 0.001, f64::INFINITY).unwrap();
+        assert!(hit.t >= 2.0 && hit.t < 2.0 + 1e-4, "{}", hit.t);
+        assert!(medium.hit(ray, 0.001, 1.9).is_none());
+        // Missing the boundary
+        assert!(medium.hit(Ray::new(Point::new(-5.0, 2.0, 0.0), Vec3::new(1.0, 0.0, 0.0)), 0.001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn rays_starting_inside_should_scatter() {
+        let material = isotropic();
+        let boundary = Arc::new(Cuboid::new(Point::new(-1.0, -1.0, -1.0), Point::new(1.0, 1.0, 1.0), material));
+        let medium = fog(boundary, 1e6);
+        // From the center the only crossing ahead is the way out
+        let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0));
+        let hit = medium.hit(ray, 0.001, f64::INFINITY).unwrap();
+        assert!(hit.t < 0.002);
+        // Thin fog scatters about as often as the chance of meeting it within the distance
+        let thin = fog(Arc::new(Sphere::new(Point::new(0.0, 0.0, 0.0), 1.0, isotropic())), 0.5);
+        let hits = (0..10_000).filter(|_| thin.hit(ray, 0.0, f64::INFINITY).is_some()).count();
+        let expected = 10_000.0 * (1.0 - (-0.5f64).exp());
+        assert!((hits as f64 - expected).abs() < 300.0, "{} hits", hits);
+        for _ in 0..100 {
+            if let Some(hit) = thin.hit(ray, 0.0, f64::INFINITY) {
+                assert!(hit.t > 0.0 && hit.t < 1.0);
+            }
+        }
+    }
+
+    #[test]
+    fn every_stretch_inside_should_count() {
+        // Two separate spheres make a boundary the ray enters twice
+        let material = isotropic();
+        let pair = World::new(vec![
+            Arc::new(Sphere::new(Point::new(-2.0, 0.0, 0.0), 0.5, material.clone())),
+            Arc::new(Sphere::new(Point::new(2.0, 0.0, 0.0), 0.5, material)),
+        ]);
+        let medium = fog(Arc::new(pair), 1.0);
+        let ray = Ray::new(Point::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let (mut near, mut far) = (0, 0);
+        for _ in 0..10_000 {
+            match medium.hit(ray, 0.001, f64::INFINITY) {
+                Some(hit) if hit.point.x < 0.0 => near += 1,
+                Some(hit) => {
+                    assert!(hit.point.x > 1.5 && hit.point.x < 2.5);
+                    far += 1
+                }
+                None => (),
+            }
+        }
+        // Each 1 unit long stretch scatters 1 - 1/e of the rays reaching it
+        let reach_far = 10_000.0 * (-1.0f64).exp();
+        assert!((near as f64 - 10_000.0 * (1.0 - (-1.0f64).exp())).abs() < 300.0, "{}", near);
+        assert!((far as f64 - reach_far * (1.0 - (-1.0f64).exp())).abs() < 200.0, "{}", far);
+    }
+}
@@ -0,0 +1,141 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::bvh::BvhNode;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::point::Point;
+use crate::ray::Ray;
+use crate::triangle::{triangle_bounding_box, triangle_hit_record};
+use crate::vec3::Vec3;
+
+// Vertex attributes shared by all the triangles of a mesh
+// A vertex used by several faces is stored once, faces refer to it by index.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MeshData {
+    pub positions: Vec<Point>,
+    pub normals: Vec<Vec3>, // Unit vectors
+    pub uvs: Vec<(f64, f64)>,
+}
+
+// Corner of a face, as indices into MeshData
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FaceVertex {
+    pub position: usize,
+    pub normal: Option<usize>,
+    pub uv: Option<usize>,
+}
+
+#[derive(Clone)]
+pub struct Face {
+    pub vertices: [FaceVertex; 3],
+    pub material: Arc<dyn Material>,
+}
+
+// Triangles sharing vertex buffers, with their own bounding volume hierarchy
+// so that the world BVH treats the whole mesh as one object.
+pub struct TriangleMesh {
+    bvh: BvhNode,
+}
+
+impl TriangleMesh {
+    // Indices in faces must be valid for data, and there must be at least one face
+    pub fn new(data: MeshData, faces: Vec<Face>) -> Self {
+        let data = Arc::new(data);
+        let triangles: Vec<Arc<dyn Hittable>> = faces
+            .into_iter()
+            .map(|face| Arc::new(MeshTriangle { data: data.clone(), face }) as Arc<dyn Hittable>)
+            .collect();
+        TriangleMesh { bvh: BvhNode::new(triangles) }
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        self.bvh.hit(ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bvh.bounding_box()
+    }
+}
+
+struct MeshTriangle {
+    data: Arc<MeshData>,
+    face: Face,
+}
+
+impl MeshTriangle {
+    fn positions(&self) -> [Point; 3] {
+        let [a, b, c] = self.face.vertices;
+        [self.data.positions[a.position], self.data.positions[b.position], self.data.positions[c.position]]
+    }
+}
+
+impl Hittable for MeshTriangle {
+    fn hit(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let [a, b, c] = self.face.vertices;
+
+        // Attributes are only interpolated when every corner has one
+        let normals = match (a.normal, b.normal, c.normal) {
+            (Some(na), Some(nb), Some(nc)) => Some([self.data.normals[na], self.data.normals[nb], self.data.normals[nc]]),
+            _ => None,
+        };
+        let uvs = match (a.uv, b.uv, c.uv) {
+            (Some(ta), Some(tb), Some(tc)) => [self.data.uvs[ta], self.data.uvs[tb], self.data.uvs[tc]],
+            _ => [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)],
+        };
+        triangle_hit_record(self.positions(), normals, uvs, &self.face.material, ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(triangle_bounding_box(self.positions()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::Lambertian;
+
+    fn corner(position: usize) -> FaceVertex {
+        FaceVertex { position, normal: None, uv: None }
+    }
+
+    #[test]
+    fn mesh_should_hit_nearest_triangle() {
+        let material: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        // Unit square at z = -1 made of two triangles, and a triangle behind it at z = -2
+        let data = MeshData {
+            positions: vec![
+                Point::new(0.0, 0.0, -1.0),
+                Point::new(1.0, 0.0, -1.0),
+                Point::new(1.0, 1.0, -1.0),
+                Point::new(0.0, 1.0, -1.0),
+                Point::new(0.0, 0.0, -2.0),
+                Point::new(1.0, 0.0, -2.0),
+                Point::new(0.0, 1.0, -2.0),
+            ],
+            ..MeshData::default()
+        };
+        let faces = vec![
+            Face { vertices: [corner(0), corner(1), corner(2)], material: material.clone() },
+            Face { vertices: [corner(0), corner(2), corner(3)], material: material.clone() },
+            Face { vertices: [corner(4), corner(5), corner(6)], material },
+        ];
+        let mesh = TriangleMesh::new(data, faces);
+
+        let ray = Ray::new(Point::new(0.2, 0.2, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert_eq!(mesh.hit(ray, 0.001, f64::INFINITY).unwrap().t, 1.0);
+        // Along the diagonal shared by the two square triangles
+        let diagonal = Ray::new(Point::new(0.5, 0.5, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert_eq!(mesh.hit(diagonal, 0.001, f64::INFINITY).unwrap().t, 1.0);
+        assert_eq!(mesh.hit(ray, 1.5, f64::INFINITY).unwrap().t, 2.0);
+
+        let bbox = mesh.bounding_box().unwrap();
+        // Padded slightly, since every triangle is flat in z
+        assert!((bbox.minimum.z + 2.0).abs() < 1e-3);
+        assert_eq!(bbox.maximum.y, 1.0);
+    }
+}
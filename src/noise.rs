@@ -0,0 +1,198 @@
+// Gradient noise for procedural textures
+//
+// Perlin's improved noise (https://mrl.cs.nyu.edu/~perlin/noise/): every lattice point gets a
+// pseudo-random gradient, and the noise at a point blends the gradients of the 8 surrounding
+// lattice points with a smooth fade curve. It is 0 at every lattice point and varies smoothly,
+// with features about one unit apart.
+//
+// The permutation is shuffled with a generator of our own rather than one from the rand crate,
+// so that the same seed gives the same noise whatever the version of rand.
+
+use crate::point::Point;
+
+#[derive(Clone)]
+pub struct Perlin {
+    // Permutation of 0..256, repeated once so lookups of i + 1 need no wrapping
+    permutation: [u8; 512],
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        let mut values: Vec<u8> = (0..=255).collect();
+        // Fisher-Yates
+        let mut state = seed;
+        for i in (1..values.len()).rev() {
+            let j = (splitmix64(&mut state) % (i as u64 + 1)) as usize;
+            values.swap(i, j);
+        }
+        let mut permutation = [0; 512];
+        for (i, value) in permutation.iter_mut().enumerate() {
+            *value = values[i % 256];
+        }
+        Perlin { permutation }
+    }
+
+    // Roughly in [-1, 1]
+    pub fn noise(&self, p: Point) -> f64 {
+        let (x_floor, y_floor, z_floor) = (p.x.floor(), p.y.floor(), p.z.floor());
+        // Lattice cell, wrapped to the 256 cell period of the permutation
+        let xi = (x_floor as i64).rem_euclid(256) as usize;
+        let yi = (y_floor as i64).rem_euclid(256) as usize;
+        let zi = (z_floor as i64).rem_euclid(256) as usize;
+        // Position within the cell
+        let (x, y, z) = (p.x - x_floor, p.y - y_floor, p.z - z_floor);
+        let (u, v, w) = (fade(x), fade(y), fade(z));
+
+        let hash = |dx: usize, dy: usize, dz: usize| {
+            let perm = &self.permutation;
+            perm[perm[perm[xi + dx] as usize + yi + dy] as usize + zi + dz]
+        };
+        let corner = |dx: usize, dy: usize, dz: usize| {
+            gradient(hash(dx, dy, dz), x - dx as f64, y - dy as f64, z - dz as f64)
+        };
+
+        lerp(
+            w,
+            lerp(v, lerp(u, corner(0, 0, 0), corner(1, 0, 0)), lerp(u, corner(0, 1, 0), corner(1, 1, 0))),
+            lerp(v, lerp(u, corner(0, 0, 1), corner(1, 0, 1)), lerp(u, corner(0, 1, 1), corner(1, 1, 1))),
+        )
+    }
+
+    // Sum of |noise| over octaves of doubling frequency and halving weight, in [0, 2)
+    // The absolute value gives creases where the noise crosses 0, like veins or flames
+    pub fn turbulence(&self, p: Point, octaves: u32) -> f64 {
+        let mut sum = 0.0;
+        let mut point = p;
+        let mut weight = 1.0;
+        for _ in 0..octaves {
+            sum += weight * self.noise(point).abs();
+            weight *= 0.5;
+            point = point * 2.0;
+        }
+        sum
+    }
+
+    // Fractional Brownian motion: sum of signed noise over octaves
+    // Each octave multiplies the frequency by lacunarity and the amplitude by gain
+    pub fn fbm(&self, p: Point, octaves: u32, lacunarity: f64, gain: f64) -> f64 {
+        let mut sum = 0.0;
+        let mut point = p;
+        let mut amplitude = 1.0;
+        for _ in 0..octaves {
+            sum += amplitude * self.noise(point);
+            amplitude *= gain;
+            point = point * lacunarity;
+        }
+        sum
+    }
+}
+
+// 6t^5 - 15t^4 + 10t^3, has zero first and second derivatives at 0 and 1
+// so the noise is smooth across cell boundaries
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: f64, a: f64, b: f64) -> f64 {
+    a + t * (b - a)
+}
+
+// Dot product of (x, y, z) with one of the 12 vectors from the center of a cube to its edges,
+// picked by the low 4 bits of the hash (4 of the 16 are repeated)
+fn gradient(hash: u8, x: f64, y: f64, z: f64) -> f64 {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = if h < 4 {
+        y
+    } else if h == 12 || h == 14 {
+        x
+    } else {
+        z
+    };
+    (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
+
+// https://prng.di.unimi.it/splitmix64.c
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Points spread over several lattice cells, including negative ones
+    fn sample_points() -> Vec<Point> {
+        (0..2000)
+            .map(|i| {
+                let i = i as f64;
+                Point::new((i * 0.137).sin() * 20.0, (i * 0.071).cos() * 20.0, i * 0.013 - 10.0)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_should_give_same_noise() {
+        let (a, b, c) = (Perlin::new(42), Perlin::new(42), Perlin::new(43));
+        let points = sample_points();
+        assert!(points.iter().all(|&p| a.noise(p) == b.noise(p)));
+        assert!(points.iter().any(|&p| a.noise(p) != c.noise(p)));
+    }
+
+    #[test]
+    fn seed_should_fix_values() {
+        // Golden values, changing these changes every image using noise
+        const GOLDEN: f64 = 0.31773638365950074;
+        let mut state = 7;
+        assert_eq!(splitmix64(&mut state), 0x63CB_E1E4_5932_0DD7);
+        let perlin = Perlin::new(7);
+        let value = perlin.noise(Point::new(1.3, -2.7, 0.45));
+        assert!((value - GOLDEN).abs() < 1e-12, "{}", value);
+        // The lattice repeats every 256 units
+        assert!((perlin.noise(Point::new(1.3 + 256.0, -2.7, 0.45)) - value).abs() < 1e-9);
+    }
+
+    #[test]
+    fn noise_should_be_zero_on_lattice_and_bounded() {
+        let perlin = Perlin::new(1);
+        for x in -3..3 {
+            for z in -3..3 {
+                assert_eq!(perlin.noise(Point::new(x as f64, 1.0, z as f64)), 0.0);
+            }
+        }
+        for p in sample_points() {
+            let value = perlin.noise(p);
+            assert!(value.abs() <= 1.0, "noise {} at {:?}", value, p);
+        }
+    }
+
+    #[test]
+    fn noise_should_be_continuous() {
+        let perlin = Perlin::new(3);
+        for p in sample_points() {
+            let step = Point::new(1e-6, 1e-6, 1e-6);
+            assert!((perlin.noise(p) - perlin.noise(p + step)).abs() < 1e-4);
+        }
+        // Across a cell boundary too
+        let below = perlin.noise(Point::new(0.5, 1.0 - 1e-9, 0.5));
+        let above = perlin.noise(Point::new(0.5, 1.0 + 1e-9, 0.5));
+        assert!((below - above).abs() < 1e-6);
+    }
+
+    #[test]
+    fn octaves_should_add_detail() {
+        let perlin = Perlin::new(5);
+        for p in sample_points().into_iter().take(100) {
+            assert_eq!(perlin.fbm(p, 1, 2.0, 0.5), perlin.noise(p));
+            assert_eq!(perlin.turbulence(p, 1), perlin.noise(p).abs());
+            let turbulence = perlin.turbulence(p, 6);
+            assert!((0.0..2.0).contains(&turbulence));
+            let fbm = perlin.fbm(p, 2, 2.0, 0.5);
+            assert_eq!(fbm, perlin.noise(p) + 0.5 * perlin.noise(p * 2.0));
+        }
+    }
+}
@@ -0,0 +1,545 @@
+// Wavefront OBJ meshes (http://paulbourke.net/dataformats/obj/) and their MTL material
+// libraries (http://paulbourke.net/dataformats/mtl/)
+//
+// Supported OBJ statements: v, vn, vt, f (any polygon, triangulated), g, mtllib and usemtl.
+// Indices may be negative, counting back from the last element defined so far.
+// Other statements (o, s, l, p, ...) are accepted and ignored.
+//
+// MTL materials are mapped onto the materials of the raytracer:
+// - transparent (d or Tr below fully opaque, or illum 4, 6, 7, 9): Dielectric with index Ni
+// - reflective (illum 3, 5, 8): Metal with albedo Ks
+// - anything else: Lambertian with albedo Kd
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::color::Color;
+use crate::material::{Dielectric, Lambertian, Material, Metal};
+use crate::mesh::{Face, FaceVertex, MeshData, TriangleMesh};
+use crate::point::Point;
+use crate::utils;
+use crate::vec3::Vec3;
+
+// Faces before any "g" statement belong to this group
+pub const DEFAULT_GROUP: &str = "default";
+
+// Materials of an MTL file by name
+pub type MaterialLibrary = HashMap<String, Arc<dyn Material>>;
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io { file: String, error: io::Error },
+    Parse { file: String, line: usize, message: String },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjError::Io { file, error } => write!(f, "{}: {}", file, error),
+            ObjError::Parse { file, line, message } => write!(f, "{}:{}: {}", file, line, message),
+        }
+    }
+}
+
+// Parsed OBJ file, before choosing which groups to render
+pub struct ObjModel {
+    pub data: MeshData,
+    pub faces: Vec<Face>,
+    // Group names in order of appearance, with the faces (indices into faces) in each
+    pub groups: Vec<(String, Vec<usize>)>,
+}
+
+impl ObjModel {
+    // Mesh of the faces in any of the given groups, or all faces without a selection
+    // None if no faces are left
+    pub fn into_mesh(self, groups: Option<&[String]>) -> Option<TriangleMesh> {
+        let faces: Vec<Face> = match groups {
+            None => self.faces,
+            Some(selected) => {
+                let mut in_selection = vec![false; self.faces.len()];
+                for (name, faces) in self.groups.iter() {
+                    if selected.contains(name) {
+                        for &face in faces {
+                            in_selection[face] = true;
+                        }
+                    }
+                }
+                self.faces
+                    .into_iter()
+                    .zip(in_selection)
+                    .filter(|(_, selected)| *selected)
+                    .map(|(face, _)| face)
+                    .collect()
+            }
+        };
+        if faces.is_empty() {
+            return None;
+        }
+        Some(TriangleMesh::new(self.data, faces))
+    }
+}
+
+// Material libraries are looked up next to the OBJ file
+// default_material is used for faces before any usemtl statement
+pub fn load_obj(path: &Path, default_material: Option<Arc<dyn Material>>) -> Result<ObjModel, ObjError> {
+    let file = path.display().to_string();
+    let text = fs::read_to_string(path).map_err(|error| ObjError::Io { file: file.clone(), error })?;
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    let mut load_library = |name: &str| {
+        let library_path = directory.join(name);
+        let library_file = library_path.display().to_string();
+        let text = fs::read_to_string(&library_path)
+            .map_err(|error| ObjError::Io { file: library_file.clone(), error })?;
+        parse_mtl(&text, &library_file)
+    };
+    parse_obj(&text, &file, &mut load_library, default_material)
+}
+
+// file is only used in error messages, load_library reads a material library by name
+pub fn parse_obj(
+    text: &str,
+    file: &str,
+    load_library: &mut dyn FnMut(&str) -> Result<MaterialLibrary, ObjError>,
+    default_material: Option<Arc<dyn Material>>,
+) -> Result<ObjModel, ObjError> {
+    let mut data = MeshData::default();
+    let mut faces = vec![];
+    let mut groups: Vec<(String, Vec<usize>)> = vec![];
+    let mut current_groups = vec![String::from(DEFAULT_GROUP)];
+    let mut materials = MaterialLibrary::new();
+    let mut current_material = default_material;
+
+    for (index, line) in text.lines().enumerate() {
+        let error = |message: String| ObjError::Parse { file: file.to_string(), line: index + 1, message };
+        let line = line.split('#').next().unwrap_or("");
+        let mut words = line.split_whitespace();
+        let keyword = match words.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        let arguments: Vec<&str> = words.collect();
+
+        match keyword {
+            "v" => data.positions.push(parse_numbers(&arguments, 3, 4).map_err(error).map(|v| Point::new(v[0], v[1], v[2]))?),
+            "vn" => {
+                let normal = parse_numbers(&arguments, 3, 3).map_err(error).map(|n| Vec3::new(n[0], n[1], n[2]))?;
+                if normal.length_squared() == 0.0 {
+                    return Err(error(String::from("normal must not be zero")));
+                }
+                data.normals.push(normal.unit_vector());
+            }
+            "vt" => {
+                let uv = parse_numbers(&arguments, 1, 3).map_err(error)?;
+                data.uvs.push((uv[0], uv.get(1).copied().unwrap_or(0.0)));
+            }
+            "f" => {
+                if arguments.len() < 3 {
+                    return Err(error(format!("face needs at least 3 vertices but has {}", arguments.len())));
+                }
+                let material = match &current_material {
+                    Some(material) => material.clone(),
+                    None => return Err(error(String::from("face has no material, use usemtl or give the mesh a material"))),
+                };
+                let corners = arguments
+                    .iter()
+                    .map(|argument| parse_face_vertex(argument, &data))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(error)?;
+                let positions: Vec<Point> = corners.iter().map(|corner| data.positions[corner.position]).collect();
+                for [a, b, c] in triangulate(&positions) {
+                    for name in current_groups.iter() {
+                        match groups.iter_mut().find(|(group, _)| group == name) {
+                            Some((_, group_faces)) => group_faces.push(faces.len()),
+                            None => groups.push((name.clone(), vec![faces.len()])),
+                        }
+                    }
+                    faces.push(Face { vertices: [corners[a], corners[b], corners[c]], material: material.clone() });
+                }
+            }
+            "g" => {
+                current_groups = if arguments.is_empty() {
+                    vec![String::from(DEFAULT_GROUP)]
+                } else {
+                    arguments.iter().map(|name| name.to_string()).collect()
+                };
+            }
+            "mtllib" => {
+                if arguments.is_empty() {
+                    return Err(error(String::from("mtllib needs a file name")));
+                }
+                for name in arguments.iter() {
+                    materials.extend(load_library(name)?);
+                }
+            }
+            "usemtl" => {
+                let name = arguments.join(" ");
+                match materials.get(&name) {
+                    Some(material) => current_material = Some(material.clone()),
+                    None => return Err(error(format!("unknown material '{}'", name))),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if faces.is_empty() {
+        return Err(ObjError::Parse { file: file.to_string(), line: text.lines().count(), message: String::from("no faces") });
+    }
+    Ok(ObjModel { data, faces, groups })
+}
+
+// Between min and max numbers, all finite
+fn parse_numbers(arguments: &[&str], min: usize, max: usize) -> Result<Vec<f64>, String> {
+    if arguments.len() < min || arguments.len() > max {
+        let expected = if min == max { format!("{}", min) } else { format!("{} to {}", min, max) };
+        return Err(format!("expected {} numbers but found {}", expected, arguments.len()));
+    }
+    arguments
+        .iter()
+        .map(|argument| match argument.parse::<f64>() {
+            Ok(number) if number.is_finite() => Ok(number),
+            _ => Err(format!("invalid number '{}'", argument)),
+        })
+        .collect()
+}
+
+// "v", "v/vt", "v//vn" or "v/vt/vn"
+fn parse_face_vertex(argument: &str, data: &MeshData) -> Result<FaceVertex, String> {
+    let mut parts = argument.split('/');
+    let position = parse_index(parts.next().unwrap_or(""), data.positions.len(), "vertex")?;
+    let uv = match parts.next() {
+        None | Some("") => None,
+        Some(uv) => Some(parse_index(uv, data.uvs.len(), "texture coordinate")?),
+    };
+    let normal = match parts.next() {
+        None => None,
+        Some(normal) => Some(parse_index(normal, data.normals.len(), "normal")?),
+    };
+    if parts.next().is_some() {
+        return Err(format!("invalid face vertex '{}'", argument));
+    }
+    Ok(FaceVertex { position, normal, uv })
+}
+
+// OBJ indices start at 1, negative indices count back from the last element (-1)
+fn parse_index(index: &str, count: usize, kind: &str) -> Result<usize, String> {
+    let parsed: i64 = index.parse().map_err(|_| format!("invalid {} index '{}'", kind, index))?;
+    let resolved = if parsed < 0 { count as i64 + parsed } else { parsed - 1 };
+    if parsed == 0 || resolved < 0 || resolved >= count as i64 {
+        return Err(format!("{} index {} out of range, {} defined so far", kind, parsed, count));
+    }
+    Ok(resolved as usize)
+}
+
+// Ear clipping, so concave polygons are split correctly too
+// The polygon is projected onto the plane most facing its normal, and ears are cut
+// until one triangle is left. Returns indices into positions.
+fn triangulate(positions: &[Point]) -> Vec<[usize; 3]> {
+    if positions.len() == 3 {
+        return vec![[0, 1, 2]];
+    }
+
+    // Newell's method, robust for non-planar and concave polygons
+    let mut normal = Vec3::new(0.0, 0.0, 0.0);
+    for (i, current) in positions.iter().enumerate() {
+        let next = positions[(i + 1) % positions.len()];
+        normal = normal + (*current - next).cross(*current + next);
+    }
+    let drop_axis = (0..3)
+        .max_by(|&a, &b| normal[a].abs().partial_cmp(&normal[b].abs()).unwrap_or(std::cmp::Ordering::Equal))
+        .unwrap();
+    let (x_axis, y_axis) = ((drop_axis + 1) % 3, (drop_axis + 2) % 3);
+    let points: Vec<(f64, f64)> = positions.iter().map(|p| (p[x_axis], p[y_axis])).collect();
+    let orientation = normal[drop_axis].signum();
+
+    let cross = |a: (f64, f64), b: (f64, f64), c: (f64, f64)| {
+        orientation * ((b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0))
+    };
+
+    let mut remaining: Vec<usize> = (0..positions.len()).collect();
+    let mut triangles = vec![];
+    while remaining.len() > 3 {
+        let count = remaining.len();
+        let ear = (0..count).find(|&i| {
+            let (a, b, c) = (remaining[(i + count - 1) % count], remaining[i], remaining[(i + 1) % count]);
+            if cross(points[a], points[b], points[c]) <= 0.0 {
+                return false; // Reflex corner
+            }
+            // No other corner may lie inside the ear
+            remaining.iter().all(|&other| {
+                other == a
+                    || other == b
+                    || other == c
+                    || cross(points[a], points[b], points[other]) < 0.0
+                    || cross(points[b], points[c], points[other]) < 0.0
+                    || cross(points[c], points[a], points[other]) < 0.0
+            })
+        });
+        // Degenerate polygons have no ear, fall back to a fan over what is left
+        let i = match ear {
+            Some(i) => i,
+            None => break,
+        };
+        triangles.push([remaining[(i + count - 1) % count], remaining[i], remaining[(i + 1) % count]]);
+        remaining.remove(i);
+    }
+    for i in 1..remaining.len() - 1 {
+        triangles.push([remaining[0], remaining[i], remaining[i + 1]]);
+    }
+    triangles
+}
+
+// Values read from an MTL material, defaults follow common exporters
+struct MtlMaterial {
+    diffuse: Color,
+    specular: Color,
+    refractive_index: f64,
+    dissolve: f64, // 1 is fully opaque
+    illumination: u32,
+}
+
+impl Default for MtlMaterial {
+    fn default() -> Self {
+        MtlMaterial {
+            diffuse: Color::new(0.8, 0.8, 0.8),
+            specular: Color::new(0.0, 0.0, 0.0),
+            refractive_index: 1.0,
+            dissolve: 1.0,
+            illumination: 1,
+        }
+    }
+}
+
+impl MtlMaterial {
+    fn to_material(&self) -> Arc<dyn Material> {
+        let transparent = self.dissolve < 1.0 || [4, 6, 7, 9].contains(&self.illumination);
+        if transparent {
+            // An index of 1 (the default) would be invisible, assume glass
+            let refractive_index = if self.refractive_index > 1.0 { self.refractive_index } else { 1.5 };
+            return Arc::new(Dielectric::new(refractive_index));
+        }
+        if [3, 5, 8].contains(&self.illumination) {
+            return Arc::new(Metal::new(self.specular, 0.0));
+        }
+        Arc::new(Lambertian::new(self.diffuse))
+    }
+}
+
+// file is only used in error messages
+pub fn parse_mtl(text: &str, file: &str) -> Result<MaterialLibrary, ObjError> {
+    let mut parsed: Vec<(String, MtlMaterial)> = vec![];
+
+    for (index, line) in text.lines().enumerate() {
+        let error = |message: String| ObjError::Parse { file: file.to_string(), line: index + 1, message };
+        let line = line.split('#').next().unwrap_or("");
+        let mut words = line.split_whitespace();
+        let keyword = match words.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        let arguments: Vec<&str> = words.collect();
+
+        if keyword == "newmtl" {
+            if arguments.is_empty() {
+                return Err(error(String::from("newmtl needs a name")));
+            }
+            parsed.push((arguments.join(" "), MtlMaterial::default()));
+            continue;
+        }
+        let material = match parsed.last_mut() {
+            Some((_, material)) => material,
+            None if ["Kd", "Ks", "Ni", "d", "Tr", "illum"].contains(&keyword) => {
+                return Err(error(format!("{} before any newmtl", keyword)));
+            }
+            None => continue,
+        };
+        let color = |arguments: &[&str]| {
+            parse_numbers(arguments, 3, 3).map(|c| Color::new(utils::clamp(c[0], 0.0, 1.0), utils::clamp(c[1], 0.0, 1.0), utils::clamp(c[2], 0.0, 1.0)))
+        };
+        match keyword {
+            "Kd" => material.diffuse = color(&arguments).map_err(error)?,
+            "Ks" => material.specular = color(&arguments).map_err(error)?,
+            "Ni" => material.refractive_index = parse_numbers(&arguments, 1, 1).map_err(error)?[0],
+            "d" => material.dissolve = parse_numbers(&arguments, 1, 1).map_err(error)?[0],
+            "Tr" => material.dissolve = 1.0 - parse_numbers(&arguments, 1, 1).map_err(error)?[0],
+            "illum" => {
+                material.illumination = arguments
+                    .first()
+                    .and_then(|illumination| illumination.parse().ok())
+                    .filter(|illumination| *illumination <= 10)
+                    .ok_or_else(|| error(String::from("illum must be a model number from 0 to 10")))?;
+            }
+            _ => {}
+        }
+    }
+
+    Ok(parsed.into_iter().map(|(name, material)| (name, material.to_material())).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::{HitRecord, Hittable};
+    use crate::ray::Ray;
+
+    fn grey() -> Option<Arc<dyn Material>> {
+        Some(Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))))
+    }
+
+    fn no_libraries(name: &str) -> Result<MaterialLibrary, ObjError> {
+        Err(ObjError::Parse { file: name.to_string(), line: 0, message: String::from("unexpected library") })
+    }
+
+    fn parse(text: &str) -> Result<ObjModel, ObjError> {
+        parse_obj(text, "test.obj", &mut no_libraries, grey())
+    }
+
+    fn error_line(text: &str) -> (usize, String) {
+        match parse(text) {
+            Err(ObjError::Parse { line, message, .. }) => (line, message),
+            Err(other) => panic!("expected a parse error, got {}", other),
+            Ok(_) => panic!("expected a parse error"),
+        }
+    }
+
+    fn down(x: f64, y: f64) -> Ray {
+        Ray::new(Point::new(x, y, 1.0), Vec3::new(0.0, 0.0, -1.0))
+    }
+
+    #[test]
+    fn faces_should_resolve_all_index_forms() {
+        let text = "\
+# Unit square in z = 0
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vn 0 0 2
+f 1/1/1 2/2/1 3/3/1
+f -4//-1 -2//-1 -1//-1
+";
+        let model = parse(text).unwrap();
+        assert_eq!(model.faces.len(), 2);
+        assert_eq!(model.data.normals, vec![Vec3::new(0.0, 0.0, 1.0)]);
+        assert_eq!(model.faces[0].vertices[2], FaceVertex { position: 2, normal: Some(0), uv: Some(2) });
+        assert_eq!(model.faces[1].vertices[1], FaceVertex { position: 2, normal: Some(0), uv: None });
+
+        let mesh = model.into_mesh(None).unwrap();
+        let hit = mesh.hit(down(0.75, 0.25), 0.001, f64::INFINITY).unwrap();
+        assert_eq!(hit.t, 1.0);
+        assert_eq!((hit.u, hit.v), (0.75, 0.25));
+        assert!(mesh.hit(down(0.25, 0.75), 0.001, f64::INFINITY).is_some());
+    }
+
+    #[test]
+    fn concave_polygon_should_be_triangulated_inside_its_outline() {
+        // L shape, a fan from the first corner would cover the missing quarter
+        let text = "\
+v 1 1 0
+v 0 1 0
+v 0 0 0
+v 2 0 0
+v 2 2 0
+v 1 2 0
+f 1 2 3 4 5 6
+";
+        let model = parse(text).unwrap();
+        assert_eq!(model.faces.len(), 4);
+        let mesh = model.into_mesh(None).unwrap();
+        for (x, y) in [(0.5, 0.5), (1.5, 0.5), (1.5, 1.5)].iter() {
+            assert!(mesh.hit(down(*x, *y), 0.001, f64::INFINITY).is_some(), "({}, {}) should hit", x, y);
+        }
+        assert!(mesh.hit(down(0.5, 1.5), 0.001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn groups_should_select_faces() {
+        let text = "\
+v 0 0 0
+v 1 0 0
+v 0 1 0
+v 0 0 -1
+v 1 0 -1
+v 0 1 -1
+f 1 2 3
+g lower both
+f 4 5 6
+g
+f 1 2 3
+";
+        let model = parse(text).unwrap();
+        let names: Vec<&str> = model.groups.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["default", "lower", "both"]);
+        assert_eq!(model.groups[0].1, vec![0, 2]);
+        assert_eq!(model.groups[1].1, vec![1]);
+
+        // Only the face behind, the two in front are left out
+        let lower = model.into_mesh(Some(&[String::from("lower")])).unwrap();
+        assert_eq!(lower.hit(down(0.2, 0.2), 0.001, f64::INFINITY).unwrap().t, 2.0);
+        assert!(parse(text).unwrap().into_mesh(Some(&[String::from("missing")])).is_none());
+    }
+
+    #[test]
+    fn invalid_statements_should_report_line() {
+        assert_eq!(error_line("v 0 0 0\nv 1 0\n").0, 2);
+        assert_eq!(error_line("v 0 0 0\nf 1 2 3\n"), (2, String::from("vertex index 2 out of range, 1 defined so far")));
+        assert_eq!(error_line("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 0\n").0, 4);
+        assert_eq!(error_line("v 0 0 0\nv 1 0 0\nf 1 2\n").0, 3);
+        assert_eq!(error_line("usemtl steel\n"), (1, String::from("unknown material 'steel'")));
+        assert_eq!(error_line("v 0 0 0\n").1, "no faces");
+
+        let without_material = parse_obj("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n", "test.obj", &mut no_libraries, None);
+        assert!(without_material.err().unwrap().to_string().starts_with("test.obj:4: face has no material"));
+    }
+
+    #[test]
+    fn mtl_should_map_onto_materials() {
+        let mtl = "\
+newmtl red
+Kd 1 0 0
+illum 2
+newmtl mirror
+Ks 0.9 0.9 0.9
+illum 3
+newmtl glass
+Ni 1.33
+d 0.5
+";
+        let mut libraries = |name: &str| {
+            assert_eq!(name, "scene.mtl");
+            parse_mtl(mtl, name)
+        };
+        let text = "\
+mtllib scene.mtl
+v 0 0 0
+v 1 0 0
+v 0 1 0
+usemtl red
+f 1 2 3
+usemtl mirror
+f 1 2 3
+usemtl glass
+f 1 2 3
+";
+        let model = parse_obj(text, "test.obj", &mut libraries, None).unwrap();
+        let albedo = |face: usize| {
+            let material = model.faces[face].material.clone();
+            let hit = HitRecord::new(down(0.0, 0.0), 1.0, Vec3::new(0.0, 0.0, 1.0), (0.0, 0.0), material);
+            hit.material.get_albedo(&hit)
+        };
+        assert_eq!(albedo(0), Color::new(1.0, 0.0, 0.0));
+        assert_eq!(albedo(1), Color::new(0.9, 0.9, 0.9));
+        // Dielectrics are colourless, unlike the default diffuse albedo
+        assert_eq!(albedo(2), Color::new(1.0, 1.0, 1.0));
+
+        let error = parse_mtl("Kd 1 1 1\n", "bad.mtl").err().unwrap();
+        assert_eq!(error.to_string(), "bad.mtl:1: Kd before any newmtl");
+    }
+}
@@ -0,0 +1,195 @@
+use crate::aabb::Aabb;
+use crate::point::Point;
+use crate::ray::Ray;
+use crate::hittable::{HitRecord, Hittable, Interval};
+use crate::vec3::Vec3;
+use crate::material::Material;
+
+use std::sync::Arc;
+
+// Thickness given to the bounding box of a disk lying in an axis plane
+const MIN_BOX_EXTENT: f64 = 1e-4;
+
+// Infinite plane through point, facing towards normal
+// (u, v) are distances from point along two perpendicular directions in the plane,
+// so repeating textures tile once per unit.
+pub struct Plane {
+    point: Point,
+    normal: Vec3,
+    basis: (Vec3, Vec3),
+    material: Arc<dyn Material>,
+}
+
+impl Plane {
+    pub fn new(point: Point, normal: Vec3, material: Arc<dyn Material>) -> Self {
+        let normal = normal.unit_vector();
+        Plane { point, normal, basis: plane_basis(normal), material }
+    }
+}
+
+impl Hittable for Plane {
+    fn hit(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let t = plane_hit(self.point, self.normal, ray, t_min, t_max)?;
+        let planar = ray.at(t) - self.point;
+        let (u_axis, v_axis) = self.basis;
+        let uv = (planar.dot(u_axis), planar.dot(v_axis));
+        let mut hit = HitRecord::new(ray, t, self.normal, uv, self.material.clone());
+        hit.tangent = Some(u_axis);
+        Some(hit)
+    }
+
+    // As a solid, the half of space behind the plane
+    // A ray that does not cross it within the range is inside or outside all along,
+    // which following the ray from hit to hit cannot tell.
+    fn intervals(&self, ray: Ray, t_min: f64, t_max: f64) -> Vec<Interval> {
+        match self.hit(ray, t_min, t_max) {
+            Some(hit) if hit.front_face => vec![Interval { enter: Some(hit), exit: None }],
+            Some(hit) => vec![Interval { enter: None, exit: Some(hit) }],
+            None => {
+                let probe = ray.at(if t_min.is_finite() { t_min } else { 0.0 });
+                if (probe - self.point).dot(self.normal) < 0.0 {
+                    vec![Interval { enter: None, exit: None }]
+                } else {
+                    Vec::new()
+                }
+            }
+        }
+    }
+
+    // Unbounded, checked on every ray instead of going into the BVH
+    fn bounding_box(&self) -> Option<Aabb> {
+        None
+    }
+}
+
+// Round disk around center, facing towards normal
+// (u, v) map the square around the disk onto [0, 1], so an image covers it edge to edge.
+pub struct Disk {
+    center: Point,
+    normal: Vec3,
+    radius: f64,
+    basis: (Vec3, Vec3),
+    material: Arc<dyn Material>,
+}
+
+impl Disk {
+    pub fn new(center: Point, normal: Vec3, radius: f64, material: Arc<dyn Material>) -> Self {
+        let normal = normal.unit_vector();
+        Disk { center, normal, radius, basis: plane_basis(normal), material }
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let t = plane_hit(self.center, self.normal, ray, t_min, t_max)?;
+        let planar = ray.at(t) - self.center;
+        if planar.length_squared() > self.radius * self.radius {
+            return None;
+        }
+        let (u_axis, v_axis) = self.basis;
+        let scale = 0.5 / self.radius;
+        let uv = (0.5 + scale * planar.dot(u_axis), 0.5 + scale * planar.dot(v_axis));
+        let mut hit = HitRecord::new(ray, t, self.normal, uv, self.material.clone());
+        hit.tangent = Some(u_axis);
+        Some(hit)
+    }
+
+    // Along each axis the disk reaches radius * sin of the angle between the axis and the normal
+    fn bounding_box(&self) -> Option<Aabb> {
+        let mut extent = Vec3::new(0.0, 0.0, 0.0);
+        for axis in 0..3 {
+            extent[axis] = self.radius * (1.0 - self.normal[axis] * self.normal[axis]).max(0.0).sqrt();
+        }
+        Some(Aabb::new(self.center - extent, self.center + extent).padded(MIN_BOX_EXTENT))
+    }
+}
+
+// Distance along the ray to the plane through point with the given unit normal
+pub fn plane_hit(point: Point, normal: Vec3, ray: Ray, t_min: f64, t_max: f64) -> Option<f64> {
+    let denominator = normal.dot(ray.direction);
+    // Ray parallel to the plane
+    if denominator.abs() < 1e-12 * ray.direction.length() {
+        return None;
+    }
+    let t = (normal.dot(point) - normal.dot(ray.origin)) / denominator;
+    if t <= t_min || t >= t_max {
+        return None;
+    }
+    Some(t)
+}
+
+// Two unit vectors u and v in the plane with the given unit normal, such that u x v = normal
+// u follows the x axis where it can, so a floor facing up gets u = +x and v = -z.
+pub fn plane_basis(normal: Vec3) -> (Vec3, Vec3) {
+    let helper = if normal.x.abs() > 0.9 { Vec3::new(0.0, 1.0, 0.0) } else { Vec3::new(1.0, 0.0, 0.0) };
+    let u = (helper - helper.dot(normal) * normal).unit_vector();
+    (u, normal.cross(u))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::Lambertian;
+
+    fn material() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))
+    }
+
+    #[test]
+    fn plane_should_be_flat_far_away() {
+        let plane = Plane::new(Point::new(0.0, 0.0, 0.0), Vec3::new(0.0, 2.0, 0.0), material());
+        assert!(plane.bounding_box().is_none());
+        // Grazing ray far from the origin still lands exactly on y = 0
+        let ray = Ray::new(Point::new(1e4, 1.0, -3e4), Vec3::new(1.0, -1e-3, 0.5));
+        let hit = plane.hit(ray, 0.001, f64::INFINITY).unwrap();
+        assert!(hit.point.y.abs() < 1e-9);
+        assert_eq!(hit.normal, Vec3::new(0.0, 1.0, 0.0));
+        assert!(hit.front_face);
+
+        let ray = Ray::new(Point::new(3.0, 1.0, 2.0), Vec3::new(0.0, -1.0, 0.0));
+        let hit = plane.hit(ray, 0.001, f64::INFINITY).unwrap();
+        assert_eq!((hit.u, hit.v), (3.0, -2.0));
+        assert_eq!(hit.tangent, Some(Vec3::new(1.0, 0.0, 0.0)));
+        // Parallel, and pointing away
+        assert!(plane.hit(Ray::new(Point::new(0.0, 1.0, 0.0), Vec3::new(1.0, 0.0, 0.0)), 0.001, f64::INFINITY).is_none());
+        assert!(plane.hit(Ray::new(Point::new(0.0, 1.0, 0.0), Vec3::new(0.0, 1.0, 0.0)), 0.001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn basis_should_be_right_handed() {
+        let normals = [
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(-1.0, 0.0, 0.0),
+            Vec3::new(1.0, 2.0, 3.0).unit_vector(),
+            Vec3::new(0.0, 0.0, -1.0),
+        ];
+        for &normal in normals.iter() {
+            let (u, v) = plane_basis(normal);
+            assert!((u.length() - 1.0).abs() < 1e-12 && (v.length() - 1.0).abs() < 1e-12);
+            assert!(u.dot(normal).abs() < 1e-12 && v.dot(normal).abs() < 1e-12);
+            assert!((u.cross(v) - normal).length() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn disk_should_map_uv_across_its_square() {
+        let disk = Disk::new(Point::new(1.0, 2.0, 3.0), Vec3::new(0.0, 0.0, 1.0), 2.0, material());
+        let down = Vec3::new(0.0, 0.0, -1.0);
+        let hit = disk.hit(Ray::new(Point::new(1.0, 2.0, 5.0), down), 0.001, f64::INFINITY).unwrap();
+        assert_eq!((hit.u, hit.v), (0.5, 0.5));
+        // On the rim, along u
+        let hit = disk.hit(Ray::new(Point::new(3.0, 2.0, 5.0), down), 0.001, f64::INFINITY).unwrap();
+        assert_eq!((hit.u, hit.v), (1.0, 0.5));
+        // Inside the square but outside the circle
+        assert!(disk.hit(Ray::new(Point::new(2.5, 3.5, 5.0), down), 0.001, f64::INFINITY).is_none());
+
+        let bbox = disk.bounding_box().unwrap();
+        assert_eq!((bbox.minimum.x, bbox.maximum.x), (-1.0, 3.0));
+        assert!(bbox.maximum.z - bbox.minimum.z < 1e-3);
+        // Tilted disks are bounded by their rim
+        let tilted = Disk::new(Point::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 1.0), 1.0, material());
+        let bbox = tilted.bounding_box().unwrap();
+        assert!((bbox.maximum.x - 1.0).abs() < 1e-3 && (bbox.maximum.y - 0.5f64.sqrt()).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,309 @@
+// PNG specification: https://www.w3.org/TR/png/
+//
+// Images are written as 8-bit RGB. Reading supports 8-bit grayscale, RGB, palette and
+// alpha variants without interlacing, which covers what image editors usually export.
+// Alpha is dropped when reading, pixels always come back as RGB.
+
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::zlib;
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+
+// Colour types
+const GRAYSCALE: u8 = 0;
+const RGB: u8 = 2;
+const PALETTE: u8 = 3;
+const GRAYSCALE_ALPHA: u8 = 4;
+const RGB_ALPHA: u8 = 6;
+
+#[derive(Debug, PartialEq)]
+pub struct PngError(pub String);
+
+impl fmt::Display for PngError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid PNG: {}", self.0)
+    }
+}
+
+fn error<T>(message: &str) -> Result<T, PngError> {
+    Err(PngError(message.to_string()))
+}
+
+// Decoded image, rows from top to bottom, 3 bytes (r, g, b) per pixel
+#[derive(Debug, PartialEq)]
+pub struct RgbImage {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+/* ==========
+ * = ENCODE =
+ * ==========
+ */
+
+// rgb holds 3 bytes per pixel, rows from top to bottom
+pub fn encode_png(out: &mut impl Write, width: usize, height: usize, rgb: &[u8]) -> io::Result<()> {
+    assert_eq!(rgb.len(), width * height * 3, "pixel data does not match the image size");
+
+    out.write_all(&SIGNATURE)?;
+
+    let mut header = vec![];
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    header.extend_from_slice(&[8, RGB, 0, 0, 0]); // Bit depth, colour type, compression, filter, interlace
+    write_chunk(out, b"IHDR", &header)?;
+
+    // Each row is prefixed by the filter which makes it most compressible
+    let stride = width * 3;
+    let mut filtered = Vec::with_capacity((stride + 1) * height);
+    let empty_row = vec![0; stride];
+    for row in 0..height {
+        let current = &rgb[row * stride..(row + 1) * stride];
+        let previous = if row == 0 { &empty_row[..] } else { &rgb[(row - 1) * stride..row * stride] };
+        let (filter, bytes) = (0..5)
+            .map(|filter| (filter, filter_row(filter, current, previous)))
+            .min_by_key(|(_, bytes)| bytes.iter().map(|&byte| (byte as i8).unsigned_abs() as u32).sum::<u32>())
+            .unwrap();
+        filtered.push(filter);
+        filtered.extend_from_slice(&bytes);
+    }
+    write_chunk(out, b"IDAT", &zlib::compress(&filtered))?;
+    write_chunk(out, b"IEND", &[])
+}
+
+fn write_chunk(out: &mut impl Write, chunk_type: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    out.write_all(chunk_type)?;
+    out.write_all(data)?;
+    let crc = zlib::crc32_update(zlib::crc32(chunk_type), data);
+    out.write_all(&crc.to_be_bytes())
+}
+
+// Filters predict each byte from its neighbours: a (left), b (above), c (above left)
+fn filter_row(filter: u8, current: &[u8], previous: &[u8]) -> Vec<u8> {
+    (0..current.len())
+        .map(|i| {
+            let a = if i >= 3 { current[i - 3] } else { 0 };
+            let b = previous[i];
+            let c = if i >= 3 { previous[i - 3] } else { 0 };
+            current[i].wrapping_sub(predict(filter, a, b, c))
+        })
+        .collect()
+}
+
+fn predict(filter: u8, a: u8, b: u8, c: u8) -> u8 {
+    match filter {
+        1 => a,
+        2 => b,
+        3 => ((a as u16 + b as u16) / 2) as u8,
+        4 => paeth(a, b, c),
+        _ => 0,
+    }
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/* ==========
+ * = DECODE =
+ * ==========
+ */
+
+pub fn decode_png(bytes: &[u8]) -> Result<RgbImage, PngError> {
+    if bytes.len() < 8 || bytes[..8] != SIGNATURE {
+        return error("missing PNG signature");
+    }
+
+    let mut header = None;
+    let mut palette: Vec<u8> = vec![];
+    let mut compressed = vec![];
+    let mut position = 8;
+    loop {
+        let length = match bytes.get(position..position + 4) {
+            Some(length) => u32::from_be_bytes([length[0], length[1], length[2], length[3]]) as usize,
+            None => return error("missing IEND chunk"),
+        };
+        let chunk = match bytes.get(position + 4..position + 12 + length) {
+            Some(chunk) => chunk,
+            None => return error("truncated chunk"),
+        };
+        let (chunk_type, rest) = chunk.split_at(4);
+        let (data, crc) = rest.split_at(length);
+        if zlib::crc32(&chunk[..4 + length]) != u32::from_be_bytes([crc[0], crc[1], crc[2], crc[3]]) {
+            return error(&format!("CRC mismatch in {} chunk", String::from_utf8_lossy(chunk_type)));
+        }
+        position += 12 + length;
+
+        match chunk_type {
+            b"IHDR" => header = Some(Header::parse(data)?),
+            b"PLTE" => palette = data.to_vec(),
+            b"IDAT" => compressed.extend_from_slice(data),
+            b"IEND" => break,
+            // Lowercase first letter: ancillary chunk, safe to ignore
+            _ if chunk_type[0].is_ascii_lowercase() => {}
+            _ => return error(&format!("unsupported critical chunk {}", String::from_utf8_lossy(chunk_type))),
+        }
+    }
+
+    let header = match header {
+        Some(header) => header,
+        None => return error("missing IHDR chunk"),
+    };
+    let filtered = zlib::decompress(&compressed).map_err(|error| PngError(error.to_string()))?;
+    let raw = unfilter(&header, &filtered)?;
+
+    // Expand every pixel to RGB
+    let channels = header.channels();
+    let mut pixels = Vec::with_capacity(header.width * header.height * 3);
+    for pixel in raw.chunks(channels) {
+        match header.colour_type {
+            GRAYSCALE | GRAYSCALE_ALPHA => pixels.extend_from_slice(&[pixel[0], pixel[0], pixel[0]]),
+            RGB | RGB_ALPHA => pixels.extend_from_slice(&pixel[..3]),
+            _ => {
+                let index = pixel[0] as usize * 3;
+                match palette.get(index..index + 3) {
+                    Some(colour) => pixels.extend_from_slice(colour),
+                    None => return error("palette index out of range"),
+                }
+            }
+        }
+    }
+    Ok(RgbImage { width: header.width, height: header.height, pixels })
+}
+
+struct Header {
+    width: usize,
+    height: usize,
+    colour_type: u8,
+}
+
+impl Header {
+    fn parse(data: &[u8]) -> Result<Self, PngError> {
+        if data.len() != 13 {
+            return error("IHDR chunk has the wrong length");
+        }
+        let width = u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as usize;
+        let height = u32::from_be_bytes([data[4], data[5], data[6], data[7]]) as usize;
+        let (bit_depth, colour_type, interlace) = (data[8], data[9], data[12]);
+        if width == 0 || height == 0 {
+            return error("image has no pixels");
+        }
+        if bit_depth != 8 {
+            return error(&format!("unsupported bit depth {}, only 8 is supported", bit_depth));
+        }
+        if ![GRAYSCALE, RGB, PALETTE, GRAYSCALE_ALPHA, RGB_ALPHA].contains(&colour_type) {
+            return error(&format!("unknown colour type {}", colour_type));
+        }
+        if interlace != 0 {
+            return error("interlaced images are not supported");
+        }
+        let header = Header { width, height, colour_type };
+        // Sizes are untrusted, the filtered data and the RGB pixels must both fit in memory
+        let row_bytes = width.checked_mul(header.channels().max(3)).and_then(|stride| stride.checked_add(1));
+        if row_bytes.and_then(|row_bytes| row_bytes.checked_mul(height)).is_none() {
+            return error("image too large");
+        }
+        Ok(header)
+    }
+
+    fn channels(&self) -> usize {
+        match self.colour_type {
+            GRAYSCALE | PALETTE => 1,
+            GRAYSCALE_ALPHA => 2,
+            RGB => 3,
+            _ => 4,
+        }
+    }
+}
+
+fn unfilter(header: &Header, filtered: &[u8]) -> Result<Vec<u8>, PngError> {
+    let channels = header.channels();
+    let stride = header.width * channels;
+    if filtered.len() != (stride + 1) * header.height {
+        return error("image data has the wrong length");
+    }
+
+    let mut raw: Vec<u8> = Vec::with_capacity(stride * header.height);
+    for (row, line) in filtered.chunks(stride + 1).enumerate() {
+        let filter = line[0];
+        if filter > 4 {
+            return error(&format!("unknown filter type {}", filter));
+        }
+        let row_start = row * stride;
+        for i in 0..stride {
+            let a = if i >= channels { raw[row_start + i - channels] } else { 0 };
+            let b = if row > 0 { raw[row_start - stride + i] } else { 0 };
+            let c = if row > 0 && i >= channels { raw[row_start - stride + i - channels] } else { 0 };
+            raw.push(line[1 + i].wrapping_add(predict(filter, a, b, c)));
+        }
+    }
+    Ok(raw)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn encoded_image_should_decode_to_same_pixels() {
+        let mut rng = crate::utils::rng();
+        let (width, height) = (37, 23);
+        // Smooth gradients exercise the predicting filters, noise the literal path
+        let pixels: Vec<u8> = (0..width * height * 3)
+            .map(|i| if i % 7 == 0 { rng.gen() } else { (i / 3 % width * 5 + i / (width * 3)) as u8 })
+            .collect();
+
+        let mut encoded = vec![];
+        encode_png(&mut encoded, width, height, &pixels).unwrap();
+        let decoded = decode_png(&encoded).unwrap();
+        assert_eq!(decoded, RgbImage { width, height, pixels });
+    }
+
+    #[test]
+    fn png_from_another_encoder_should_decode() {
+        // 2x2 RGBA compressed by Python's zlib with filter 0: red, green / blue, half transparent white
+        let mut bytes = SIGNATURE.to_vec();
+        let header = [0, 0, 0, 2, 0, 0, 0, 2, 8, RGB_ALPHA, 0, 0, 0];
+        write_chunk(&mut bytes, b"IHDR", &header).unwrap();
+        write_chunk(&mut bytes, b"tEXt", b"Comment\0ignored").unwrap();
+        let compressed = [
+            0x78, 0xda, 0x63, 0xf8, 0xcf, 0xc0, 0xf0, 0x1f, 0x0c, 0x81, 0x34, 0x08, 0x34, 0x00,
+            0x00, 0x49, 0x49, 0x09, 0x78,
+        ];
+        write_chunk(&mut bytes, b"IDAT", &compressed).unwrap();
+        write_chunk(&mut bytes, b"IEND", &[]).unwrap();
+
+        let decoded = decode_png(&bytes).unwrap();
+        assert_eq!(decoded.pixels, vec![255, 0, 0, 0, 255, 0, 0, 0, 255, 255, 255, 255]);
+
+        // Corrupting a chunk breaks its CRC
+        let mut corrupt = bytes.clone();
+        corrupt[16] = 1;
+        assert!(decode_png(&corrupt).is_err());
+    }
+
+    #[test]
+    fn oversized_header_should_be_rejected() {
+        let mut bytes = SIGNATURE.to_vec();
+        let header = [0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 8, RGB_ALPHA, 0, 0, 0];
+        write_chunk(&mut bytes, b"IHDR", &header).unwrap();
+        write_chunk(&mut bytes, b"IDAT", &[]).unwrap();
+        write_chunk(&mut bytes, b"IEND", &[]).unwrap();
+        assert_eq!(decode_png(&bytes), Err(PngError(String::from("image too large"))));
+    }
+}
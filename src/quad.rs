@@ -0,0 +1,216 @@
+use crate::aabb::Aabb;
+use crate::point::Point;
+use crate::ray::Ray;
+use crate::hittable::{HitRecord, Hittable};
+use crate::vec3::Vec3;
+use crate::material::Material;
+use crate::plane::plane_hit;
+
+use std::sync::Arc;
+
+// Thickness given to the bounding box of a quad lying in an axis plane
+const MIN_BOX_EXTENT: f64 = 1e-4;
+
+// Parallelogram with one corner at corner and sides u and v
+// The front is the side u x v points to, and (u, v) surface coordinates
+// run from 0 to 1 along the two sides.
+#[derive(Clone)]
+pub struct Quad {
+    corner: Point,
+    u: Vec3,
+    v: Vec3,
+    normal: Vec3,
+    // n / (n . n) for the unnormalized normal n = u x v, projects points onto (u, v) coordinates
+    w: Vec3,
+    material: Arc<dyn Material>,
+}
+
+impl Quad {
+    pub fn new(corner: Point, u: Vec3, v: Vec3, material: Arc<dyn Material>) -> Self {
+        let n = u.cross(v);
+        let normal = n.unit_vector();
+        Quad {
+            corner,
+            u,
+            v,
+            normal,
+            w: n / n.dot(n),
+            material,
+        }
+    }
+
+    // Axis-aligned rectangles, facing the positive direction of the remaining axis
+    // x0..x1 by y0..y1 at z = k, facing +z
+    pub fn xy_rect(x0: f64, x1: f64, y0: f64, y1: f64, k: f64, material: Arc<dyn Material>) -> Self {
+        Quad::new(Point::new(x0, y0, k), Vec3::new(x1 - x0, 0.0, 0.0), Vec3::new(0.0, y1 - y0, 0.0), material)
+    }
+
+    // x0..x1 by z0..z1 at y = k, facing +y
+    pub fn xz_rect(x0: f64, x1: f64, z0: f64, z1: f64, k: f64, material: Arc<dyn Material>) -> Self {
+        Quad::new(Point::new(x0, k, z0), Vec3::new(0.0, 0.0, z1 - z0), Vec3::new(x1 - x0, 0.0, 0.0), material)
+    }
+
+    // y0..y1 by z0..z1 at x = k, facing +x
+    pub fn yz_rect(y0: f64, y1: f64, z0: f64, z1: f64, k: f64, material: Arc<dyn Material>) -> Self {
+        Quad::new(Point::new(k, y0, z0), Vec3::new(0.0, y1 - y0, 0.0), Vec3::new(0.0, 0.0, z1 - z0), material)
+    }
+}
+
+impl Hittable for Quad {
+    // Intersect the plane, then check the hit lies within the sides
+    // A point p in the plane is corner + alpha * u + beta * v, inside when both are in [0, 1]
+    fn hit(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let t = plane_hit(self.corner, self.normal, ray, t_min, t_max)?;
+        let planar = ray.at(t) - self.corner;
+        let alpha = self.w.dot(planar.cross(self.v));
+        let beta = self.w.dot(self.u.cross(planar));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+
+        let mut hit = HitRecord::new(ray, t, self.normal, (alpha, beta), self.material.clone());
+        hit.tangent = Some(self.u.unit_vector());
+        Some(hit)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let opposite = self.corner + self.u + self.v;
+        let diagonal1 = Aabb::surrounding_box(Aabb::new(self.corner, self.corner), Aabb::new(opposite, opposite));
+        let (side_u, side_v) = (self.corner + self.u, self.corner + self.v);
+        let diagonal2 = Aabb::surrounding_box(Aabb::new(side_u, side_u), Aabb::new(side_v, side_v));
+        Some(Aabb::surrounding_box(diagonal1, diagonal2).padded(MIN_BOX_EXTENT))
+    }
+}
+
+// Axis-aligned box made of six quads facing outwards
+pub struct Cuboid {
+    sides: Vec<Quad>,
+    bbox: Aabb,
+}
+
+impl Cuboid {
+    // Any two opposite corners
+    pub fn new(a: Point, b: Point, material: Arc<dyn Material>) -> Self {
+        let min = Point::new(f64::min(a.x, b.x), f64::min(a.y, b.y), f64::min(a.z, b.z));
+        let max = Point::new(f64::max(a.x, b.x), f64::max(a.y, b.y), f64::max(a.z, b.z));
+        let dx = Vec3::new(max.x - min.x, 0.0, 0.0);
+        let dy = Vec3::new(0.0, max.y - min.y, 0.0);
+        let dz = Vec3::new(0.0, 0.0, max.z - min.z);
+
+        let sides = vec![
+            Quad::new(Point::new(min.x, min.y, max.z), dx, dy, material.clone()), // Front (+z)
+            Quad::new(Point::new(max.x, min.y, max.z), -dz, dy, material.clone()), // Right (+x)
+            Quad::new(Point::new(max.x, min.y, min.z), -dx, dy, material.clone()), // Back (-z)
+            Quad::new(Point::new(min.x, min.y, min.z), dz, dy, material.clone()), // Left (-x)
+            Quad::new(Point::new(min.x, max.y, max.z), dx, -dz, material.clone()), // Top (+y)
+            Quad::new(Point::new(min.x, min.y, min.z), dx, dz, material), // Bottom (-y)
+        ];
+        Cuboid { sides, bbox: Aabb::new(min, max).padded(MIN_BOX_EXTENT) }
+    }
+}
+
+impl Hittable for Cuboid {
+    fn hit(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let mut nearest: Option<HitRecord> = None;
+        for side in self.sides.iter() {
+            let closest_so_far = nearest.as_ref().map_or(t_max, |hit| hit.t);
+            if let Some(hit) = side.hit(ray, t_min, closest_so_far) {
+                nearest = Some(hit);
+            }
+        }
+        nearest
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bbox)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::Lambertian;
+
+    fn material() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))
+    }
+
+    fn assert_close(actual: Vec3, expected: Vec3) {
+        assert!((actual - expected).length() < 1e-12, "{:?} != {:?}", actual, expected);
+    }
+
+    #[test]
+    fn ray_should_hit_quad_with_uv() {
+        // Slanted parallelogram
+        let quad = Quad::new(Point::new(0.0, 0.0, -2.0), Vec3::new(2.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 0.0), material());
+        let ray = Ray::new(Point::new(1.5, 0.5, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let hit = quad.hit(ray, 0.001, f64::INFINITY).unwrap();
+        assert_eq!(hit.t, 2.0);
+        assert_eq!(hit.normal, Vec3::new(0.0, 0.0, 1.0));
+        assert!(hit.front_face);
+        assert!((hit.u - 0.5).abs() < 1e-12 && (hit.v - 0.5).abs() < 1e-12);
+        assert_eq!(hit.tangent, Some(Vec3::new(1.0, 0.0, 0.0)));
+
+        // Outside the slanted side, but inside the bounding box
+        let outside = Ray::new(Point::new(0.2, 0.8, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(quad.hit(outside, 0.001, f64::INFINITY).is_none());
+        // Parallel
+        let parallel = Ray::new(Point::new(0.5, 0.5, -1.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!(quad.hit(parallel, 0.001, f64::INFINITY).is_none());
+        // From behind
+        let behind = Ray::new(Point::new(1.5, 0.5, -3.0), Vec3::new(0.0, 0.0, 1.0));
+        let back = quad.hit(behind, 0.001, f64::INFINITY).unwrap();
+        assert!(!back.front_face);
+        // Light hitting the back is scattered back to that side
+        for _ in 0..100 {
+            assert!(material().scatter(behind, &back).unwrap().direction.z <= 0.0);
+        }
+    }
+
+    #[test]
+    fn axis_aligned_rects_should_face_positive_axis() {
+        let rects = [
+            (Quad::xy_rect(0.0, 1.0, 0.0, 1.0, 0.0, material()), Vec3::new(0.0, 0.0, 1.0)),
+            (Quad::xz_rect(0.0, 1.0, 0.0, 1.0, 0.0, material()), Vec3::new(0.0, 1.0, 0.0)),
+            (Quad::yz_rect(0.0, 1.0, 0.0, 1.0, 0.0, material()), Vec3::new(1.0, 0.0, 0.0)),
+        ];
+        for (rect, normal) in rects.iter() {
+            // Straight at the center from the front
+            let origin = Point::new(0.5, 0.5, 0.5) - Point::new(0.5 * normal.x, 0.5 * normal.y, 0.5 * normal.z) + *normal;
+            let hit = rect.hit(Ray::new(origin, -*normal), 0.001, f64::INFINITY).unwrap();
+            assert_eq!(hit.normal, *normal);
+            assert!(hit.front_face);
+            assert_eq!(hit.t, 1.0);
+            let bbox = rect.bounding_box().unwrap();
+            assert!(bbox.hit(Ray::new(origin, -*normal), 0.001, f64::INFINITY));
+        }
+    }
+
+    #[test]
+    fn cuboid_sides_should_face_outwards() {
+        let cuboid = Cuboid::new(Point::new(1.0, 1.0, 1.0), Point::new(-1.0, -1.0, -1.0), material());
+        let axes = [
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+        ];
+        for axis in axes.iter() {
+            for sign in [-1.0, 1.0].iter() {
+                let outward = *sign * *axis;
+                let ray = Ray::new(3.0 * outward + Vec3::new(0.1, 0.2, 0.3) - (Vec3::new(0.1, 0.2, 0.3).dot(outward)) * outward, -outward);
+                let hit = cuboid.hit(ray, 0.001, f64::INFINITY).unwrap();
+                assert_close(hit.normal, outward);
+                assert!(hit.front_face);
+                assert_eq!(hit.t, 2.0);
+            }
+        }
+
+        // From inside, the far side is hit from behind
+        let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let hit = cuboid.hit(ray, 0.001, f64::INFINITY).unwrap();
+        assert_eq!(hit.t, 1.0);
+        assert!(!hit.front_face);
+        assert_eq!(cuboid.bounding_box().unwrap().maximum, Point::new(1.0, 1.0, 1.0));
+    }
+}
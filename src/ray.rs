@@ -2,16 +2,25 @@ use crate::vec3::Vec3;
 use crate::point::Point;
 
 // P(t) = origin + t * direction_vector
+// time is the moment the ray is cast at, within the camera's shutter interval,
+// for objects which move during the exposure.
 #[derive(Copy, Clone)]
 pub struct Ray {
     pub origin: Point, // A
     pub direction: Vec3, // b
+    pub time: f64,
 }
 
 // P(t)
 impl Ray {
+    // At time 0, for objects that do not move
+    #[allow(dead_code)]
     pub fn new(origin: Point, direction: Vec3) -> Self {
-        Self { origin, direction }
+        Self::with_time(origin, direction, 0.0)
+    }
+
+    pub fn with_time(origin: Point, direction: Vec3, time: f64) -> Self {
+        Self { origin, direction, time }
     }
 
     pub fn at(self, t: f64) -> Point {
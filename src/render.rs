@@ -0,0 +1,247 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use rand::Rng;
+
+use crate::camera::Camera;
+use crate::color::Color;
+use crate::framebuffer::Framebuffer;
+use crate::hittable::Hittable;
+use crate::ray::Ray;
+use crate::utils;
+use crate::world::World;
+
+pub struct RenderSettings {
+    pub image_width: i32,
+    pub image_height: i32,
+    pub samples_per_pixel: i32,
+    pub max_depth: i32, // Maximum number of times rays can diffuse
+
+    // With a seed, every scanline draws from its own seeded generator,
+    // so the image is the same however many threads render it
+    pub seed: Option<u64>,
+
+    // Number of worker threads, 1 renders on the calling thread
+    pub threads: usize,
+
+    // Light arriving along rays which miss every object
+    pub background: Background,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Background {
+    // White to blue gradient going up
+    Sky,
+    Solid(Color),
+}
+
+impl Background {
+    pub fn color(&self, ray: Ray) -> Color {
+        match self {
+            Background::Sky => {
+                let unit_direction = ray.direction.unit_vector();
+                let t = 0.5 * (unit_direction.y + 1.0);
+                (1.0 - t) * Color::new(1.0, 1.0, 1.0)
+                    + t * Color { x: 0.5, y: 0.7, z: 1.0 }
+            }
+            Background::Solid(color) => *color,
+        }
+    }
+}
+
+pub fn render(world: &World, camera: &Camera, settings: &RenderSettings) -> Framebuffer {
+    if settings.threads <= 1 {
+        return render_serial(world, camera, settings);
+    }
+    render_parallel(world, camera, settings)
+}
+
+pub fn render_serial(world: &World, camera: &Camera, settings: &RenderSettings) -> Framebuffer {
+    let mut framebuffer = new_framebuffer(settings);
+    for height in (0..settings.image_height).rev() {
+        eprintln!("\rScanlines remaining: {}", height);
+        let row = (settings.image_height - 1 - height) as usize;
+        add_scanline(&mut framebuffer, row, render_scanline(world, camera, settings, height), settings);
+    }
+    framebuffer
+}
+
+// Scanlines are handed out one at a time from a shared counter,
+// so threads which finish cheap rows early go on to pick up more work
+pub fn render_parallel(world: &World, camera: &Camera, settings: &RenderSettings) -> Framebuffer {
+    let image_height = settings.image_height as usize;
+    let next_row = AtomicUsize::new(0);
+
+    let rows: Vec<(usize, Vec<Color>)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..settings.threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut finished = vec![];
+                    loop {
+                        let row = next_row.fetch_add(1, Ordering::Relaxed);
+                        if row >= image_height {
+                            break;
+                        }
+                        // Rows are numbered from the top, scanlines from the bottom
+                        let height = (image_height - 1 - row) as i32;
+                        finished.push((row, render_scanline(world, camera, settings, height)));
+                        eprintln!("\rScanlines remaining: {}", image_height - 1 - row);
+                    }
+                    finished
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("Render thread panicked"))
+            .collect()
+    });
+
+    let mut framebuffer = new_framebuffer(settings);
+    for (row, pixels) in rows {
+        add_scanline(&mut framebuffer, row, pixels, settings);
+    }
+    framebuffer
+}
+
+fn new_framebuffer(settings: &RenderSettings) -> Framebuffer {
+    Framebuffer::new(settings.image_width as usize, settings.image_height as usize)
+}
+
+// Rows are numbered from the top of the image
+fn add_scanline(framebuffer: &mut Framebuffer, row: usize, pixel_sums: Vec<Color>, settings: &RenderSettings) {
+    for (x, pixel_sum) in pixel_sums.into_iter().enumerate() {
+        framebuffer.add_samples(x, row, pixel_sum, settings.samples_per_pixel as u32);
+    }
+}
+
+// Sum of the samples of each pixel in the scanline
+fn render_scanline(world: &World, camera: &Camera, settings: &RenderSettings, height: i32) -> Vec<Color> {
+    if let Some(seed) = settings.seed {
+        utils::seed_rng(scanline_seed(seed, height));
+    }
+
+    let mut rng = utils::rng();
+    // Write the pixels for each row from left to right
+    (0..settings.image_width)
+        .map(|width| {
+            let mut pixel_color = Color::new(0.0, 0.0, 0.0);
+            for _samples in 0..settings.samples_per_pixel {
+                // Horizontal direction vector
+                let u = (width as f64 + rng.gen::<f64>()) / (settings.image_width - 1) as f64;
+
+                // Vertical direction vector
+                let v = (height as f64 + rng.gen::<f64>()) / (settings.image_height - 1) as f64;
+                let ray = camera.get_ray(u, v);
+                pixel_color = pixel_color + ray_color(ray, world, settings.background, settings.max_depth);
+            }
+            pixel_color
+        })
+        .collect()
+}
+
+// Mix the scanline into the seed (splitmix64), so neighbouring rows get unrelated streams
+fn scanline_seed(seed: u64, height: i32) -> u64 {
+    let mut z = seed.wrapping_add((height as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+pub fn ray_color(ray: Ray, world: &World, background: Background, depth: i32) -> Color {
+    if depth <= 0 {
+        return Color::new(0.0, 0.0, 0.0);
+    }
+
+    // TODO Refactor this to be a ray event, on hitting world object
+    match world.hit(ray, 0.001, f64::INFINITY) {
+        Some (hit) => {
+            let emitted = hit.material.emitted(hit.u, hit.v, hit.point);
+            if let Some (scattered_ray) = hit.material.scatter(ray, &hit) {
+                return emitted + hit.material.get_albedo(&hit) * ray_color(scattered_ray, world, background, depth - 1);
+            }
+            emitted
+        },
+
+        None => background.color(ray),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    use crate::material::{Dielectric, DiffuseLight, Lambertian, Metal};
+    use crate::point::Point;
+    use crate::sphere::Sphere;
+    use crate::texture::SolidColor;
+    use crate::vec3::Vec3;
+
+    fn small_scene() -> (World, Camera) {
+        let objects: Vec<Arc<dyn Hittable>> = vec![
+            Arc::new(Sphere::new(
+                Point::new(0.0, -100.5, -1.0),
+                100.0,
+                Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.0))),
+            )),
+            Arc::new(Sphere::new(
+                Point::new(0.0, 0.0, -1.0),
+                0.5,
+                Arc::new(Dielectric::new(1.5)),
+            )),
+            Arc::new(Sphere::new(
+                Point::new(1.0, 0.0, -1.0),
+                0.5,
+                Arc::new(Metal::new(Color::new(0.8, 0.6, 0.2), 0.3)),
+            )),
+        ];
+        let camera = Camera::new(
+            Point::new(0.0, 0.0, 1.0),
+            Point::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            60.0,
+            16.0 / 9.0,
+            0.1,
+            2.0,
+        );
+        (World::new(objects), camera)
+    }
+
+    fn settings(threads: usize) -> RenderSettings {
+        RenderSettings {
+            image_width: 32,
+            image_height: 18,
+            samples_per_pixel: 4,
+            max_depth: 10,
+            seed: Some(7),
+            threads,
+            background: Background::Sky,
+        }
+    }
+
+    #[test]
+    fn parallel_render_should_match_serial_render_with_seed() {
+        let (world, camera) = small_scene();
+        let serial = render(&world, &camera, &settings(1));
+        let parallel = render(&world, &camera, &settings(4));
+        assert_eq!((serial.width(), serial.height()), (32, 18));
+        assert_eq!(serial, parallel);
+    }
+
+    #[test]
+    fn light_should_be_only_illumination_on_black_background() {
+        let light = Arc::new(DiffuseLight::textured(Arc::new(SolidColor::new(Color::new(4.0, 2.0, 1.0)))));
+        let objects: Vec<Arc<dyn Hittable>> = vec![Arc::new(Sphere::new(Point::new(0.0, 0.0, -1.0), 0.5, light))];
+        let world = World::new(objects);
+        let black = Background::Solid(Color::new(0.0, 0.0, 0.0));
+
+        // Lights do not scatter, so a ray hitting one returns exactly its emission
+        let towards_light = Ray::new(Point::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert_eq!(ray_color(towards_light, &world, black, 10), Color::new(4.0, 2.0, 1.0));
+
+        let away = Ray::new(Point::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(ray_color(away, &world, black, 10), Color::new(0.0, 0.0, 0.0));
+        assert_eq!(ray_color(away, &world, Background::Solid(Color::new(0.1, 0.2, 0.3)), 10), Color::new(0.1, 0.2, 0.3));
+    }
+}
@@ -1,51 +1,57 @@
 extern crate rand;
 use rand::Rng;
-use std::rc::Rc;
+use std::sync::Arc;
 
 use crate::world::World;
 use crate::hittable::Hittable;
 use crate::material::{Metal, Lambertian, Dielectric};
 use crate::color::Color;
 use crate::sphere::Sphere;
+use crate::plane::Plane;
+use crate::vec3::Vec3;
 use crate::point::Point;
-use crate::utils::random_probability;
+use crate::utils::{self, random_probability};
+
+// Built-in scenes, selectable by name from the command line
+pub fn named_scene(name: &str) -> Option<World> {
+    match name {
+        "random" => Some(random_scene()),
+        _ => None,
+    }
+}
 
 pub fn random_scene() -> World {
     let ground_material = Lambertian::new(Color::new(0.5, 0.5, 0.5));
-    let ground_sphere = Sphere::new(
-        Point::new(0.0, -1000.0, 0.0),
-        1000.0,
-        Rc::new(ground_material)
-    );
+    let ground = Plane::new(Point::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), Arc::new(ground_material));
 
-    let mut rng = rand::thread_rng();
+    let mut rng = utils::rng();
 
     let material1 = Dielectric::new(1.5);
     let sphere1 = Sphere::new(
         Point::new(0.0, 1.0, 0.0),
         1.0,
-       Rc::new(material1)
+       Arc::new(material1)
     );
 
     let material2 = Lambertian::new(Color::new(0.4, 0.2, 0.1));
     let sphere2 = Sphere::new(
         Point::new(-4.0, 1.0, 0.0),
         1.0,
-        Rc::new(material2)
+        Arc::new(material2)
     );
 
     let material3 = Metal::new(Color::new(0.7, 0.6, 0.5), 0.0);
     let sphere3 = Sphere::new(
         Point::new(4.0, 1.0, 0.0),
         1.0,
-        Rc::new(material3)
+        Arc::new(material3)
     );
 
-    let mut world_objects: Vec<Box<dyn Hittable>> = vec![
-        Box::new(ground_sphere),
-        Box::new(sphere1),
-        Box::new(sphere2),
-        Box::new(sphere3)
+    let mut world_objects: Vec<Arc<dyn Hittable>> = vec![
+        Arc::new(ground),
+        Arc::new(sphere1),
+        Arc::new(sphere2),
+        Arc::new(sphere3)
     ];
 
     for a in -11..11 {
@@ -62,9 +68,9 @@ pub fn random_scene() -> World {
                     let sphere = Sphere::new(
                         center,
                         0.2,
-                        Rc::new(sphere_material)
+                        Arc::new(sphere_material)
                     );
-                    world_objects.push(Box::new(sphere));
+                    world_objects.push(Arc::new(sphere));
                 } else if choose_material < 0.95 {
                     // metal
                     let albedo = Color::random() * Color::random();
@@ -73,18 +79,18 @@ pub fn random_scene() -> World {
                     let sphere = Sphere::new(
                         center,
                         0.2,
-                        Rc::new(sphere_material)
+                        Arc::new(sphere_material)
                     );
-                    world_objects.push(Box::new(sphere));
+                    world_objects.push(Arc::new(sphere));
                 } else {
                     // glass
                     let sphere_material = Dielectric::new(1.5);
                     let sphere = Sphere::new(
                         center,
                         0.2,
-                        Rc::new(sphere_material)
+                        Arc::new(sphere_material)
                     );
-                    world_objects.push(Box::new(sphere));
+                    world_objects.push(Arc::new(sphere));
                 }
             }
         }
@@ -0,0 +1,1788 @@
+// Scene description files
+//
+// A scene file is a JSON document with six sections, all optional except "objects":
+//
+// {
+//   "camera": { "look_from": [13, 2, 3], "look_at": [0, 0, 0], "vup": [0, 1, 0],
+//               "vfov": 20, "aperture": 0.1, "focus_dist": 10, "shutter": [0, 1] },
+//   "render": { "width": 384, "height": 216, "spp": 100, "max_depth": 50, "seed": 1,
+//               "background": "sky" },
+//   "textures": {
+//     "tiles": { "type": "checker", "scale": 1, "even": [1, 1, 1], "odd": [0.2, 0.3, 0.1] },
+//     "earth": { "type": "image", "file": "textures/earth.png", "wrap": "repeat" }
+//   },
+//   "materials": {
+//     "ground": { "type": "lambertian", "albedo": "tiles" },
+//     "clay": { "type": "lambertian", "albedo": [0.5, 0.5, 0.5] },
+//     "glass": { "type": "dielectric", "refractive_index": 1.5 },
+//     "lamp": { "type": "diffuse_light", "emit": [4, 4, 4] },
+//     "fog": { "type": "isotropic", "albedo": [0.9, 0.9, 0.9] },
+//     "fur": { "type": "hair", "albedo": [0.6, 0.4, 0.2], "roughness": 0.2 }
+//   },
+//   "shapes": {
+//     "teapot": { "type": "mesh", "file": "models/teapot.obj", "material": "clay" }
+//   },
+//   "objects": [
+//     { "type": "instance", "shape": "teapot", "transform": [{ "scale": 0.5 }, { "translate": [2, 0, 0] }] },
+//     { "type": "plane", "point": [0, 0, 0], "normal": [0, 1, 0], "material": "ground" },
+//     { "type": "sphere", "center": [0, 1, 0], "radius": 1, "material": "glass" },
+//     { "type": "moving_sphere", "center0": [4, 1, 0], "center1": [4, 1.5, 0], "time0": 0, "time1": 1,
+//       "radius": 0.5, "material": "clay" },
+//     { "type": "disk", "center": [3, 0.01, 0], "normal": [0, 1, 0], "radius": 1, "material": "lamp" },
+//     { "type": "triangle", "vertices": [[0, 0, 0], [1, 0, 0], [0, 1, 0]], "material": "glass",
+//       "normals": [[0, 0, 1], [0, 0, 1], [0, 0, 1]], "uvs": [[0, 0], [1, 0], [0, 1]] },
+//     { "type": "cylinder", "base": [0, 0, 2], "top": [0, 1, 2], "radius": 0.5, "material": "clay" },
+//     { "type": "cone", "base": [2, 0, 2], "apex": [2, 1, 2], "radius": 0.5, "material": "clay" },
+//     { "type": "capsule", "from": [-2, 0.5, 2], "to": [-2, 1.5, 2], "radius": 0.5, "material": "clay" },
+//     { "type": "torus", "center": [0, 0.5, -2], "axis": [0, 1, 0], "major_radius": 1, "minor_radius": 0.25,
+//       "material": "clay" },
+//     { "type": "quad", "corner": [-1, 0, -1], "u": [2, 0, 0], "v": [0, 2, 0], "material": "clay" },
+//     { "type": "rect", "plane": "xz", "min": [-5, -5], "max": [5, 5], "offset": 0, "material": "clay" },
+//     { "type": "box", "min": [-1, 0, -1], "max": [1, 2, 1], "material": "clay" },
+//     { "type": "mesh", "file": "models/teapot.obj", "material": "ground", "groups": ["lid"] },
+//     { "type": "csg", "operation": "intersection",
+//       "left": { "type": "sphere", "center": [0, 2, 4], "radius": 2, "material": "glass" },
+//       "right": { "type": "sphere", "center": [0, 2, 7], "radius": 2, "material": "glass" } },
+//     { "type": "sdf", "material": "clay", "shape": { "type": "smooth_union", "smoothness": 0.3, "shapes": [
+//       { "type": "box", "center": [6, 0.5, 0], "size": [1, 1, 1], "radius": 0.1 },
+//       { "type": "sphere", "center": [6, 1, 0], "radius": 0.5 } ] } },
+//     { "type": "metaballs", "threshold": 0.5, "material": "glass", "balls": [
+//       { "center": [-4, 1, 2], "radius": 1.5 }, { "center": [-3, 1, 2], "radius": 1, "strength": 2 } ] },
+//     { "type": "curve", "points": [[5, 0, 3], [5, 1, 3], [6, 1, 3], [6, 2, 4]], "width": [0.1, 0.02],
+//       "shape": "cylinder", "material": "clay" },
+//     { "type": "grass", "corner": [-5, 0, 5], "size": [10, 2], "count": 5000, "height": 0.3,
+//       "width": [0.02, 0], "seed": 1, "material": "clay" },
+//     { "type": "heightfield", "corner": [-50, -5, -50], "size": [100, 5, 100], "material": "ground",
+//       "noise": { "seed": 1, "scale": 4, "octaves": 6, "resolution": 256 } },
+//     { "type": "constant_medium", "density": 0.5, "material": "fog",
+//       "boundary": { "type": "box", "min": [-3, 0, -3], "max": [3, 1, 3] } }
+//   ]
+// }
+//
+// The background is "sky" for the default gradient or a color such as [0, 0, 0], in which case
+// only lights illuminate the scene. Triangle normals and uvs are optional.
+//
+// A plane is infinite, with texture coordinates measured in units along it. A disk's texture
+// coordinates span the square around it from 0 to 1.
+// Cylinders and cones have caps unless "capped" is false. A capsule is every point within
+// "radius" of the segment from "from" to "to". A torus's "axis" defaults to [0, 1, 0], and its
+// "minor_radius", the radius of the tube, must be smaller than "major_radius".
+// A quad is the parallelogram with sides "u" and "v" from "corner", facing towards u x v.
+// A rect is axis-aligned: "min" and "max" are its corners in the two axes of "plane", "offset"
+// its position along the third, and it faces the positive direction of that axis.
+// A box spans from "min" to "max", with its sides facing outwards.
+// A csg object combines two solid objects, "left" and "right", by their "operation": union
+// (inside either), intersection (inside both) or difference (inside left but not right).
+// Its surface keeps the materials of the two it is made from. A plane is solid behind it.
+// A heightfield is terrain over the rectangle from "corner" to "corner" + "size" in x and z, rising
+// up to size's y above the corner. Heights come from the brightness of a grayscale "image" (PNG,
+// PPM or PGM, its top row towards -z) or from fractal "noise" ("seed", "scale", "octaves" as
+// for noise textures, sampled on a "resolution" by resolution grid, default 256), stretched to
+// span the full height. Its texture coordinates cover it once, the same way up as the image.
+// An sdf object is the surface of a signed distance field "shape", found by ray marching. Shapes
+// are "sphere" ("center", "radius"), "box" ("center", "size", and "radius" to round its edges),
+// "torus" ("center", "major_radius", "minor_radius", around the y axis), "capsule" ("from",
+// "to", "radius"), "smooth_union" (of "shapes", blended over "smoothness", default 0),
+// "smooth_subtraction" ("cut" carved out of "from", with "smoothness") and "repeat" (copies of
+// "shape" every "spacing" along each axis where it is not 0, forever, or "limit" copies each
+// side of the original). A repeated shape should fit within half the spacing around the origin.
+// A metaballs object is a blobby surface where the fields around its "balls" add up to its
+// "threshold" (default 0.5). Each ball's field is its "strength" (default 1) at its "center",
+// falling smoothly to 0 at its "radius", so nearby balls merge. A negative strength dents the
+// surface instead, and at least one ball must have a positive strength.
+// A curve is a cubic Bezier curve through its four control "points", a strip facing the viewer
+// whose "width" is a number or [start, end], changing steadily along it. A "cylinder" curve (the
+// default) is shaded as if round, a "flat" one as a ribbon. Grass is "count" flat curves (at
+// most a million), blades up to "height" tall and bending over at random (from "seed", default
+// 0), scattered over the rectangle from "corner" to "corner" + "size" ([x, z]). Curves suit a
+// "hair" material, which reflects light into the cone around the fiber, spread by "roughness"
+// (default 0.2).
+// A constant medium fills the inside of its "boundary", any object, with fog of the given
+// "density" that scatters light as its "material" does, normally an isotropic one. The
+// boundary needs no material of its own, and one given is not used.
+//
+// Wherever a material takes a color ("albedo", "emit", and a checker's "even" and "odd"),
+// it also takes a texture: a texture's name, or a texture object written in place.
+// Textures are "solid" ("color"), "checker" (3D cubes of side "scale", default 1)
+// and "image" (PNG, PPM or PGM "file", "wrap" is repeat, clamp or mirror, default repeat).
+// Noise textures are "marble" and "wood" (between colors "light" and "dark") and "turbulence"
+// ("color"), with optional "seed" (default 0), "scale" (default 1) and "octaves". The scale is
+// the frequency of turbulence, but for marble and wood only the spacing of bands and rings.
+// A texture can only refer to textures defined before it.
+//
+// Files are found relative to the scene file. Meshes are Wavefront OBJ files. Their faces use the
+// materials of the OBJ's own MTL libraries, and the mesh's "material" where they name none.
+// "groups" renders only the faces of the listed OBJ groups.
+//
+// Shapes are objects defined once, by name, and placed any number of times by objects of type
+// "instance", sharing their geometry. A shape can only refer to shapes defined before it.
+// Any object can have a "transform", a list of steps applied in order: { "translate": [x, y, z] },
+// { "scale": [x, y, z] } or { "scale": factor }, { "rotate": { "axis": [x, y, z], "angle": degrees } }
+// (counterclockwise looking down the axis), or { "matrix": [[...], [...], [...], [0, 0, 0, 1]] }.
+//
+// Objects move during the camera's "shutter" interval, blurring them, when a translate, scale or
+// rotate step has "from" and "to" values in place of its single one, as in
+// { "translate": { "from": [0, 0, 0], "to": [1, 0, 0], "time0": 0, "time1": 1 } } or
+// { "rotate": { "axis": [0, 1, 0], "from": 0, "to": 90 } }. The step changes steadily from
+// "time0" to "time1" (default 0 and 1), and holds still before and after. A moving sphere
+// likewise goes from "center0" to "center1". Without a shutter interval nothing is blurred.
+//
+// Objects refer to materials by name. Camera and render settings left out of the file
+// keep the values they had before loading, and command-line options override the file.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::cli::{Options, MAX_IMAGE_SIZE};
+use crate::bvh::BvhNode;
+use crate::csg::{Csg, Operation};
+use crate::curve::{self, Curve, CurveShape};
+use crate::cylinder::{Capsule, Cone, Cylinder};
+use crate::hittable::Hittable;
+use crate::heightfield::Heightfield;
+use crate::implicit::{ImplicitSurface, Metaball};
+use crate::image;
+use crate::instance::Instance;
+use crate::json::{self, Json, JsonValue};
+use crate::material::{Dielectric, DiffuseLight, Hair, Isotropic, Lambertian, Material, Metal};
+use crate::medium::ConstantMedium;
+use crate::noise::Perlin;
+use crate::obj;
+use crate::plane::{Disk, Plane};
+use crate::quad::{Cuboid, Quad};
+use crate::render::Background;
+use crate::sdf::{BoxSdf, CapsuleSdf, Repeat, Sdf, SdfHittable, SmoothSubtraction, SmoothUnion, SphereSdf, TorusSdf};
+use crate::sphere::{MovingSphere, Sphere};
+use crate::texture::{Checker, ImageTexture, Marble, SolidColor, Texture, TurbulenceTexture, Wood, WrapMode};
+use crate::torus::Torus;
+use crate::transform::{Change, Motion, Transform};
+use crate::triangle::Triangle;
+use crate::vec3::Vec3;
+use crate::world::World;
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io { file: String, error: io::Error },
+    Syntax { file: String, error: json::ParseError },
+    // The document is valid JSON but not a valid scene, path is e.g. "objects[2].material"
+    Invalid { file: String, line: usize, column: usize, path: String, message: String },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneError::Io { file, error } => write!(f, "{}: {}", file, error),
+            SceneError::Syntax { file, error } => write!(f, "{}:{}", file, error),
+            SceneError::Invalid { file, line, column, path, message } => {
+                write!(f, "{}:{}:{}: {}: {}", file, line, column, path, message)
+            }
+        }
+    }
+}
+
+// Scene errors before the file name is attached
+struct Invalid {
+    line: usize,
+    column: usize,
+    path: String,
+    message: String,
+}
+
+type LoadResult<T> = Result<T, Invalid>;
+
+pub fn load_scene(path: &Path, base: Options) -> Result<(World, Options), SceneError> {
+    let file = path.display().to_string();
+    let text = fs::read_to_string(path).map_err(|error| SceneError::Io { file: file.clone(), error })?;
+    parse_scene(&text, &file, base)
+}
+
+// file is the path of the scene, files it refers to are found relative to it
+pub fn parse_scene(text: &str, file: &str, base: Options) -> Result<(World, Options), SceneError> {
+    let document = json::parse(text).map_err(|error| SceneError::Syntax { file: file.to_string(), error })?;
+    let directory = Path::new(file).parent().unwrap_or_else(|| Path::new(""));
+    build_scene(&Node::root(&document), directory, base).map_err(|invalid| SceneError::Invalid {
+        file: file.to_string(),
+        line: invalid.line,
+        column: invalid.column,
+        path: invalid.path,
+        message: invalid.message,
+    })
+}
+
+fn build_scene(root: &Node, directory: &Path, base: Options) -> LoadResult<(World, Options)> {
+    root.check_fields(&["camera", "render", "textures", "materials", "shapes", "objects"])?;
+    let mut options = base;
+
+    if let Some(camera) = root.optional("camera")? {
+        load_camera(&camera, &mut options)?;
+    }
+    if let Some(render) = root.optional("render")? {
+        load_render_settings(&render, &mut options)?;
+    }
+
+    let mut textures = HashMap::new();
+    if let Some(textures_node) = root.optional("textures")? {
+        for (name, texture) in textures_node.members()? {
+            let loaded = load_texture(&texture, |node: &Node| node.color(), directory, &textures)?;
+            textures.insert(name.clone(), loaded);
+        }
+    }
+
+    let mut materials = HashMap::new();
+    if let Some(materials_node) = root.optional("materials")? {
+        for (name, material) in materials_node.members()? {
+            materials.insert(name.clone(), load_material(&material, directory, &textures)?);
+        }
+    }
+
+    let mut shapes = HashMap::new();
+    if let Some(shapes_node) = root.optional("shapes")? {
+        for (name, shape) in shapes_node.members()? {
+            let loaded = load_object(&shape, directory, &materials, None, &shapes)?;
+            shapes.insert(name.clone(), loaded);
+        }
+    }
+
+    let mut objects = vec![];
+    for object in root.field("objects")?.elements()? {
+        objects.push(load_object(&object, directory, &materials, None, &shapes)?);
+    }
+
+    Ok((World::new(objects), options))
+}
+
+fn load_camera(camera: &Node, options: &mut Options) -> LoadResult<()> {
+    camera.check_fields(&["look_from", "look_at", "vup", "vfov", "aperture", "focus_dist", "shutter"])?;
+    let camera_options = &mut options.camera;
+    if let Some(look_from) = camera.optional("look_from")? {
+        camera_options.look_from = look_from.vec3()?;
+    }
+    if let Some(look_at) = camera.optional("look_at")? {
+        camera_options.look_at = look_at.vec3()?;
+    }
+    if let Some(vup) = camera.optional("vup")? {
+        camera_options.vup = vup.vec3()?;
+    }
+    if let Some(vfov) = camera.optional("vfov")? {
+        let vertical_fov = vfov.number()?;
+        if vertical_fov <= 0.0 || vertical_fov >= 180.0 {
+            return Err(vfov.error("must be between 0 and 180 degrees"));
+        }
+        camera_options.vertical_fov = vertical_fov;
+    }
+    if let Some(aperture) = camera.optional("aperture")? {
+        camera_options.aperture = aperture.non_negative()?;
+    }
+    if let Some(focus_dist) = camera.optional("focus_dist")? {
+        camera_options.focus_dist = focus_dist.positive()?;
+    }
+    if let Some(shutter) = camera.optional("shutter")? {
+        let (open, close) = shutter.uv()?;
+        if close < open {
+            return Err(shutter.error("the shutter must close after it opens"));
+        }
+        camera_options.shutter = (open, close);
+    }
+    camera_options.check_basis().map_err(|message| camera.error(message))
+}
+
+fn image_size(size: &Node) -> LoadResult<i32> {
+    let value = size.integer(2)?;
+    if value > MAX_IMAGE_SIZE as i64 {
+        return Err(size.error(&format!("must be at most {}", MAX_IMAGE_SIZE)));
+    }
+    Ok(value as i32)
+}
+
+fn load_render_settings(render: &Node, options: &mut Options) -> LoadResult<()> {
+    render.check_fields(&["width", "height", "spp", "max_depth", "seed", "background"])?;
+    if let Some(width) = render.optional("width")? {
+        options.image_width = image_size(&width)?;
+    }
+    if let Some(height) = render.optional("height")? {
+        options.image_height = image_size(&height)?;
+    }
+    if let Some(spp) = render.optional("spp")? {
+        options.samples_per_pixel = spp.integer(1)? as i32;
+    }
+    if let Some(max_depth) = render.optional("max_depth")? {
+        options.max_depth = max_depth.integer(1)? as i32;
+    }
+    if let Some(seed) = render.optional("seed")? {
+        options.seed = Some(seed.integer(0)? as u64);
+    }
+    if let Some(background) = render.optional("background")? {
+        options.background = match background.json.value {
+            JsonValue::String(_) if background.string()? == "sky" => Background::Sky,
+            JsonValue::String(_) => return Err(background.error("expected \"sky\" or a color")),
+            _ => Background::Solid(background.radiance()?),
+        };
+    }
+    Ok(())
+}
+
+type Shapes = HashMap<String, Arc<dyn Hittable>>;
+type Textures = HashMap<String, Arc<dyn Texture>>;
+
+fn load_material(material: &Node, directory: &Path, textures: &Textures) -> LoadResult<Arc<dyn Material>> {
+    let material_type = material.field("type")?;
+    match material_type.string()? {
+        "lambertian" => {
+            material.check_fields(&["type", "albedo"])?;
+            let albedo = load_texture(&material.field("albedo")?, |node: &Node| node.color(), directory, textures)?;
+            Ok(Arc::new(Lambertian::textured(albedo)))
+        }
+        "metal" => {
+            material.check_fields(&["type", "albedo", "fuzz"])?;
+            let albedo = load_texture(&material.field("albedo")?, |node: &Node| node.color(), directory, textures)?;
+            let fuzz = match material.optional("fuzz")? {
+                Some(fuzz) => fuzz.non_negative()?,
+                None => 0.0,
+            };
+            Ok(Arc::new(Metal::textured(albedo, fuzz)))
+        }
+        "dielectric" => {
+            material.check_fields(&["type", "refractive_index"])?;
+            let refractive_index = material.field("refractive_index")?.positive()?;
+            Ok(Arc::new(Dielectric::new(refractive_index)))
+        }
+        "diffuse_light" => {
+            material.check_fields(&["type", "emit"])?;
+            let emit = load_texture(&material.field("emit")?, |node: &Node| node.radiance(), directory, textures)?;
+            Ok(Arc::new(DiffuseLight::textured(emit)))
+        }
+        "isotropic" => {
+            material.check_fields(&["type", "albedo"])?;
+            let albedo = load_texture(&material.field("albedo")?, |node: &Node| node.color(), directory, textures)?;
+            Ok(Arc::new(Isotropic::textured(albedo)))
+        }
+        "hair" => {
+            material.check_fields(&["type", "albedo", "roughness"])?;
+            let albedo = load_texture(&material.field("albedo")?, |node: &Node| node.color(), directory, textures)?;
+            let roughness = match material.optional("roughness")? {
+                Some(roughness) => roughness.non_negative()?,
+                None => 0.2,
+            };
+            Ok(Arc::new(Hair::textured(albedo, roughness)))
+        }
+        other => Err(material_type.error(&format!(
+            "unknown material type '{}', expected one of lambertian, metal, dielectric, diffuse_light, isotropic, hair",
+            other
+        ))),
+    }
+}
+
+// A color ([r, g, b] read by color), the name of a texture, or a texture object
+fn load_texture(
+    texture: &Node,
+    color: fn(&Node) -> LoadResult<Vec3>,
+    directory: &Path,
+    textures: &Textures,
+) -> LoadResult<Arc<dyn Texture>> {
+    match &texture.json.value {
+        JsonValue::Array(_) => return Ok(Arc::new(SolidColor::new(color(texture)?))),
+        JsonValue::String(name) => {
+            return textures
+                .get(name)
+                .cloned()
+                .ok_or_else(|| texture.error(&format!("unknown texture '{}'", name)));
+        }
+        JsonValue::Object(_) => {}
+        _ => return Err(texture.expected("a color, a texture name or a texture")),
+    }
+
+    let texture_type = texture.field("type")?;
+    match texture_type.string()? {
+        "solid" => {
+            texture.check_fields(&["type", "color"])?;
+            Ok(Arc::new(SolidColor::new(color(&texture.field("color")?)?)))
+        }
+        "checker" => {
+            texture.check_fields(&["type", "even", "odd", "scale"])?;
+            let even = load_texture(&texture.field("even")?, color, directory, textures)?;
+            let odd = load_texture(&texture.field("odd")?, color, directory, textures)?;
+            let scale = match texture.optional("scale")? {
+                Some(scale) => scale.positive()?,
+                None => 1.0,
+            };
+            Ok(Arc::new(Checker::new(even, odd, scale)))
+        }
+        "image" => {
+            texture.check_fields(&["type", "file", "wrap"])?;
+            let file = texture.field("file")?;
+            let image = image::load_image(&directory.join(file.string()?)).map_err(|error| file.error(&error.to_string()))?;
+            let wrap = match texture.optional("wrap")? {
+                None => WrapMode::Repeat,
+                Some(wrap) => match wrap.string()? {
+                    "repeat" => WrapMode::Repeat,
+                    "clamp" => WrapMode::Clamp,
+                    "mirror" => WrapMode::Mirror,
+                    other => {
+                        return Err(wrap.error(&format!("unknown wrap mode '{}', expected one of repeat, clamp, mirror", other)))
+                    }
+                },
+            };
+            Ok(Arc::new(ImageTexture::new(&image, wrap)))
+        }
+        "marble" | "wood" => {
+            texture.check_fields(&["type", "light", "dark", "seed", "scale", "octaves"])?;
+            let (default_light, default_dark) = if texture_type.string()? == "marble" {
+                (Vec3::new(0.9, 0.9, 0.9), Vec3::new(0.2, 0.2, 0.25))
+            } else {
+                (Vec3::new(0.75, 0.55, 0.3), Vec3::new(0.4, 0.22, 0.08))
+            };
+            let light = texture.optional("light")?.map_or(Ok(default_light), |light| color(&light))?;
+            let dark = texture.optional("dark")?.map_or(Ok(default_dark), |dark| color(&dark))?;
+            let (seed, scale, octaves) = noise_settings(texture)?;
+            if texture_type.string()? == "marble" {
+                Ok(Arc::new(Marble::new(seed, light, dark, scale, octaves)))
+            } else {
+                Ok(Arc::new(Wood::new(seed, light, dark, scale, octaves)))
+            }
+        }
+        "turbulence" => {
+            texture.check_fields(&["type", "color", "seed", "scale", "octaves"])?;
+            let turbulence_color = texture.optional("color")?.map_or(Ok(Vec3::new(1.0, 1.0, 1.0)), |c| color(&c))?;
+            let (seed, scale, octaves) = noise_settings(texture)?;
+            Ok(Arc::new(TurbulenceTexture::new(seed, turbulence_color, scale, octaves)))
+        }
+        other => Err(texture_type.error(&format!(
+            "unknown texture type '{}', expected one of solid, checker, image, marble, wood, turbulence",
+            other
+        ))),
+    }
+}
+
+// Seed, scale and octaves of a noise texture
+fn noise_settings(texture: &Node) -> LoadResult<(u64, f64, u32)> {
+    let seed = texture.optional("seed")?.map_or(Ok(0), |seed| seed.integer(0))? as u64;
+    let scale = texture.optional("scale")?.map_or(Ok(1.0), |scale| scale.positive())?;
+    let octaves = texture.optional("octaves")?.map_or(Ok(7), |octaves| octaves.integer(1))?;
+    if octaves > 32 {
+        return Err(texture.field("octaves")?.error("must be at most 32"));
+    }
+    Ok((seed, scale, octaves as u32))
+}
+
+// A width, or [start, end] for one changing along a curve
+fn load_widths(width: &Node) -> LoadResult<(f64, f64)> {
+    if width.elements().is_ok() {
+        let widths = width.uv()?;
+        if widths.0 < 0.0 || widths.1 < 0.0 || widths.0.max(widths.1) == 0.0 {
+            return Err(width.error("widths must not be negative, nor both 0"));
+        }
+        return Ok(widths);
+    }
+    let width = width.positive()?;
+    Ok((width, width))
+}
+
+// Any object can be moved by a "transform"
+// Objects without a "material" take the fallback, if there is one
+fn load_object(
+    object: &Node,
+    directory: &Path,
+    materials: &HashMap<String, Arc<dyn Material>>,
+    fallback: Option<&Arc<dyn Material>>,
+    shapes: &Shapes,
+) -> LoadResult<Arc<dyn Hittable>> {
+    let shape = load_shape(object, directory, materials, fallback, shapes)?;
+    match object.optional("transform")? {
+        Some(transform) => Ok(Arc::new(Instance::moving(shape, load_transform(&transform)?))),
+        None => Ok(shape),
+    }
+}
+
+// Steps applied in order, each an object with a single field
+fn load_transform(transform: &Node) -> LoadResult<Motion> {
+    let mut result = Motion::new();
+    for step in transform.elements()? {
+        let members = step.members()?;
+        if members.len() != 1 {
+            return Err(step.error("expected a single translate, rotate, scale or matrix"));
+        }
+        let (name, value) = &members[0];
+        if let Some(change) = load_change(name, value)? {
+            let (time0, time1) = load_times(value)?;
+            result = result
+                .then_moving(change, time0, time1)
+                .ok_or_else(|| value.error("scale must not pass through zero"))?;
+            continue;
+        }
+        let next = match name.as_str() {
+            "translate" => Transform::translation(value.vec3()?),
+            "scale" => {
+                let factors = match value.number() {
+                    Ok(factor) => Vec3::new(factor, factor, factor),
+                    Err(_) => value.vec3()?,
+                };
+                Transform::scaling(factors).ok_or_else(|| value.error("scale must not be zero"))?
+            }
+            "rotate" => {
+                value.check_fields(&["axis", "angle"])?;
+                Transform::rotation(value.field("axis")?.direction()?, value.field("angle")?.number()?)
+            }
+            "matrix" => {
+                let rows = value.elements()?;
+                if rows.len() != 4 {
+                    return Err(value.error(&format!("expected four rows but found {}", rows.len())));
+                }
+                let mut matrix = [[0.0; 4]; 4];
+                for (row, node) in matrix.iter_mut().zip(rows.iter()) {
+                    let numbers = node.elements()?;
+                    if numbers.len() != 4 {
+                        return Err(node.error(&format!("expected four numbers but found {}", numbers.len())));
+                    }
+                    for (entry, number) in row.iter_mut().zip(numbers.iter()) {
+                        *entry = number.number()?;
+                    }
+                }
+                Transform::from_matrix(matrix)
+                    .ok_or_else(|| value.error("matrix must be invertible with last row [0, 0, 0, 1]"))?
+            }
+            other => {
+                return Err(value.error(&format!(
+                    "unknown transform '{}', expected translate, rotate, scale or matrix",
+                    other
+                )))
+            }
+        };
+        result = result.then(&next);
+    }
+    Ok(result)
+}
+
+// A moving translate, rotate or scale step, which has "from" and "to" instead of a single value
+fn load_change(name: &str, value: &Node) -> LoadResult<Option<Change>> {
+    if !value.is_object() || value.optional("from")?.is_none() {
+        return Ok(None);
+    }
+    let change = match name {
+        "translate" => {
+            value.check_fields(&["from", "to", "time0", "time1"])?;
+            Change::Translate(value.field("from")?.vec3()?, value.field("to")?.vec3()?)
+        }
+        "rotate" => {
+            value.check_fields(&["axis", "from", "to", "time0", "time1"])?;
+            let axis = value.field("axis")?.direction()?;
+            Change::Rotate(axis, value.field("from")?.number()?, value.field("to")?.number()?)
+        }
+        "scale" => {
+            value.check_fields(&["from", "to", "time0", "time1"])?;
+            let factors = |node: Node| match node.number() {
+                Ok(factor) => Ok(Vec3::new(factor, factor, factor)),
+                Err(_) => node.vec3(),
+            };
+            Change::Scale(factors(value.field("from")?)?, factors(value.field("to")?)?)
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(change))
+}
+
+// "time0" and "time1" of moving objects, by default 0 and 1
+fn load_times(object: &Node) -> LoadResult<(f64, f64)> {
+    let time0 = match object.optional("time0")? {
+        Some(time) => time.number()?,
+        None => 0.0,
+    };
+    let time1 = match object.optional("time1")? {
+        Some(time) => time.number()?,
+        None => 1.0,
+    };
+    if time1 < time0 {
+        return Err(object.error("time1 must not be before time0"));
+    }
+    Ok((time0, time1))
+}
+
+// Every type load_shape handles, and only those, for the message about unknown types
+const OBJECT_TYPES: &[&str] = &[
+    "instance", "sphere", "moving_sphere", "plane", "disk", "cylinder", "cone", "capsule", "torus", "triangle",
+    "quad", "rect", "box", "mesh", "csg", "sdf", "heightfield", "metaballs", "curve", "grass", "constant_medium",
+];
+
+fn load_shape(
+    object: &Node,
+    directory: &Path,
+    materials: &HashMap<String, Arc<dyn Material>>,
+    fallback: Option<&Arc<dyn Material>>,
+    shapes: &Shapes,
+) -> LoadResult<Arc<dyn Hittable>> {
+    let object_type = object.field("type")?;
+    let type_name = object_type.string()?;
+    if !OBJECT_TYPES.contains(&type_name) {
+        return Err(object_type.error(&format!(
+            "unknown object type '{}', expected one of {}",
+            type_name,
+            OBJECT_TYPES.join(", ")
+        )));
+    }
+    match type_name {
+        "instance" => {
+            object.check_fields(&["type", "transform", "shape"])?;
+            let name = object.field("shape")?;
+            let name_string = name.string()?;
+            shapes
+                .get(name_string)
+                .cloned()
+                .ok_or_else(|| name.error(&format!("unknown shape '{}'", name_string)))
+        }
+        "heightfield" => {
+            object.check_fields(&["type", "transform", "corner", "size", "image", "noise", "material"])?;
+            let corner = object.field("corner")?.vec3()?;
+            let size = object.field("size")?.three(|node| node.positive())?;
+            let size = Vec3::new(size[0], size[1], size[2]);
+            let material = object_material(object, materials, fallback)?;
+            match (object.optional("image")?, object.optional("noise")?) {
+                (Some(file), None) => {
+                    let image = image::load_image(&directory.join(file.string()?)).map_err(|error| file.error(&error.to_string()))?;
+                    if image.width < 2 || image.height < 2 {
+                        return Err(file.error("the image must be at least 2 by 2 pixels"));
+                    }
+                    Ok(Arc::new(Heightfield::from_image(&image, corner, size, material)))
+                }
+                (None, Some(noise)) => {
+                    noise.check_fields(&["seed", "scale", "octaves", "resolution"])?;
+                    let (seed, scale, octaves) = noise_settings(&noise)?;
+                    let resolution = match noise.optional("resolution")? {
+                        Some(resolution) if resolution.integer(2)? > 4096 => {
+                            return Err(resolution.error("must be at most 4096"))
+                        }
+                        Some(resolution) => resolution.integer(2)? as usize,
+                        None => 256,
+                    };
+                    let perlin = Perlin::new(seed);
+                    let height = |u: f64, v: f64| perlin.fbm(Vec3::new(u * scale, 0.0, v * scale), octaves, 2.0, 0.5);
+                    Ok(Arc::new(Heightfield::from_function(resolution, resolution, height, corner, size, material)))
+                }
+                _ => Err(object.error("expected either an image or noise for the heights")),
+            }
+        }
+        "sdf" => {
+            object.check_fields(&["type", "transform", "shape", "material"])?;
+            let sdf = load_sdf(&object.field("shape")?)?;
+            let material = object_material(object, materials, fallback)?;
+            Ok(Arc::new(SdfHittable::new(sdf, material)))
+        }
+        "curve" => {
+            object.check_fields(&["type", "transform", "points", "width", "shape", "material"])?;
+            let points_node = object.field("points")?;
+            let points = points_node.elements()?;
+            if points.len() != 4 {
+                return Err(points_node.error(&format!("expected four control points but found {}", points.len())));
+            }
+            let points = [points[0].vec3()?, points[1].vec3()?, points[2].vec3()?, points[3].vec3()?];
+            if points.iter().all(|&point| point == points[0]) {
+                return Err(points_node.error("the control points must not all be the same"));
+            }
+            let widths = load_widths(&object.field("width")?)?;
+            let shape = match object.optional("shape")? {
+                Some(shape) => match shape.string()? {
+                    "flat" => CurveShape::Flat,
+                    "cylinder" => CurveShape::Cylinder,
+                    other => return Err(shape.error(&format!("unknown curve shape '{}', expected flat or cylinder", other))),
+                },
+                None => CurveShape::Cylinder,
+            };
+            let material = object_material(object, materials, fallback)?;
+            Ok(Arc::new(Curve::new(points, widths, shape, material)))
+        }
+        "grass" => {
+            object.check_fields(&["type", "transform", "corner", "size", "count", "height", "width", "seed", "material"])?;
+            let corner = object.field("corner")?.vec3()?;
+            let size_node = object.field("size")?;
+            let size = size_node.uv()?;
+            if size.0 <= 0.0 || size.1 <= 0.0 {
+                return Err(size_node.error("must be positive"));
+            }
+            let count_node = object.field("count")?;
+            let count = count_node.integer(1)?;
+            if count > 1_000_000 {
+                return Err(count_node.error("must be at most 1000000"));
+            }
+            let count = count as usize;
+            let height = object.field("height")?.positive()?;
+            let widths = load_widths(&object.field("width")?)?;
+            let seed = object.optional("seed")?.map_or(Ok(0), |seed| seed.integer(0))? as u64;
+            let material = object_material(object, materials, fallback)?;
+            Ok(Arc::new(BvhNode::new(curve::grass(corner, size, count, height, widths, seed, material))))
+        }
+        "metaballs" => {
+            object.check_fields(&["type", "transform", "balls", "threshold", "material"])?;
+            let balls_node = object.field("balls")?;
+            let mut balls = Vec::new();
+            let mut any_positive = false;
+            for ball in balls_node.elements()? {
+                ball.check_fields(&["center", "radius", "strength"])?;
+                let strength = match ball.optional("strength")? {
+                    Some(strength) => strength.number()?,
+                    None => 1.0,
+                };
+                any_positive |= strength > 0.0;
+                balls.push(Metaball::new(ball.field("center")?.vec3()?, ball.field("radius")?.positive()?, strength));
+            }
+            if !any_positive {
+                return Err(balls_node.error("expected at least one ball with a positive strength"));
+            }
+            let threshold = match object.optional("threshold")? {
+                Some(threshold) => threshold.positive()?,
+                None => 0.5,
+            };
+            let material = object_material(object, materials, fallback)?;
+            Ok(Arc::new(ImplicitSurface::new(balls, threshold, material)))
+        }
+        "csg" => {
+            object.check_fields(&["type", "transform", "operation", "left", "right"])?;
+            let operation_node = object.field("operation")?;
+            let operation = match operation_node.string()? {
+                "union" => Operation::Union,
+                "intersection" => Operation::Intersection,
+                "difference" => Operation::Difference,
+                other => {
+                    return Err(operation_node.error(&format!(
+                        "unknown operation '{}', expected union, intersection or difference",
+                        other
+                    )))
+                }
+            };
+            let left = load_object(&object.field("left")?, directory, materials, fallback, shapes)?;
+            let right = load_object(&object.field("right")?, directory, materials, fallback, shapes)?;
+            Ok(Arc::new(Csg::new(operation, left, right)))
+        }
+        "constant_medium" => {
+            object.check_fields(&["type", "transform", "boundary", "density", "material"])?;
+            let density = object.field("density")?.positive()?;
+            let material = object_material(object, materials, fallback)?;
+            // Only the boundary's shape matters, it needs no material of its own
+            let boundary = load_object(&object.field("boundary")?, directory, materials, Some(&material), shapes)?;
+            Ok(Arc::new(ConstantMedium::new(boundary, density, material)))
+        }
+        "moving_sphere" => {
+            object.check_fields(&["type", "transform", "center0", "center1", "time0", "time1", "radius", "material"])?;
+            let center0 = object.field("center0")?.vec3()?;
+            let center1 = object.field("center1")?.vec3()?;
+            let (time0, time1) = load_times(object)?;
+            let radius = object.field("radius")?.positive()?;
+            let material = object_material(object, materials, fallback)?;
+            Ok(Arc::new(MovingSphere::new(center0, center1, time0, time1, radius, material)))
+        }
+        "sphere" => {
+            object.check_fields(&["type", "transform", "center", "radius", "material"])?;
+            let center = object.field("center")?.vec3()?;
+            let radius = object.field("radius")?.positive()?;
+            let material = object_material(object, materials, fallback)?;
+            Ok(Arc::new(Sphere::new(center, radius, material)))
+        }
+        "triangle" => {
+            object.check_fields(&["type", "transform", "vertices", "normals", "uvs", "material"])?;
+            let vertices = object.field("vertices")?.three(Node::vec3)?;
+            let material = object_material(object, materials, fallback)?;
+            let mut triangle = Triangle::new(vertices[0], vertices[1], vertices[2], material);
+            if let Some(normals) = object.optional("normals")? {
+                let normals = normals.three(Node::direction)?;
+                triangle = triangle.with_normals([normals[0], normals[1], normals[2]]);
+            }
+            if let Some(uvs) = object.optional("uvs")? {
+                let uvs = uvs.three(Node::uv)?;
+                triangle = triangle.with_uvs([uvs[0], uvs[1], uvs[2]]);
+            }
+            Ok(Arc::new(triangle))
+        }
+        "plane" => {
+            object.check_fields(&["type", "transform", "point", "normal", "material"])?;
+            let point = object.field("point")?.vec3()?;
+            let normal = object.field("normal")?.direction()?;
+            let material = object_material(object, materials, fallback)?;
+            Ok(Arc::new(Plane::new(point, normal, material)))
+        }
+        "disk" => {
+            object.check_fields(&["type", "transform", "center", "normal", "radius", "material"])?;
+            let center = object.field("center")?.vec3()?;
+            let normal = object.field("normal")?.direction()?;
+            let radius = object.field("radius")?.positive()?;
+            let material = object_material(object, materials, fallback)?;
+            Ok(Arc::new(Disk::new(center, normal, radius, material)))
+        }
+        "cylinder" => {
+            object.check_fields(&["type", "transform", "base", "top", "radius", "capped", "material"])?;
+            let base = object.field("base")?.vec3()?;
+            let top = object.field("top")?.vec3()?;
+            if top == base {
+                return Err(object.field("top")?.error("must not be the same point as base"));
+            }
+            let radius = object.field("radius")?.positive()?;
+            let capped = optional_boolean(object, "capped", true)?;
+            let material = object_material(object, materials, fallback)?;
+            Ok(Arc::new(Cylinder::new(base, top, radius, capped, material)))
+        }
+        "cone" => {
+            object.check_fields(&["type", "transform", "base", "apex", "radius", "capped", "material"])?;
+            let base = object.field("base")?.vec3()?;
+            let apex = object.field("apex")?.vec3()?;
+            if apex == base {
+                return Err(object.field("apex")?.error("must not be the same point as base"));
+            }
+            let radius = object.field("radius")?.positive()?;
+            let capped = optional_boolean(object, "capped", true)?;
+            let material = object_material(object, materials, fallback)?;
+            Ok(Arc::new(Cone::new(base, apex, radius, capped, material)))
+        }
+        "capsule" => {
+            object.check_fields(&["type", "transform", "from", "to", "radius", "material"])?;
+            let from = object.field("from")?.vec3()?;
+            let to = object.field("to")?.vec3()?;
+            let radius = object.field("radius")?.positive()?;
+            let material = object_material(object, materials, fallback)?;
+            Ok(Arc::new(Capsule::new(from, to, radius, material)))
+        }
+        "torus" => {
+            object.check_fields(&["type", "transform", "center", "axis", "major_radius", "minor_radius", "material"])?;
+            let center = object.field("center")?.vec3()?;
+            let axis = match object.optional("axis")? {
+                Some(axis) => axis.direction()?,
+                None => Vec3::new(0.0, 1.0, 0.0),
+            };
+            let major_radius = object.field("major_radius")?.positive()?;
+            let minor = object.field("minor_radius")?;
+            let minor_radius = minor.positive()?;
+            if minor_radius >= major_radius {
+                return Err(minor.error("must be smaller than major_radius"));
+            }
+            let material = object_material(object, materials, fallback)?;
+            Ok(Arc::new(Torus::new(center, axis, major_radius, minor_radius, material)))
+        }
+        "quad" => {
+            object.check_fields(&["type", "transform", "corner", "u", "v", "material"])?;
+            let corner = object.field("corner")?.vec3()?;
+            let u = object.field("u")?.direction()?;
+            let v = object.field("v")?.direction()?;
+            if u.cross(v).length_squared() == 0.0 {
+                return Err(object.field("v")?.error("sides must not be parallel"));
+            }
+            let material = object_material(object, materials, fallback)?;
+            Ok(Arc::new(Quad::new(corner, u, v, material)))
+        }
+        "rect" => {
+            object.check_fields(&["type", "transform", "plane", "min", "max", "offset", "material"])?;
+            let plane = object.field("plane")?;
+            let (min, max) = (object.field("min")?.uv()?, object.field("max")?.uv()?);
+            if min.0 >= max.0 || min.1 >= max.1 {
+                return Err(object.field("max")?.error("must be greater than min"));
+            }
+            let rect: fn(f64, f64, f64, f64, f64, Arc<dyn Material>) -> Quad = match plane.string()? {
+                "xy" => Quad::xy_rect,
+                "xz" => Quad::xz_rect,
+                "yz" => Quad::yz_rect,
+                other => return Err(plane.error(&format!("unknown plane '{}', expected xy, xz or yz", other))),
+            };
+            let offset = object.field("offset")?.number()?;
+            let material = object_material(object, materials, fallback)?;
+            Ok(Arc::new(rect(min.0, max.0, min.1, max.1, offset, material)))
+        }
+        "box" => {
+            object.check_fields(&["type", "transform", "min", "max", "material"])?;
+            let (min, max) = (object.field("min")?.vec3()?, object.field("max")?.vec3()?);
+            if min.x >= max.x || min.y >= max.y || min.z >= max.z {
+                return Err(object.field("max")?.error("must be greater than min"));
+            }
+            let material = object_material(object, materials, fallback)?;
+            Ok(Arc::new(Cuboid::new(min, max, material)))
+        }
+        "mesh" => {
+            object.check_fields(&["type", "transform", "file", "material", "groups"])?;
+            let file = object.field("file")?;
+            let path: PathBuf = directory.join(file.string()?);
+            let default_material = match object.optional("material")? {
+                Some(material) => Some(lookup_material(&material, materials)?),
+                None => fallback.cloned(),
+            };
+            let groups = match object.optional("groups")? {
+                Some(groups) => Some(
+                    groups
+                        .elements()?
+                        .iter()
+                        .map(|group| group.string().map(String::from))
+                        .collect::<LoadResult<Vec<_>>>()?,
+                ),
+                None => None,
+            };
+            let model = obj::load_obj(&path, default_material).map_err(|error| file.error(&error.to_string()))?;
+            match model.into_mesh(groups.as_deref()) {
+                Some(mesh) => Ok(Arc::new(mesh)),
+                None => Err(object.field("groups")?.error("no faces in the listed groups")),
+            }
+        }
+        other => unreachable!("object type '{}' is listed but not loaded", other),
+    }
+}
+
+// A signed distance field, shapes nested within combinations and repetitions
+fn load_sdf(shape: &Node) -> LoadResult<Arc<dyn Sdf>> {
+    let shape_type = shape.field("type")?;
+    let smoothness = |shape: &Node| match shape.optional("smoothness")? {
+        Some(smoothness) => smoothness.non_negative(),
+        None => Ok(0.0),
+    };
+    match shape_type.string()? {
+        "sphere" => {
+            shape.check_fields(&["type", "center", "radius"])?;
+            Ok(Arc::new(SphereSdf::new(shape.field("center")?.vec3()?, shape.field("radius")?.positive()?)))
+        }
+        "box" => {
+            shape.check_fields(&["type", "center", "size", "radius"])?;
+            let center = shape.field("center")?.vec3()?;
+            let size = shape.field("size")?.three(|node| node.positive())?;
+            let size = Vec3::new(size[0], size[1], size[2]);
+            let radius = match shape.optional("radius")? {
+                Some(radius) => {
+                    let value = radius.non_negative()?;
+                    if 2.0 * value > size.x.min(size.y).min(size.z) {
+                        return Err(radius.error("radius must be at most half the smallest side"));
+                    }
+                    value
+                }
+                None => 0.0,
+            };
+            Ok(Arc::new(BoxSdf::new(center, size, radius)))
+        }
+        "torus" => {
+            shape.check_fields(&["type", "center", "major_radius", "minor_radius"])?;
+            let center = shape.field("center")?.vec3()?;
+            let major_radius = shape.field("major_radius")?.positive()?;
+            let minor_radius = shape.field("minor_radius")?.positive()?;
+            Ok(Arc::new(TorusSdf::new(center, major_radius, minor_radius)))
+        }
+        "capsule" => {
+            shape.check_fields(&["type", "from", "to", "radius"])?;
+            let from = shape.field("from")?.vec3()?;
+            let to = shape.field("to")?.vec3()?;
+            Ok(Arc::new(CapsuleSdf::new(from, to, shape.field("radius")?.positive()?)))
+        }
+        "smooth_union" => {
+            shape.check_fields(&["type", "shapes", "smoothness"])?;
+            let smoothness = smoothness(shape)?;
+            let shapes_node = shape.field("shapes")?;
+            let mut shapes = shapes_node.elements()?.into_iter();
+            let first = shapes.next().ok_or_else(|| shapes_node.error("expected at least one shape"))?;
+            shapes.try_fold(load_sdf(&first)?, |union, next| {
+                Ok(Arc::new(SmoothUnion::new(union, load_sdf(&next)?, smoothness)) as Arc<dyn Sdf>)
+            })
+        }
+        "smooth_subtraction" => {
+            shape.check_fields(&["type", "from", "cut", "smoothness"])?;
+            let from = load_sdf(&shape.field("from")?)?;
+            let cut = load_sdf(&shape.field("cut")?)?;
+            Ok(Arc::new(SmoothSubtraction::new(from, cut, smoothness(shape)?)))
+        }
+        "repeat" => {
+            shape.check_fields(&["type", "shape", "spacing", "limit"])?;
+            let spacing = shape.field("spacing")?.three(|node| node.non_negative())?;
+            let spacing = Vec3::new(spacing[0], spacing[1], spacing[2]);
+            let limit = match shape.optional("limit")? {
+                Some(limit) => {
+                    let counts = limit.three(|node| Ok(node.integer(0)? as f64))?;
+                    Some(Vec3::new(counts[0], counts[1], counts[2]))
+                }
+                None => None,
+            };
+            Ok(Arc::new(Repeat::new(load_sdf(&shape.field("shape")?)?, spacing, limit)))
+        }
+        other => Err(shape_type.error(&format!(
+            "unknown sdf type '{}', expected one of sphere, box, torus, capsule, smooth_union, smooth_subtraction, repeat",
+            other
+        ))),
+    }
+}
+
+fn optional_boolean(object: &Node, name: &str, default: bool) -> LoadResult<bool> {
+    match object.optional(name)? {
+        Some(value) => value.boolean(),
+        None => Ok(default),
+    }
+}
+
+// The material an object names, or the fallback if it names none
+fn object_material(
+    object: &Node,
+    materials: &HashMap<String, Arc<dyn Material>>,
+    fallback: Option<&Arc<dyn Material>>,
+) -> LoadResult<Arc<dyn Material>> {
+    match (object.optional("material")?, fallback) {
+        (None, Some(fallback)) => Ok(fallback.clone()),
+        _ => lookup_material(&object.field("material")?, materials),
+    }
+}
+
+fn lookup_material(name: &Node, materials: &HashMap<String, Arc<dyn Material>>) -> LoadResult<Arc<dyn Material>> {
+    let name_string = name.string()?;
+    materials
+        .get(name_string)
+        .cloned()
+        .ok_or_else(|| name.error(&format!("unknown material '{}'", name_string)))
+}
+
+// A JSON value together with where it sits in the document
+struct Node<'a> {
+    json: &'a Json,
+    path: String,
+}
+
+impl<'a> Node<'a> {
+    fn root(json: &'a Json) -> Self {
+        Node { json, path: String::from("(root)") }
+    }
+
+    fn child(&self, json: &'a Json, name: &str) -> Self {
+        let path = if self.path == "(root)" {
+            name.to_string()
+        } else {
+            format!("{}.{}", self.path, name)
+        };
+        Node { json, path }
+    }
+
+    fn error(&self, message: &str) -> Invalid {
+        Invalid {
+            line: self.json.line,
+            column: self.json.column,
+            path: self.path.clone(),
+            message: message.to_string(),
+        }
+    }
+
+    fn expected(&self, kind: &str) -> Invalid {
+        self.error(&format!("expected {} but found {}", kind, self.json.value.kind()))
+    }
+
+    fn is_object(&self) -> bool {
+        matches!(self.json.value, JsonValue::Object(_))
+    }
+
+    fn members(&self) -> LoadResult<Vec<(String, Node<'a>)>> {
+        match &self.json.value {
+            JsonValue::Object(members) => Ok(members
+                .iter()
+                .map(|(name, json)| (name.clone(), self.child(json, name)))
+                .collect()),
+            _ => Err(self.expected("an object")),
+        }
+    }
+
+    fn elements(&self) -> LoadResult<Vec<Node<'a>>> {
+        match &self.json.value {
+            JsonValue::Array(elements) => Ok(elements
+                .iter()
+                .enumerate()
+                .map(|(index, json)| Node { json, path: format!("{}[{}]", self.path, index) })
+                .collect()),
+            _ => Err(self.expected("an array")),
+        }
+    }
+
+    fn optional(&self, name: &str) -> LoadResult<Option<Node<'a>>> {
+        match &self.json.value {
+            JsonValue::Object(members) => Ok(members
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, json)| self.child(json, name))),
+            _ => Err(self.expected("an object")),
+        }
+    }
+
+    fn field(&self, name: &str) -> LoadResult<Node<'a>> {
+        self.optional(name)?
+            .ok_or_else(|| self.error(&format!("missing field '{}'", name)))
+    }
+
+    // Catches misspelt fields, which would otherwise be silently ignored
+    fn check_fields(&self, allowed: &[&str]) -> LoadResult<()> {
+        for (name, member) in self.members()? {
+            if !allowed.contains(&name.as_str()) {
+                return Err(member.error(&format!(
+                    "unknown field '{}', expected one of {}",
+                    name,
+                    allowed.join(", ")
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn string(&self) -> LoadResult<&'a str> {
+        match &self.json.value {
+            JsonValue::String(string) => Ok(string),
+            _ => Err(self.expected("a string")),
+        }
+    }
+
+    fn boolean(&self) -> LoadResult<bool> {
+        match self.json.value {
+            JsonValue::Bool(value) => Ok(value),
+            _ => Err(self.expected("a boolean")),
+        }
+    }
+
+    fn number(&self) -> LoadResult<f64> {
+        match self.json.value {
+            JsonValue::Number(number) if number.is_finite() => Ok(number),
+            _ => Err(self.expected("a number")),
+        }
+    }
+
+    fn positive(&self) -> LoadResult<f64> {
+        let number = self.number()?;
+        if number <= 0.0 {
+            return Err(self.error("must be positive"));
+        }
+        Ok(number)
+    }
+
+    fn non_negative(&self) -> LoadResult<f64> {
+        let number = self.number()?;
+        if number < 0.0 {
+            return Err(self.error("must not be negative"));
+        }
+        Ok(number)
+    }
+
+    fn integer(&self, min: i64) -> LoadResult<i64> {
+        let number = self.number()?;
+        if number.fract() != 0.0 || number > i32::MAX as f64 {
+            return Err(self.expected("an integer"));
+        }
+        if (number as i64) < min {
+            return Err(self.error(&format!("must be at least {}", min)));
+        }
+        Ok(number as i64)
+    }
+
+    // [x, y, z]
+    fn vec3(&self) -> LoadResult<Vec3> {
+        let elements = self.elements().map_err(|_| self.expected("an array of three numbers"))?;
+        if elements.len() != 3 {
+            return Err(self.error(&format!("expected three numbers but found {}", elements.len())));
+        }
+        Ok(Vec3::new(elements[0].number()?, elements[1].number()?, elements[2].number()?))
+    }
+
+    // Non-zero vector
+    fn direction(&self) -> LoadResult<Vec3> {
+        let direction = self.vec3()?;
+        if direction.length_squared() == 0.0 {
+            return Err(self.error("direction must not be zero"));
+        }
+        Ok(direction)
+    }
+
+    // [u, v]
+    fn uv(&self) -> LoadResult<(f64, f64)> {
+        let elements = self.elements().map_err(|_| self.expected("an array of two numbers"))?;
+        if elements.len() != 2 {
+            return Err(self.error(&format!("expected two numbers but found {}", elements.len())));
+        }
+        Ok((elements[0].number()?, elements[1].number()?))
+    }
+
+    // Array of exactly three values
+    fn three<T>(&self, element: impl Fn(&Node<'a>) -> LoadResult<T>) -> LoadResult<Vec<T>> {
+        let elements = self.elements()?;
+        if elements.len() != 3 {
+            return Err(self.error(&format!("expected three values but found {}", elements.len())));
+        }
+        elements.iter().map(element).collect()
+    }
+
+    // [r, g, b], each component in [0, 1]
+    fn color(&self) -> LoadResult<Vec3> {
+        let color = self.vec3()?;
+        for component in [color.x, color.y, color.z].iter() {
+            if *component < 0.0 || *component > 1.0 {
+                return Err(self.error("color components must be between 0 and 1"));
+            }
+        }
+        Ok(color)
+    }
+
+    // [r, g, b] of emitted light, components can be brighter than 1
+    fn radiance(&self) -> LoadResult<Vec3> {
+        let color = self.vec3()?;
+        if color.x < 0.0 || color.y < 0.0 || color.z < 0.0 {
+            return Err(self.error("color components must not be negative"));
+        }
+        Ok(color)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::point::Point;
+    use crate::ray::Ray;
+
+    fn parse(text: &str) -> Result<(World, Options), SceneError> {
+        parse_scene(text, "test.json", Options::default())
+    }
+
+    // Line, column and path of an Invalid error
+    fn invalid_at(text: &str) -> (usize, usize, String, String) {
+        match parse(text) {
+            Err(SceneError::Invalid { line, column, path, message, .. }) => (line, column, path, message),
+            Err(other) => panic!("expected an invalid scene, got {}", other),
+            Ok(_) => panic!("expected an invalid scene"),
+        }
+    }
+
+    #[test]
+    fn example_scene_should_load() {
+        let (world, options) = parse_scene(
+            include_str!("../scenes/example.json"),
+            "example.json",
+            Options::default(),
+        )
+        .unwrap();
+        assert_eq!(options.image_width, 400);
+        assert_eq!(options.samples_per_pixel, 50);
+        assert_eq!(options.camera.look_from, Point::new(13.0, 2.0, 3.0));
+
+        // Straight down onto the ground sphere
+        let ray = Ray::new(Point::new(1.5, 5.0, -2.5), Vec3::new(0.0, -1.0, 0.0));
+        let hit = world.hit(ray, 0.001, f64::INFINITY).unwrap();
+        assert!(hit.point.y.abs() < 1e-2);
+    }
+
+    #[test]
+    fn settings_missing_from_file_should_keep_base_values() {
+        let (_, options) = parse(r#"{ "camera": { "vfov": 40 }, "objects": [] }"#).unwrap();
+        assert_eq!(options.camera.vertical_fov, 40.0);
+        assert_eq!(options.camera.aperture, Options::default().camera.aperture);
+        assert_eq!(options.image_height, Options::default().image_height);
+    }
+
+    #[test]
+    fn unknown_material_should_report_path_and_line() {
+        let text = r#"{
+  "materials": { "red": { "type": "lambertian", "albedo": [1, 0, 0] } },
+  "objects": [
+    { "type": "sphere", "center": [0, 0, 0], "radius": 1, "material": "red" },
+    { "type": "sphere", "center": [0, 0, 0], "radius": 1, "material": "blue" }
+  ]
+}"#;
+        let (line, column, path, message) = invalid_at(text);
+        assert_eq!((line, column), (5, 71));
+        assert_eq!(path, "objects[1].material");
+        assert_eq!(message, "unknown material 'blue'");
+    }
+
+    #[test]
+    fn missing_field_should_be_reported() {
+        let text = r#"{ "objects": [ { "type": "sphere", "center": [0, 0, 0], "material": "x" } ] }"#;
+        let (_, column, path, message) = invalid_at(text);
+        assert_eq!(column, 16);
+        assert_eq!(path, "objects[0]");
+        assert_eq!(message, "missing field 'radius'");
+
+        let (_, _, path, message) = invalid_at(r#"{ "camera": {} }"#);
+        assert_eq!(path, "(root)");
+        assert_eq!(message, "missing field 'objects'");
+    }
+
+    #[test]
+    fn invalid_values_should_be_reported() {
+        let (_, _, path, message) = invalid_at(r#"{ "camera": { "look_from": [0, 1] }, "objects": [] }"#);
+        assert_eq!(path, "camera.look_from");
+        assert_eq!(message, "expected three numbers but found 2");
+
+        let (_, _, path, _) = invalid_at(r#"{ "render": { "spp": 2.5 }, "objects": [] }"#);
+        assert_eq!(path, "render.spp");
+        let (_, _, path, _) = invalid_at(r#"{ "render": { "width": 4294967298 }, "objects": [] }"#);
+        assert_eq!(path, "render.width");
+
+        let (_, _, path, message) = invalid_at(r#"{ "render": { "sample": 2 }, "objects": [] }"#);
+        assert_eq!(path, "render.sample");
+        assert!(message.starts_with("unknown field 'sample'"));
+
+        let (_, _, path, _) = invalid_at(r#"{ "materials": { "m": { "type": "plastic" } }, "objects": [] }"#);
+        assert_eq!(path, "materials.m.type");
+    }
+
+    #[test]
+    fn lights_and_background_should_load() {
+        let text = r#"{
+  "render": { "background": [0, 0, 0] },
+  "materials": { "lamp": { "type": "diffuse_light", "emit": [4, 4, 4] } },
+  "objects": [ { "type": "sphere", "center": [0, 0, 0], "radius": 1, "material": "lamp" } ]
+}"#;
+        let (world, options) = parse(text).unwrap();
+        assert_eq!(options.background, Background::Solid(Vec3::new(0.0, 0.0, 0.0)));
+        let ray = Ray::new(Point::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        let hit = world.hit(ray, 0.001, f64::INFINITY).unwrap();
+        assert_eq!(hit.material.emitted(hit.u, hit.v, hit.point), Vec3::new(4.0, 4.0, 4.0));
+
+        let (_, _, path, _) = invalid_at(r#"{ "render": { "background": "night" }, "objects": [] }"#);
+        assert_eq!(path, "render.background");
+        let (_, _, path, _) = invalid_at(r#"{ "materials": { "m": { "type": "diffuse_light", "emit": [-1, 0, 0] } }, "objects": [] }"#);
+        assert_eq!(path, "materials.m.emit");
+    }
+
+    #[test]
+    fn triangle_should_load() {
+        let text = r#"{
+  "materials": { "red": { "type": "lambertian", "albedo": [1, 0, 0] } },
+  "objects": [
+    { "type": "triangle", "vertices": [[0, 0, -1], [1, 0, -1], [0, 1, -1]], "uvs": [[0, 0], [1, 0], [1, 1]],
+      "material": "red" }
+  ]
+}"#;
+        let (world, _) = parse(text).unwrap();
+        let ray = Ray::new(Point::new(0.5, 0.5, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let hit = world.hit(ray, 0.001, f64::INFINITY).unwrap();
+        assert_eq!((hit.u, hit.v), (1.0, 0.5));
+
+        let (_, _, path, message) = invalid_at(
+            r#"{ "objects": [ { "type": "triangle", "vertices": [[0, 0, 0], [1, 0, 0]], "material": "m" } ] }"#,
+        );
+        assert_eq!(path, "objects[0].vertices");
+        assert_eq!(message, "expected three values but found 2");
+    }
+
+    #[test]
+    fn mesh_scene_should_load() {
+        let (world, _) = load_scene(Path::new("scenes/mesh.json"), Options::default()).unwrap();
+        // Straight down onto the apex of the pyramid
+        let ray = Ray::new(Point::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let hit = world.hit(ray, 0.001, f64::INFINITY).unwrap();
+        assert!((hit.point.y - 1.5).abs() < 1e-9);
+
+        let (_, _, path, message) = invalid_at(r#"{ "objects": [ { "type": "mesh", "file": "missing.obj" } ] }"#);
+        assert_eq!(path, "objects[0].file");
+        assert!(message.starts_with("missing.obj: "));
+    }
+
+    #[test]
+    fn plane_and_disk_should_load() {
+        let text = r#"{
+  "materials": { "grey": { "type": "lambertian", "albedo": [0.5, 0.5, 0.5] } },
+  "objects": [
+    { "type": "plane", "point": [0, -1, 0], "normal": [0, 1, 0], "material": "grey" },
+    { "type": "disk", "center": [0, 0, 0], "normal": [0, 1, 0], "radius": 1, "material": "grey" }
+  ]
+}"#;
+        let (world, _) = parse(text).unwrap();
+        let down = Vec3::new(0.0, -1.0, 0.0);
+        assert_eq!(world.hit(Ray::new(Point::new(0.5, 1.0, 0.5), down), 0.001, f64::INFINITY).unwrap().t, 1.0);
+        assert_eq!(world.hit(Ray::new(Point::new(1.5, 1.0, 0.5), down), 0.001, f64::INFINITY).unwrap().t, 2.0);
+
+        let (_, _, path, message) = invalid_at(
+            r#"{ "objects": [ { "type": "plane", "point": [0, 0, 0], "normal": [0, 0, 0], "material": "m" } ] }"#,
+        );
+        assert_eq!(path, "objects[0].normal");
+        assert_eq!(message, "direction must not be zero");
+    }
+
+    #[test]
+    fn shapes_of_revolution_should_load() {
+        let text = r#"{
+  "materials": { "grey": { "type": "lambertian", "albedo": [0.5, 0.5, 0.5] } },
+  "objects": [
+    { "type": "cylinder", "base": [0, 0, 0], "top": [0, 1, 0], "radius": 1, "capped": false, "material": "grey" },
+    { "type": "cone", "base": [5, 0, 0], "apex": [5, 1, 0], "radius": 1, "material": "grey" },
+    { "type": "capsule", "from": [10, 0, 0], "to": [10, 1, 0], "radius": 1, "material": "grey" },
+    { "type": "torus", "center": [15, 0, 0], "major_radius": 2, "minor_radius": 1, "material": "grey" }
+  ]
+}"#;
+        let (world, _) = parse(text).unwrap();
+        let down = Vec3::new(0.0, -1.0, 0.0);
+        // Through the open cylinder
+        assert!(world.hit(Ray::new(Point::new(0.0, 3.0, 0.0), down), 0.001, f64::INFINITY).is_none());
+        assert_eq!(world.hit(Ray::new(Point::new(5.0, 3.0, 0.0), down), 0.001, f64::INFINITY).unwrap().t, 2.0);
+        assert_eq!(world.hit(Ray::new(Point::new(10.0, 3.0, 0.0), down), 0.001, f64::INFINITY).unwrap().t, 1.0);
+        let hit = world.hit(Ray::new(Point::new(17.0, 3.0, 0.0), down), 0.001, f64::INFINITY).unwrap();
+        assert!((hit.t - 2.0).abs() < 1e-9);
+
+        let (_, _, path, message) = invalid_at(
+            r#"{ "objects": [ { "type": "cone", "base": [0, 0, 0], "apex": [0, 1, 0], "radius": 1, "capped": 1, "material": "m" } ] }"#,
+        );
+        assert_eq!(path, "objects[0].capped");
+        assert!(message.contains("a boolean"), "{}", message);
+    }
+
+    #[test]
+    fn instances_should_place_shapes() {
+        let text = r#"{
+  "materials": { "grey": { "type": "lambertian", "albedo": [0.5, 0.5, 0.5] } },
+  "shapes": {
+    "ball": { "type": "sphere", "center": [0, 0, 0], "radius": 1, "material": "grey" },
+    "pair": { "type": "instance", "shape": "ball", "transform": [{ "scale": [1, 2, 1] }] }
+  },
+  "objects": [
+    { "type": "instance", "shape": "ball", "transform": [{ "translate": [5, 0, 0] }] },
+    { "type": "instance", "shape": "pair", "transform": [{ "rotate": { "axis": [0, 0, 1], "angle": 90 } }] },
+    { "type": "box", "min": [0, 0, 0], "max": [1, 1, 1], "material": "grey",
+      "transform": [{ "matrix": [[1, 0, 0, 10], [0, 1, 0, 0], [0, 0, 1, 0], [0, 0, 0, 1]] }] }
+  ]
+}"#;
+        let (world, _) = parse(text).unwrap();
+        let down = Vec3::new(0.0, -1.0, 0.0);
+        assert_eq!(world.hit(Ray::new(Point::new(5.0, 3.0, 0.0), down), 0.001, f64::INFINITY).unwrap().t, 2.0);
+        // Stretched along y, then turned to lie along x
+        let across = Ray::new(Point::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!((world.hit(across, 0.001, f64::INFINITY).unwrap().t - 3.0).abs() < 1e-9);
+        assert_eq!(world.hit(Ray::new(Point::new(10.5, 3.0, 0.5), down), 0.001, f64::INFINITY).unwrap().t, 2.0);
+
+        let (_, _, path, message) = invalid_at(r#"{ "objects": [ { "type": "instance", "shape": "ball" } ] }"#);
+        assert_eq!(path, "objects[0].shape");
+        assert_eq!(message, "unknown shape 'ball'");
+        let (_, _, path, message) = invalid_at(
+            r#"{ "materials": { "m": { "type": "lambertian", "albedo": [1, 1, 1] } },
+                 "objects": [ { "type": "plane", "point": [0, 0, 0], "normal": [0, 1, 0], "material": "m",
+                 "transform": [{ "scale": [1, 0, 1] }] } ] }"#,
+        );
+        assert_eq!(path, "objects[0].transform[0].scale");
+        assert_eq!(message, "scale must not be zero");
+        let (_, _, path, _) = invalid_at(
+            r#"{ "materials": { "m": { "type": "lambertian", "albedo": [1, 1, 1] } },
+                 "objects": [ { "type": "plane", "point": [0, 0, 0], "normal": [0, 1, 0], "material": "m",
+                 "transform": [{ "shear": 1 }] } ] }"#,
+        );
+        assert_eq!(path, "objects[0].transform[0].shear");
+    }
+
+    #[test]
+    fn moving_objects_should_load() {
+        let (world, options) = parse(
+            r#"{ "camera": { "shutter": [0, 0.5] },
+                 "materials": { "m": { "type": "lambertian", "albedo": [0.5, 0.5, 0.5] } },
+                 "objects": [
+                   { "type": "moving_sphere", "center0": [0, 0, 0], "center1": [4, 0, 0], "radius": 1, "material": "m" },
+                   { "type": "sphere", "center": [0, 0, 0], "radius": 1, "material": "m",
+                     "transform": [{ "translate": { "from": [0, 10, 0], "to": [0, 14, 0], "time0": 1, "time1": 2 } },
+                                   { "scale": { "from": 1, "to": 2 } }] } ] }"#,
+        )
+        .unwrap();
+        assert_eq!(options.camera.shutter, (0.0, 0.5));
+        let down = Vec3::new(0.0, -1.0, 0.0);
+        let at = |x: f64, time: f64| world.hit(Ray::with_time(Point::new(x, 100.0, 0.0), down, time), 0.001, f64::INFINITY);
+        // The moving sphere halfway along, and the instance still where it starts until time 1
+        assert!((at(2.0, 0.5).unwrap().point.y - 1.0).abs() < 1e-9);
+        assert!((at(0.0, 0.0).unwrap().point.y - 11.0).abs() < 1e-9);
+        // Halfway up, with everything doubled by the scale after the translation
+        assert!((at(0.0, 1.5).unwrap().point.y - 26.0).abs() < 1e-9);
+
+        let invalid = |text: &str| invalid_at(text).2;
+        assert_eq!(
+            invalid(
+                r#"{ "objects": [ { "type": "moving_sphere", "center0": [0, 0, 0], "center1": [1, 0, 0],
+                                   "time0": 1, "time1": 0, "radius": 1, "material": "m" } ] }"#
+            ),
+            "objects[0]"
+        );
+        assert_eq!(
+            invalid(
+                r#"{ "materials": { "m": { "type": "lambertian", "albedo": [0.5, 0.5, 0.5] } },
+                     "objects": [ { "type": "sphere", "center": [0, 0, 0], "radius": 1, "material": "m",
+                                    "transform": [{ "scale": { "from": 1, "to": -1 } }] } ] }"#
+            ),
+            "objects[0].transform[0].scale"
+        );
+        assert_eq!(invalid(r#"{ "camera": { "shutter": [1, 0] }, "objects": [] }"#), "camera.shutter");
+    }
+
+    #[test]
+    fn heightfields_should_load() {
+        let text = r#"{
+  "materials": { "m": { "type": "lambertian", "albedo": [0.5, 0.5, 0.5] } },
+  "objects": [
+    { "type": "heightfield", "corner": [0, 0, 0], "size": [32, 8, 32], "image": "textures/hills.pgm", "material": "m" },
+    { "type": "heightfield", "corner": [100, 0, 0], "size": [10, 2, 10], "material": "m",
+      "noise": { "seed": 3, "scale": 2, "octaves": 4, "resolution": 17 } }
+  ]
+}"#;
+        let (world, _) = parse_scene(text, "scenes/terrain.json", Options::default()).unwrap();
+        let down = Vec3::new(0.0, -1.0, 0.0);
+        // Pixel (10, 12) at the top of the higher hill is 238 bright
+        let hit = world.hit(Ray::new(Point::new(10.0, 20.0, 12.0), down), 0.001, f64::INFINITY).unwrap();
+        assert!((hit.point.y - 8.0 * 238.0 / 255.0).abs() < 1e-9, "{:?}", hit.point);
+        assert!(hit.normal.y > 0.9);
+        // Noise heights span the full height
+        let heights: Vec<f64> = (0..=20)
+            .flat_map(|i| (0..=20).map(move |j| (i, j)))
+            .filter_map(|(i, j)| {
+                let ray = Ray::new(Point::new(100.0 + i as f64 * 0.5, 5.0, j as f64 * 0.5), down);
+                world.hit(ray, 0.001, f64::INFINITY).map(|hit| hit.point.y)
+            })
+            .collect();
+        assert_eq!(heights.len(), 441);
+        assert!(heights.iter().all(|&y| (-1e-9..=2.0 + 1e-9).contains(&y)));
+        assert!(heights.iter().any(|&y| y > 1.9) && heights.iter().any(|&y| y < 0.1));
+
+        let (_, _, path, _) = invalid_at(
+            r#"{ "materials": { "m": { "type": "lambertian", "albedo": [0.5, 0.5, 0.5] } },
+                 "objects": [ { "type": "heightfield", "corner": [0, 0, 0], "size": [1, 1, 1], "material": "m" } ] }"#,
+        );
+        assert_eq!(path, "objects[0]");
+    }
+
+    #[test]
+    fn every_object_type_should_be_known() {
+        for object_type in OBJECT_TYPES {
+            let text = format!(r#"{{ "objects": [ {{ "type": "{}" }} ] }}"#, object_type);
+            let (_, _, _, message) = invalid_at(&text);
+            assert!(!message.starts_with("unknown object type"), "{}", message);
+        }
+        let (_, _, path, message) = invalid_at(r#"{ "objects": [ { "type": "metaball" } ] }"#);
+        assert_eq!(path, "objects[0].type");
+        assert!(message.contains("metaballs") && message.contains("constant_medium"), "{}", message);
+    }
+
+    #[test]
+    fn curves_should_load() {
+        let text = r#"{
+  "materials": { "m": { "type": "lambertian", "albedo": [0.5, 0.5, 0.5] } },
+  "objects": [
+    { "type": "curve", "points": [[0, 0, 0], [1, 0, 0], [2, 0, 0], [3, 0, 0]], "width": [0.4, 0], "material": "m" },
+    { "type": "grass", "corner": [10, 0, 0], "size": [1, 1], "count": 200, "height": 1, "width": 0.05,
+      "seed": 2, "material": "m" }
+  ]
+}"#;
+        let (world, _) = parse(text).unwrap();
+        let down = Vec3::new(0.0, -1.0, 0.0);
+        let hit = world.hit(Ray::new(Point::new(0.375, 5.0, 0.1), down), 0.001, f64::INFINITY).unwrap();
+        assert!(hit.tangent.is_some());
+        // A round curve, turned towards its side
+        assert!(hit.normal.z > 0.5);
+        assert!(world.hit(Ray::new(Point::new(2.625, 5.0, 0.1), down), 0.001, f64::INFINITY).is_none());
+        // Blades stand among each other, less than a meter tall
+        let across = Ray::new(Point::new(9.0, 0.2, 0.5), Vec3::new(1.0, 0.0, 0.0));
+        assert!(world.hit(across, 0.001, f64::INFINITY).is_some());
+        let above = Ray::new(Point::new(9.0, 1.1, 0.5), Vec3::new(1.0, 0.0, 0.0));
+        assert!(world.hit(above, 0.001, f64::INFINITY).is_none());
+
+        let (_, _, path, _) = invalid_at(
+            r#"{ "materials": { "m": { "type": "lambertian", "albedo": [0.5, 0.5, 0.5] } },
+                 "objects": [ { "type": "curve", "points": [[0, 0, 0], [1, 0, 0], [2, 0, 0]], "width": 1, "material": "m" } ] }"#,
+        );
+        assert_eq!(path, "objects[0].points");
+        let (_, _, path, _) = invalid_at(
+            r#"{ "materials": { "m": { "type": "lambertian", "albedo": [0.5, 0.5, 0.5] } },
+                 "objects": [ { "type": "curve", "points": [[0, 0, 0], [1, 0, 0], [2, 0, 0], [3, 0, 0]],
+                   "width": [0, 0], "material": "m" } ] }"#,
+        );
+        assert_eq!(path, "objects[0].width");
+        let (_, _, path, _) = invalid_at(
+            r#"{ "materials": { "m": { "type": "lambertian", "albedo": [0.5, 0.5, 0.5] } },
+                 "objects": [ { "type": "curve", "points": [[1, 2, 3], [1, 2, 3], [1, 2, 3], [1, 2, 3]],
+                   "width": 1, "material": "m" } ] }"#,
+        );
+        assert_eq!(path, "objects[0].points");
+        let (_, _, path, message) = invalid_at(
+            r#"{ "materials": { "m": { "type": "lambertian", "albedo": [0.5, 0.5, 0.5] } },
+                 "objects": [ { "type": "grass", "corner": [0, 0, 0], "size": [1, 1], "count": 2000000000,
+                   "height": 1, "width": 0.05, "material": "m" } ] }"#,
+        );
+        assert_eq!((path.as_str(), message.as_str()), ("objects[0].count", "must be at most 1000000"));
+    }
+
+    #[test]
+    fn metaballs_should_load() {
+        let text = r#"{
+  "materials": { "glass": { "type": "dielectric", "refractive_index": 1.5 } },
+  "objects": [
+    { "type": "metaballs", "material": "glass", "balls": [
+      { "center": [-1, 0, 0], "radius": 2 }, { "center": [1, 0, 0], "radius": 2 },
+      { "center": [0, 0, 5], "radius": 2, "strength": -1 } ] }
+  ]
+}"#;
+        let (world, _) = parse(text).unwrap();
+        // Joined in the middle, 2 (1 - (1 + y^2) / 4)^3 = 0.5
+        let hit = world.hit(Ray::new(Point::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0)), 0.001, f64::INFINITY).unwrap();
+        assert!((hit.point.y - (3.0 - 4.0 * 0.25f64.cbrt()).sqrt()).abs() < 1e-9);
+
+        let (_, _, path, _) = invalid_at(
+            r#"{ "materials": { "m": { "type": "lambertian", "albedo": [0.5, 0.5, 0.5] } },
+                 "objects": [ { "type": "metaballs", "material": "m",
+                   "balls": [ { "center": [0, 0, 0], "radius": 1, "strength": -1 } ] } ] }"#,
+        );
+        assert_eq!(path, "objects[0].balls");
+        let (_, _, path, _) = invalid_at(
+            r#"{ "materials": { "m": { "type": "lambertian", "albedo": [0.5, 0.5, 0.5] } },
+                 "objects": [ { "type": "metaballs", "material": "m", "threshold": 0,
+                   "balls": [ { "center": [0, 0, 0], "radius": 1 } ] } ] }"#,
+        );
+        assert_eq!(path, "objects[0].threshold");
+    }
+
+    #[test]
+    fn sdf_should_load() {
+        let text = r#"{
+  "materials": { "m": { "type": "lambertian", "albedo": [0.5, 0.5, 0.5] } },
+  "objects": [
+    { "type": "sdf", "material": "m", "shape": {
+      "type": "smooth_subtraction", "smoothness": 0.1,
+      "from": { "type": "repeat", "spacing": [3, 0, 0], "limit": [1, 0, 0],
+                "shape": { "type": "box", "center": [0, 0, 0], "size": [2, 2, 2], "radius": 0.2 } },
+      "cut": { "type": "smooth_union", "shapes": [
+        { "type": "capsule", "from": [0, 0, -2], "to": [0, 0, 2], "radius": 0.5 },
+        { "type": "torus", "center": [0, 0, 0], "major_radius": 5, "minor_radius": 0.2 },
+        { "type": "sphere", "center": [0, 5, 0], "radius": 1 } ] } } }
+  ]
+}"#;
+        let (world, _) = parse(text).unwrap();
+        let down = Vec3::new(0.0, -1.0, 0.0);
+        // Onto the copy on the right, and along the hole through the middle one
+        let hit = world.hit(Ray::new(Point::new(3.0, 5.0, 0.0), down), 0.001, f64::INFINITY).unwrap();
+        assert!((hit.point.y - 1.0).abs() < 1e-6);
+        let along = Ray::new(Point::new(0.0, 0.0, 3.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(world.hit(along, 0.001, f64::INFINITY).is_none());
+        assert!(world.hit(Ray::new(Point::new(0.0, 5.0, 0.0), down), 0.001, f64::INFINITY).is_some());
+        assert!(world.hit(Ray::new(Point::new(6.0, 5.0, 0.0), down), 0.001, f64::INFINITY).is_none());
+
+        let (_, _, path, _) = invalid_at(
+            r#"{ "materials": { "m": { "type": "lambertian", "albedo": [0.5, 0.5, 0.5] } },
+                 "objects": [ { "type": "sdf", "material": "m",
+                   "shape": { "type": "box", "center": [0, 0, 0], "size": [2, 1, 2], "radius": 0.6 } } ] }"#,
+        );
+        assert_eq!(path, "objects[0].shape.radius");
+        let (_, _, path, _) = invalid_at(
+            r#"{ "materials": { "m": { "type": "lambertian", "albedo": [0.5, 0.5, 0.5] } },
+                 "objects": [ { "type": "sdf", "material": "m", "shape": { "type": "smooth_union", "shapes": [
+                   { "type": "sphere", "center": [0, 0, 0], "radius": 1 }, { "type": "blob" } ] } } ] }"#,
+        );
+        assert_eq!(path, "objects[0].shape.shapes[1].type");
+    }
+
+    #[test]
+    fn csg_should_load() {
+        let text = r#"{
+  "materials": { "glass": { "type": "dielectric", "refractive_index": 1.5 } },
+  "objects": [
+    { "type": "csg", "operation": "intersection",
+      "left": { "type": "sphere", "center": [0, 0, 4], "radius": 5, "material": "glass" },
+      "right": { "type": "sphere", "center": [0, 0, -4], "radius": 5, "material": "glass" },
+      "transform": [{ "translate": [0, 0, -10] }] }
+  ]
+}"#;
+        let (world, _) = parse(text).unwrap();
+        let hit = world.hit(Ray::new(Point::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0)), 0.001, f64::INFINITY).unwrap();
+        assert!((hit.t - 9.0).abs() < 1e-9);
+        assert!((hit.normal.z - 1.0).abs() < 1e-9);
+
+        let (_, _, path, _) = invalid_at(
+            r#"{ "objects": [ { "type": "csg", "operation": "xor",
+                               "left": { "type": "sphere", "center": [0, 0, 0], "radius": 1, "material": "m" },
+                               "right": { "type": "sphere", "center": [0, 0, 0], "radius": 1, "material": "m" } } ] }"#,
+        );
+        assert_eq!(path, "objects[0].operation");
+    }
+
+    #[test]
+    fn constant_medium_should_load() {
+        let text = r#"{
+  "materials": { "smoke": { "type": "isotropic", "albedo": [0.2, 0.2, 0.2] } },
+  "objects": [
+    { "type": "constant_medium", "density": 1e9, "material": "smoke",
+      "boundary": { "type": "sphere", "center": [0, 0, 0], "radius": 1, "material": "smoke" },
+      "transform": [{ "translate": [0, 0, -5] }] }
+  ]
+}"#;
+        let (world, _) = parse(text).unwrap();
+        let hit = world.hit(Ray::new(Point::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0)), 0.001, f64::INFINITY).unwrap();
+        assert!((hit.t - 4.0).abs() < 1e-6);
+        assert_eq!(hit.material.get_albedo(&hit), Vec3::new(0.2, 0.2, 0.2));
+
+        let (_, _, path, _) = invalid_at(
+            r#"{ "materials": { "smoke": { "type": "isotropic", "albedo": [0.2, 0.2, 0.2] } },
+                 "objects": [ { "type": "constant_medium", "density": 0, "material": "smoke",
+                                "boundary": { "type": "sphere", "center": [0, 0, 0], "radius": 1, "material": "smoke" } } ] }"#,
+        );
+        assert_eq!(path, "objects[0].density");
+        // The boundary needs no material, but the medium does
+        let boundary = r#"{ "type": "csg", "operation": "union",
+                            "left": { "type": "sphere", "center": [0, 0, 0], "radius": 1 },
+                            "right": { "type": "box", "min": [0, 0, 0], "max": [2, 2, 2] } }"#;
+        let text = format!(
+            r#"{{ "materials": {{ "smoke": {{ "type": "isotropic", "albedo": [0.2, 0.2, 0.2] }} }},
+                 "objects": [ {{ "type": "constant_medium", "density": 1, "material": "smoke", "boundary": {} }} ] }}"#,
+            boundary
+        );
+        assert!(parse(&text).is_ok());
+        let text = format!(r#"{{ "objects": [ {{ "type": "constant_medium", "density": 1, "boundary": {} }} ] }}"#, boundary);
+        let (_, _, path, message) = invalid_at(&text);
+        assert!(message.contains("material"), "{}", message);
+        assert_eq!(path, "objects[0]");
+        // Elsewhere objects still need one
+        let (_, _, path, _) = invalid_at(r#"{ "objects": [ { "type": "sphere", "center": [0, 0, 0], "radius": 1 } ] }"#);
+        assert_eq!(path, "objects[0]");
+    }
+
+    #[test]
+    fn cornell_box_should_load() {
+        let (world, _) = load_scene(Path::new("scenes/cornell.json"), Options::default()).unwrap();
+        // Straight up through the gap between the boxes, onto the light facing down
+        let ray = Ray::new(Point::new(278.0, 1.0, 280.0), Vec3::new(0.0, 1.0, 0.0));
+        let hit = world.hit(ray, 0.001, f64::INFINITY).unwrap();
+        assert_eq!(hit.t, 553.0);
+        assert_eq!(hit.normal, Vec3::new(0.0, -1.0, 0.0));
+        // Down onto the top of the short box
+        let ray = Ray::new(Point::new(200.0, 500.0, 100.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!((world.hit(ray, 0.001, f64::INFINITY).unwrap().point.y - 165.0).abs() < 1e-9);
+
+        let (_, _, path, message) = invalid_at(
+            r#"{ "objects": [ { "type": "rect", "plane": "xw", "min": [0, 0], "max": [1, 1], "offset": 0, "material": "m" } ] }"#,
+        );
+        assert_eq!(path, "objects[0].plane");
+        assert_eq!(message, "unknown plane 'xw', expected xy, xz or yz");
+        let (_, _, path, _) = invalid_at(
+            r#"{ "objects": [ { "type": "box", "min": [0, 0, 0], "max": [1, 0, 1], "material": "m" } ] }"#,
+        );
+        assert_eq!(path, "objects[0].max");
+    }
+
+    #[test]
+    fn textures_should_load() {
+        let text = r#"{
+  "textures": {
+    "tiles": { "type": "checker", "scale": 2, "even": [1, 1, 1], "odd": { "type": "solid", "color": [0, 0, 1] } }
+  },
+  "materials": {
+    "floor": { "type": "lambertian", "albedo": "tiles" },
+    "lamp": { "type": "diffuse_light", "emit": { "type": "checker", "even": [8, 8, 8], "odd": "tiles" } }
+  },
+  "objects": [
+    { "type": "sphere", "center": [0, -1000, 0], "radius": 1000, "material": "floor" },
+    { "type": "sphere", "center": [0, 10, 0], "radius": 1, "material": "lamp" }
+  ]
+}"#;
+        let (world, _) = parse(text).unwrap();
+        let albedo_at = |x: f64| {
+            let ray = Ray::new(Point::new(x, 1.0, 0.5), Vec3::new(0.0, -1.0, 0.0));
+            let hit = world.hit(ray, 0.001, f64::INFINITY).unwrap();
+            hit.material.get_albedo(&hit)
+        };
+        // The floor sits just below y = 0, in the odd layer of cubes
+        assert_eq!(albedo_at(0.5), Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(albedo_at(2.5), Vec3::new(1.0, 1.0, 1.0));
+
+        let (_, _, path, message) = invalid_at(r#"{ "materials": { "m": { "type": "metal", "albedo": "gold" } }, "objects": [] }"#);
+        assert_eq!(path, "materials.m.albedo");
+        assert_eq!(message, "unknown texture 'gold'");
+        let (_, _, path, _) = invalid_at(
+            r#"{ "textures": { "t": { "type": "image", "file": "missing.png" } }, "objects": [] }"#,
+        );
+        assert_eq!(path, "textures.t.file");
+        let (_, _, path, _) = invalid_at(r#"{ "textures": { "t": { "type": "wood", "octaves": 0 } }, "objects": [] }"#);
+        assert_eq!(path, "textures.t.octaves");
+        assert!(parse(r#"{ "textures": { "t": { "type": "marble", "seed": 3, "scale": 4 } }, "objects": [] }"#).is_ok());
+    }
+
+    #[test]
+    fn syntax_errors_should_name_the_file() {
+        let error = parse("{ \"objects\": [ }").err().unwrap();
+        assert!(matches!(error, SceneError::Syntax { .. }));
+        assert!(error.to_string().starts_with("test.json:1:16:"));
+    }
+}
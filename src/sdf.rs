@@ -0,0 +1,500 @@
+// Signed distance fields, rendered by sphere tracing
+//
+// A signed distance field gives, for any point, the distance to the nearest point of a
+// surface, negative inside it. Marching along a ray by that distance can never step through
+// the surface, so shapes only need a distance function, not a ray intersection. Combinations
+// and repetitions that no analytic intersector handles come almost for free.
+
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::point::Point;
+use crate::ray::Ray;
+use crate::utils;
+use crate::vec3::Vec3;
+
+// Steps along a ray before giving up, rays grazing a surface take the most
+const MAX_STEPS: usize = 1000;
+
+// Distance from the surface counted as on it
+const SURFACE_DISTANCE: f64 = 1e-6;
+
+// Smallest step, so rays starting on a surface leave it
+const MIN_STEP: f64 = 1e-5;
+
+// How far rays march through fields that extend forever
+const MAX_DISTANCE: f64 = 1000.0;
+
+// Offset for gradients by central differences
+const GRADIENT_STEP: f64 = 1e-6;
+
+pub trait Sdf: Send + Sync {
+    // Signed distance from p to the surface, negative inside
+    // It may underestimate the distance away from the surface but never overestimate it,
+    // or marching would step through the surface.
+    fn distance(&self, p: Point) -> f64;
+
+    // Direction of increasing distance, which at the surface is the outward normal
+    // Not necessarily of unit length. By default estimated by central differences.
+    fn gradient(&self, p: Point) -> Vec3 {
+        let difference = |offset: Vec3| self.distance(p + offset) - self.distance(p - offset);
+        Vec3::new(
+            difference(Vec3::new(GRADIENT_STEP, 0.0, 0.0)),
+            difference(Vec3::new(0.0, GRADIENT_STEP, 0.0)),
+            difference(Vec3::new(0.0, 0.0, GRADIENT_STEP)),
+        )
+    }
+
+    // Box around the surface, None if it goes on forever
+    fn bounds(&self) -> Option<Aabb>;
+}
+
+fn map(v: Vec3, f: impl Fn(f64) -> f64) -> Vec3 {
+    Vec3::new(f(v.x), f(v.y), f(v.z))
+}
+
+fn around(center: Point, half_size: Vec3) -> Aabb {
+    Aabb::new(center - half_size, center + half_size)
+}
+
+pub struct SphereSdf {
+    center: Point,
+    radius: f64,
+}
+
+impl SphereSdf {
+    pub fn new(center: Point, radius: f64) -> Self {
+        SphereSdf { center, radius }
+    }
+}
+
+impl Sdf for SphereSdf {
+    fn distance(&self, p: Point) -> f64 {
+        (p - self.center).length() - self.radius
+    }
+
+    fn gradient(&self, p: Point) -> Vec3 {
+        p - self.center
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(around(self.center, Vec3::new(self.radius, self.radius, self.radius)))
+    }
+}
+
+// Axis-aligned box, with its edges rounded off by radius (0 for sharp edges)
+pub struct BoxSdf {
+    center: Point,
+    half_size: Vec3,
+    radius: f64,
+}
+
+impl BoxSdf {
+    // The same overall size as the sharp box, radius at most half its smallest side
+    pub fn new(center: Point, size: Vec3, radius: f64) -> Self {
+        BoxSdf { center, half_size: size / 2.0, radius }
+    }
+}
+
+impl Sdf for BoxSdf {
+    // Outside, the distance to the nearest point of the box shrunk by radius, less radius
+    // Inside, the distance to the nearest face.
+    fn distance(&self, p: Point) -> f64 {
+        let shrunk = map(self.half_size, |half| half - self.radius);
+        let q = map(p - self.center, f64::abs) - shrunk;
+        let outside = map(q, |value| value.max(0.0)).length();
+        let inside = q.x.max(q.y).max(q.z).min(0.0);
+        outside + inside - self.radius
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(around(self.center, self.half_size))
+    }
+}
+
+// Ring around the y axis, like the analytic torus
+pub struct TorusSdf {
+    center: Point,
+    major_radius: f64,
+    minor_radius: f64,
+}
+
+impl TorusSdf {
+    pub fn new(center: Point, major_radius: f64, minor_radius: f64) -> Self {
+        TorusSdf { center, major_radius, minor_radius }
+    }
+
+    // Nearest point of the central circle
+    fn ring(&self, p: Vec3) -> Vec3 {
+        let around = Vec3::new(p.x, 0.0, p.z);
+        let distance = around.length();
+        if distance == 0.0 {
+            return Vec3::new(self.major_radius, 0.0, 0.0);
+        }
+        self.major_radius / distance * around
+    }
+}
+
+impl Sdf for TorusSdf {
+    fn distance(&self, p: Point) -> f64 {
+        let local = p - self.center;
+        (local - self.ring(local)).length() - self.minor_radius
+    }
+
+    fn gradient(&self, p: Point) -> Vec3 {
+        let local = p - self.center;
+        local - self.ring(local)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let reach = self.major_radius + self.minor_radius;
+        Some(around(self.center, Vec3::new(reach, self.minor_radius, reach)))
+    }
+}
+
+// Every point within radius of the segment from a to b
+pub struct CapsuleSdf {
+    a: Point,
+    b: Point,
+    radius: f64,
+}
+
+impl CapsuleSdf {
+    pub fn new(a: Point, b: Point, radius: f64) -> Self {
+        CapsuleSdf { a, b, radius }
+    }
+
+    fn nearest(&self, p: Point) -> Point {
+        let ab = self.b - self.a;
+        let length_squared = ab.length_squared();
+        if length_squared == 0.0 {
+            return self.a;
+        }
+        let h = utils::clamp((p - self.a).dot(ab) / length_squared, 0.0, 1.0);
+        self.a + h * ab
+    }
+}
+
+impl Sdf for CapsuleSdf {
+    fn distance(&self, p: Point) -> f64 {
+        (p - self.nearest(p)).length() - self.radius
+    }
+
+    fn gradient(&self, p: Point) -> Vec3 {
+        p - self.nearest(p)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let r = Vec3::new(self.radius, self.radius, self.radius);
+        Some(Aabb::surrounding_box(around(self.a, r), around(self.b, r)))
+    }
+}
+
+// Both shapes, blended together where they come within about smoothness of each other
+// A smoothness of 0 is the plain union.
+pub struct SmoothUnion {
+    a: Arc<dyn Sdf>,
+    b: Arc<dyn Sdf>,
+    smoothness: f64,
+}
+
+impl SmoothUnion {
+    pub fn new(a: Arc<dyn Sdf>, b: Arc<dyn Sdf>, smoothness: f64) -> Self {
+        SmoothUnion { a, b, smoothness }
+    }
+}
+
+// Polynomial smooth minimum, at most smoothness / 4 below the minimum
+fn smooth_min(a: f64, b: f64, smoothness: f64) -> f64 {
+    if smoothness <= 0.0 {
+        return a.min(b);
+    }
+    let h = utils::clamp(0.5 + 0.5 * (b - a) / smoothness, 0.0, 1.0);
+    b + (a - b) * h - smoothness * h * (1.0 - h)
+}
+
+impl Sdf for SmoothUnion {
+    fn distance(&self, p: Point) -> f64 {
+        smooth_min(self.a.distance(p), self.b.distance(p), self.smoothness)
+    }
+
+    // The blend swells out by up to smoothness / 4
+    fn bounds(&self) -> Option<Aabb> {
+        let bbox = Aabb::surrounding_box(self.a.bounds()?, self.b.bounds()?);
+        let swell = self.smoothness / 4.0;
+        Some(around(
+            (bbox.minimum + bbox.maximum) / 2.0,
+            (bbox.maximum - bbox.minimum) / 2.0 + Vec3::new(swell, swell, swell),
+        ))
+    }
+}
+
+// The first shape with the second carved out of it, the edges of the cut rounded by smoothness
+pub struct SmoothSubtraction {
+    from: Arc<dyn Sdf>,
+    cut: Arc<dyn Sdf>,
+    smoothness: f64,
+}
+
+impl SmoothSubtraction {
+    pub fn new(from: Arc<dyn Sdf>, cut: Arc<dyn Sdf>, smoothness: f64) -> Self {
+        SmoothSubtraction { from, cut, smoothness }
+    }
+}
+
+impl Sdf for SmoothSubtraction {
+    // Inside from and outside cut: the smooth maximum of the two, -smooth_min(-a, -b)
+    fn distance(&self, p: Point) -> f64 {
+        -smooth_min(-self.from.distance(p), self.cut.distance(p), self.smoothness)
+    }
+
+    // Never larger than what it is cut from
+    fn bounds(&self) -> Option<Aabb> {
+        self.from.bounds()
+    }
+}
+
+// Copies of a shape every spacing along each axis with a non-zero spacing
+// With a limit, only that many copies each side of the original along each axis, otherwise
+// copies go on forever. The shape should fit within its cell around the origin, half the
+// spacing each way, for the distance to hold.
+pub struct Repeat {
+    shape: Arc<dyn Sdf>,
+    spacing: Vec3,
+    limit: Option<Vec3>,
+}
+
+impl Repeat {
+    pub fn new(shape: Arc<dyn Sdf>, spacing: Vec3, limit: Option<Vec3>) -> Self {
+        Repeat { shape, spacing, limit }
+    }
+
+    // The point brought back into the original's cell
+    fn fold(&self, p: Point) -> Point {
+        let mut folded = p;
+        for axis in 0..3 {
+            let spacing = self.spacing[axis];
+            if spacing == 0.0 {
+                continue;
+            }
+            let mut cell = (p[axis] / spacing).round();
+            if let Some(limit) = self.limit {
+                cell = utils::clamp(cell, -limit[axis], limit[axis]);
+            }
+            folded[axis] = p[axis] - spacing * cell;
+        }
+        folded
+    }
+}
+
+impl Sdf for Repeat {
+    fn distance(&self, p: Point) -> f64 {
+        self.shape.distance(self.fold(p))
+    }
+
+    fn gradient(&self, p: Point) -> Vec3 {
+        self.shape.gradient(self.fold(p))
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let bbox = self.shape.bounds()?;
+        let spread = map(self.spacing, f64::abs);
+        let reach = match self.limit {
+            Some(limit) => Vec3::new(spread.x * limit.x, spread.y * limit.y, spread.z * limit.z),
+            None if spread.length_squared() == 0.0 => Vec3::new(0.0, 0.0, 0.0),
+            None => return None,
+        };
+        Some(Aabb::new(bbox.minimum - reach, bbox.maximum + reach))
+    }
+}
+
+// An object whose surface is where a signed distance field is 0
+pub struct SdfHittable {
+    sdf: Arc<dyn Sdf>,
+    bbox: Option<Aabb>,
+    material: Arc<dyn Material>,
+}
+
+impl SdfHittable {
+    pub fn new(sdf: Arc<dyn Sdf>, material: Arc<dyn Material>) -> Self {
+        // Padded, so marching starts a little off surfaces lying on the box
+        let bbox = sdf.bounds().map(|bbox| {
+            let pad = Vec3::new(1e-4, 1e-4, 1e-4);
+            Aabb::new(bbox.minimum - pad, bbox.maximum + pad)
+        });
+        SdfHittable { sdf, bbox, material }
+    }
+
+    // Narrows a crossing of the surface between t0 and t1 down by bisection
+    fn bisect(&self, ray: Ray, mut t0: f64, mut t1: f64, side: f64) -> f64 {
+        for _ in 0..60 {
+            let middle = 0.5 * (t0 + t1);
+            if self.sdf.distance(ray.at(middle)) * side > 0.0 {
+                t0 = middle;
+            } else {
+                t1 = middle;
+            }
+        }
+        t1
+    }
+}
+
+impl Hittable for SdfHittable {
+    // Sphere tracing: step along the ray by the distance to the surface, which is always safe
+    // The ray hits where the distance changes sign, or comes within SURFACE_DISTANCE of 0
+    // on the way towards the surface. A ray may start inside, as rays refracted into a
+    // transparent shape do, in which case it marches on the absolute distance to the way out.
+    fn hit(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let speed = ray.direction.length();
+        let (mut t, end) = match self.bbox {
+            Some(bbox) => bbox.hit_range(ray, t_min, t_max)?,
+            None => (t_min, t_max.min(MAX_DISTANCE / speed)),
+        };
+        let mut distance = self.sdf.distance(ray.at(t));
+        // On the surface, the ray is on the side it is heading towards
+        let side = if distance.abs() < SURFACE_DISTANCE {
+            -self.sdf.gradient(ray.at(t)).dot(ray.direction).signum()
+        } else {
+            distance.signum()
+        };
+
+        for _ in 0..MAX_STEPS {
+            let next = t + distance.abs().max(MIN_STEP) / speed;
+            if next >= end {
+                return None;
+            }
+            let next_distance = self.sdf.distance(ray.at(next));
+            let hit_t = if next_distance * side < 0.0 {
+                Some(self.bisect(ray, t, next, side))
+            } else if next_distance.abs() < SURFACE_DISTANCE && next_distance.abs() < distance.abs() {
+                Some(next)
+            } else {
+                None
+            };
+            if let Some(hit_t) = hit_t {
+                if hit_t <= t_min {
+                    return None;
+                }
+                let normal = self.sdf.gradient(ray.at(hit_t)).unit_vector();
+                return Some(HitRecord::new(ray, hit_t, normal, (0.0, 0.0), self.material.clone()));
+            }
+            t = next;
+            distance = next_distance;
+        }
+        None
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bbox
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::Lambertian;
+
+    fn hittable(sdf: impl Sdf + 'static) -> SdfHittable {
+        SdfHittable::new(Arc::new(sdf), Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))))
+    }
+
+    fn assert_close(actual: Vec3, expected: Vec3, tolerance: f64) {
+        assert!((actual - expected).length() < tolerance, "{:?} != {:?}", actual, expected);
+    }
+
+    #[test]
+    fn distances_should_match_the_shapes() {
+        let cube = BoxSdf::new(Point::new(0.0, 0.0, 0.0), Vec3::new(2.0, 2.0, 2.0), 0.0);
+        assert_eq!(cube.distance(Point::new(3.0, 0.0, 0.0)), 2.0);
+        assert_eq!(cube.distance(Point::new(0.5, 0.0, 0.0)), -0.5);
+        // Diagonally out from a corner
+        assert!((cube.distance(Point::new(2.0, 2.0, 1.0)) - 2f64.sqrt()).abs() < 1e-12);
+        let rounded = BoxSdf::new(Point::new(0.0, 0.0, 0.0), Vec3::new(2.0, 2.0, 2.0), 0.5);
+        assert_eq!(rounded.distance(Point::new(3.0, 0.0, 0.0)), 2.0);
+        // The rounded corner is cut back from the sharp one
+        let corner = rounded.distance(Point::new(1.0, 1.0, 1.0));
+        assert!((corner - (0.75f64.sqrt() - 0.5)).abs() < 1e-12);
+
+        let torus = TorusSdf::new(Point::new(0.0, 0.0, 0.0), 2.0, 0.5);
+        assert_eq!(torus.distance(Point::new(0.0, 0.0, 0.0)), 1.5);
+        assert_eq!(torus.distance(Point::new(0.0, 0.0, -2.0)), -0.5);
+        let capsule = CapsuleSdf::new(Point::new(0.0, 0.0, 0.0), Point::new(0.0, 2.0, 0.0), 0.5);
+        assert_eq!(capsule.distance(Point::new(1.0, 1.0, 0.0)), 0.5);
+        assert_eq!(capsule.distance(Point::new(0.0, 4.0, 0.0)), 1.5);
+    }
+
+    #[test]
+    fn combinations_should_blend() {
+        let a: Arc<dyn Sdf> = Arc::new(SphereSdf::new(Point::new(-1.0, 0.0, 0.0), 1.0));
+        let b: Arc<dyn Sdf> = Arc::new(SphereSdf::new(Point::new(1.0, 0.0, 0.0), 1.0));
+        // Sharp union, and the smooth one filling in the crease where the spheres touch
+        let sharp = SmoothUnion::new(a.clone(), b.clone(), 0.0);
+        let smooth = SmoothUnion::new(a.clone(), b.clone(), 0.5);
+        let crease = Point::new(0.0, 0.3, 0.0);
+        assert!(sharp.distance(crease) > 0.0);
+        assert!(smooth.distance(crease) < 0.0);
+        // Far from the join they agree
+        assert!((smooth.distance(Point::new(-3.0, 0.0, 0.0)) - 1.0).abs() < 1e-12);
+
+        let cut: Arc<dyn Sdf> = Arc::new(SphereSdf::new(Point::new(0.0, 0.0, 0.0), 0.5));
+        let bite = SmoothSubtraction::new(a.clone(), cut, 0.0);
+        assert!(bite.distance(Point::new(-0.1, 0.0, 0.0)) > 0.0);
+        assert!(bite.distance(Point::new(-1.5, 0.0, 0.0)) < 0.0);
+        assert_eq!(bite.bounds(), a.bounds());
+    }
+
+    #[test]
+    fn repetition_should_copy_within_limits() {
+        let ball: Arc<dyn Sdf> = Arc::new(SphereSdf::new(Point::new(0.0, 0.0, 0.0), 0.5));
+        let row = Repeat::new(ball.clone(), Vec3::new(2.0, 0.0, 0.0), Some(Vec3::new(2.0, 0.0, 0.0)));
+        assert_eq!(row.distance(Point::new(4.0, 0.0, 0.0)), -0.5);
+        assert_eq!(row.distance(Point::new(4.0, 1.0, 0.0)), 0.5);
+        // Past the last copy, distances are from the last copy
+        assert_eq!(row.distance(Point::new(8.0, 0.0, 0.0)), 3.5);
+        let bbox = row.bounds().unwrap();
+        assert_eq!((bbox.minimum.x, bbox.maximum.x), (-4.5, 4.5));
+        let forever = Repeat::new(ball, Vec3::new(2.0, 0.0, 2.0), None);
+        assert_eq!(forever.distance(Point::new(-100.0, 0.0, 40.0)), -0.5);
+        assert!(forever.bounds().is_none());
+    }
+
+    #[test]
+    fn marching_should_find_surfaces() {
+        let cube = hittable(BoxSdf::new(Point::new(0.0, 0.0, 0.0), Vec3::new(2.0, 2.0, 2.0), 0.0));
+        let ray = Ray::new(Point::new(-5.0, 0.2, 0.3), Vec3::new(2.0, 0.0, 0.0));
+        let hit = cube.hit(ray, 0.001, f64::INFINITY).unwrap();
+        assert!((hit.t - 2.0).abs() < 1e-6);
+        assert_close(hit.normal, Vec3::new(-1.0, 0.0, 0.0), 1e-6);
+        assert!(hit.front_face);
+        assert!(cube.hit(ray, 0.001, 1.9).is_none());
+        // From inside, out through the far side, as after refraction
+        let hit = cube.hit(Ray::new(hit.point, ray.direction), 0.001, f64::INFINITY).unwrap();
+        assert!((hit.point.x - 1.0).abs() < 1e-6);
+        assert!(!hit.front_face);
+        // Passing beside it
+        assert!(cube.hit(Ray::new(Point::new(-5.0, 1.1, 0.0), Vec3::new(1.0, 0.0, 0.0)), 0.001, f64::INFINITY).is_none());
+
+        let torus = hittable(TorusSdf::new(Point::new(0.0, 0.0, 0.0), 2.0, 0.5));
+        let down = Vec3::new(0.0, -1.0, 0.0);
+        let hit = torus.hit(Ray::new(Point::new(0.0, 5.0, 2.0), down), 0.001, f64::INFINITY).unwrap();
+        assert!((hit.t - 4.5).abs() < 1e-6);
+        assert_close(hit.normal, Vec3::new(0.0, 1.0, 0.0), 1e-6);
+        assert!(torus.hit(Ray::new(Point::new(0.0, 5.0, 0.0), down), 0.001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn marching_should_reach_far_copies() {
+        let ball: Arc<dyn Sdf> = Arc::new(SphereSdf::new(Point::new(0.0, 0.0, 0.0), 0.5));
+        let field = hittable(Repeat::new(ball, Vec3::new(2.0, 0.0, 2.0), None));
+        assert!(field.bounding_box().is_none());
+        // Down onto the copy at (40, 0, -60)
+        let hit = field.hit(Ray::new(Point::new(40.0, 10.0, -60.0), Vec3::new(0.0, -1.0, 0.0)), 0.001, f64::INFINITY).unwrap();
+        assert!((hit.t - 9.5).abs() < 1e-6);
+        // Between the rows, missing them all
+        let between = Ray::new(Point::new(1.0, 0.0, 1.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(field.hit(between, 0.001, f64::INFINITY).is_none());
+    }
+}
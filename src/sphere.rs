@@ -1,39 +1,126 @@
+use crate::aabb::Aabb;
 use crate::point::Point;
 use crate::ray::Ray;
 use crate::utils;
-use crate::hittable::Hittable;
+use crate::hittable::{HitRecord, Hittable, Interval};
 use crate::vec3::Vec3;
 use crate::material::Material;
 
-use std::rc::Rc;
+use std::sync::Arc;
 
+#[derive(Clone)]
 pub struct Sphere
 {
     center: Point,
     radius: f64,
-    material: Rc<dyn Material>,
+    material: Arc<dyn Material>,
 }
 
 impl Sphere {
-    pub fn new(center: Point, radius: f64, material: Rc<dyn Material>) -> Self {
+    pub fn new(center: Point, radius: f64, material: Arc<dyn Material>) -> Self {
         Sphere { center, radius, material }
     }
     pub fn outward_normal(&self, ray: Ray, t: f64) -> Vec3 {
         (ray.at(t) - self.center).unit_vector()
     }
+
+    fn hit_at(&self, ray: Ray, t: f64) -> HitRecord {
+        let outward_normal = self.outward_normal(ray, t);
+        let mut hit = HitRecord::new(ray, t, outward_normal, sphere_uv(outward_normal), self.material.clone());
+        hit.tangent = Some(sphere_tangent(outward_normal));
+        hit
+    }
+}
+
+// Spherical coordinates of a point on the unit sphere
+// u is the angle around the y axis: -x (u = 0), +z (0.25), +x (0.5), -z (0.75).
+// v is the angle from the south pole (-y, v = 0) to the north pole (+y, v = 1),
+// so an equirectangular map (e.g. of the earth) wraps the sphere upright.
+//
+// With theta = pi * v and phi = 2 * pi * u the point is
+// (-cos(phi) sin(theta), -cos(theta), sin(phi) sin(theta))
+pub fn sphere_uv(p: Vec3) -> (f64, f64) {
+    let theta = utils::clamp(-p.y, -1.0, 1.0).acos();
+    let phi = (-p.z).atan2(p.x) + std::f64::consts::PI;
+    (phi / (2.0 * std::f64::consts::PI), theta / std::f64::consts::PI)
+}
+
+// Direction of increasing u at p on the unit sphere, the derivative of the point
+// with respect to phi: (sin(phi) sin(theta), 0, cos(phi) sin(theta)) = (z, 0, -x)
+// It vanishes at the poles, where any direction in the tangent plane will do.
+pub fn sphere_tangent(p: Vec3) -> Vec3 {
+    let tangent = Vec3::new(p.z, 0.0, -p.x);
+    if tangent.length_squared() < 1e-24 {
+        return Vec3::new(1.0, 0.0, 0.0);
+    }
+    tangent.unit_vector()
 }
 
 impl Hittable for Sphere
 {
-    // Return the t value, normal
-    fn hit(&self, ray: Ray) -> Vec<(f64, Vec3, Rc<dyn Material>)> {
-        match hit_sphere(self.center, self.radius, ray) {
-            None => vec![],
-            Some ((root1, root2)) => vec![
-                (root1, *&self.outward_normal(ray, root1), self.material.clone()),
-                (root2, *&self.outward_normal(ray, root2), self.material.clone()),
-            ],
+    fn hit(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let t = hit_sphere(self.center, self.radius, ray, t_min, t_max)?;
+        Some(self.hit_at(ray, t))
+    }
+
+    // Between the two roots, even those behind the ray that hit leaves out
+    fn intervals(&self, ray: Ray, t_min: f64, t_max: f64) -> Vec<Interval> {
+        match sphere_roots(self.center, self.radius, ray) {
+            Some((enter, exit)) if enter < t_max && exit > t_min && enter < exit => vec![Interval {
+                enter: if enter > t_min { Some(self.hit_at(ray, enter)) } else { None },
+                exit: if exit < t_max { Some(self.hit_at(ray, exit)) } else { None },
+            }],
+            _ => Vec::new(),
+        }
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let radius_vector = Vec3::new(self.radius, self.radius, self.radius);
+        Some(Aabb::new(self.center - radius_vector, self.center + radius_vector))
+    }
+}
+
+// Sphere moving in a straight line from center0 at time0 to center1 at time1,
+// resting at either end outside that interval
+pub struct MovingSphere {
+    center0: Point,
+    center1: Point,
+    time0: f64,
+    time1: f64,
+    radius: f64,
+    material: Arc<dyn Material>,
+}
+
+impl MovingSphere {
+    pub fn new(center0: Point, center1: Point, time0: f64, time1: f64, radius: f64, material: Arc<dyn Material>) -> Self {
+        MovingSphere { center0, center1, time0, time1, radius, material }
+    }
+
+    pub fn center(&self, time: f64) -> Point {
+        if self.time1 <= self.time0 {
+            return self.center0;
         }
+        let fraction = utils::clamp((time - self.time0) / (self.time1 - self.time0), 0.0, 1.0);
+        self.center0 + fraction * (self.center1 - self.center0)
+    }
+}
+
+impl Hittable for MovingSphere {
+    fn hit(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let center = self.center(ray.time);
+        let t = hit_sphere(center, self.radius, ray, t_min, t_max)?;
+        let outward_normal = (ray.at(t) - center).unit_vector();
+        let mut hit = HitRecord::new(ray, t, outward_normal, sphere_uv(outward_normal), self.material.clone());
+        hit.tangent = Some(sphere_tangent(outward_normal));
+        Some(hit)
+    }
+
+    // Everywhere along the path
+    fn bounding_box(&self) -> Option<Aabb> {
+        let radius_vector = Vec3::new(self.radius, self.radius, self.radius);
+        let start = Aabb::new(self.center0 - radius_vector, self.center0 + radius_vector);
+        let end = Aabb::new(self.center1 - radius_vector, self.center1 + radius_vector);
+        Some(Aabb::surrounding_box(start, end))
     }
 }
 
@@ -69,23 +156,27 @@ impl Hittable for Sphere
 // Let c = ((CO · CO) -  r ^ 2)
 // Solve: a * t ^ 2 + b * t + c = 0
 //
-// Return t values if any
-pub fn hit_sphere(center: Point, radius: f64, ray: Ray) -> Option<(f64, f64)> {
-    let Ray { origin, direction } = ray;
+// Return t values if any, nearest first
+fn sphere_roots(center: Point, radius: f64, ray: Ray) -> Option<(f64, f64)> {
+    let Ray { origin, direction, .. } = ray;
     let co = origin - center;
     let a = direction.dot(direction);
     let b = 2.0 * (co.dot(direction));
     let c = co.dot(co) - radius * radius;
-    let (root1, root2) = utils::quadratic_solver(a, b, c)?;
-    if root1 < 0.001 || root2 < 0.001 {
-        return None;
-    }
-    Some((root1, root2))
+    utils::quadratic_solver(a, b, c)
+}
+
+// The nearest root within range, roots behind t_min (e.g. behind a ray starting inside) are skipped
+pub fn hit_sphere(center: Point, radius: f64, ray: Ray, t_min: f64, t_max: f64) -> Option<f64> {
+    let (root1, root2) = sphere_roots(center, radius, ray)?;
+    [root1, root2].iter().copied().find(|&t| t > t_min && t < t_max)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::color::Color;
+    use crate::material::Lambertian;
 
 
     #[test]
@@ -95,7 +186,7 @@ mod tests {
         let ray_origin = Point { x: 0.0, y: 0.0, z: 0.0};
         let ray_direction = center - ray_origin;
         let ray = Ray::new(ray_origin, ray_direction);
-        let hits = hit_sphere(center, radius, ray);
+        let hits = hit_sphere(center, radius, ray, 0.001, f64::INFINITY);
         assert!(hits.is_some());
     }
 
@@ -107,7 +198,124 @@ mod tests {
         let reflected_center = Point { x: 0.0, y: 100.5, z: -1.0 };
         let ray_direction = reflected_center - ray_origin;
         let ray = Ray::new(ray_origin, ray_direction);
-        let hits = hit_sphere(center, radius, ray);
+        let hits = hit_sphere(center, radius, ray, 0.001, f64::INFINITY);
         assert!(hits.is_none());
     }
+
+    #[test]
+    fn ray_from_inside_should_hit_sphere() {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let sphere = Sphere::new(Point::new(0.0, 0.0, -1.0), 0.5, material);
+        let ray = Ray::new(Point::new(0.0, 0.0, -1.0), Vec3::new(0.0, 0.0, -1.0));
+        let hit = sphere.hit(ray, 0.001, f64::INFINITY).unwrap();
+        assert_eq!(hit.t, 0.5);
+        assert_eq!(hit.point, Point::new(0.0, 0.0, -1.5));
+        assert_eq!(hit.normal, Vec3::new(0.0, 0.0, -1.0));
+        assert!(!hit.front_face);
+    }
+
+    #[test]
+    fn roots_should_be_filtered_by_the_callers_range() {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let sphere = Sphere::new(Point::new(0.0, 0.0, -1.0), 0.5, material.clone());
+        // Entering 0.0005 ahead, which only a t_min below that keeps
+        let ray = Ray::new(Point::new(0.0, 0.0, -0.4995), Vec3::new(0.0, 0.0, -1.0));
+        assert!((sphere.hit(ray, 0.0, f64::INFINITY).unwrap().t - 0.0005).abs() < 1e-12);
+        assert!((sphere.hit(ray, 0.001, f64::INFINITY).unwrap().t - 1.0005).abs() < 1e-12);
+        assert!(sphere.hit(ray, 0.001, 1.0).is_none());
+        let moving = MovingSphere::new(Point::new(0.0, 0.0, -1.0), Point::new(0.0, 0.0, -1.0), 0.0, 1.0, 0.5, material);
+        assert!((moving.hit(ray, 0.0, f64::INFINITY).unwrap().t - 0.0005).abs() < 1e-12);
+    }
+
+    #[test]
+    fn uv_should_match_known_points() {
+        let close = |(u, v): (f64, f64), (expected_u, expected_v): (f64, f64)| {
+            (u - expected_u).abs() < 1e-12 && (v - expected_v).abs() < 1e-12
+        };
+        assert!(close(sphere_uv(Vec3::new(-1.0, 0.0, 0.0)), (0.0, 0.5)));
+        assert!(close(sphere_uv(Vec3::new(0.0, 0.0, 1.0)), (0.25, 0.5)));
+        assert!(close(sphere_uv(Vec3::new(1.0, 0.0, 0.0)), (0.5, 0.5)));
+        assert!(close(sphere_uv(Vec3::new(0.0, 0.0, -1.0)), (0.75, 0.5)));
+        assert_eq!(sphere_uv(Vec3::new(0.0, 1.0, 0.0)).1, 1.0);
+        assert_eq!(sphere_uv(Vec3::new(0.0, -1.0, 0.0)).1, 0.0);
+        // 45 degrees north of +x
+        let half = std::f64::consts::FRAC_1_SQRT_2;
+        assert!(close(sphere_uv(Vec3::new(half, half, 0.0)), (0.5, 0.75)));
+    }
+
+    #[test]
+    fn tangent_should_follow_increasing_u() {
+        let half = std::f64::consts::FRAC_1_SQRT_2;
+        for p in [Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0), Vec3::new(half, half, 0.0), Vec3::new(-0.6, 0.0, -0.8)].iter() {
+            let tangent = sphere_tangent(*p);
+            assert!((tangent.length() - 1.0).abs() < 1e-12);
+            assert!(tangent.dot(*p).abs() < 1e-12);
+            // Rotating p a little about the y axis in the direction of the tangent increases u
+            let moved = (*p + 1e-6 * tangent).unit_vector();
+            assert!(sphere_uv(moved).0 > sphere_uv(*p).0);
+        }
+        assert_eq!(sphere_tangent(Vec3::new(1.0, 0.0, 0.0)), Vec3::new(0.0, 0.0, -1.0));
+        // Still a unit vector perpendicular to the normal at the poles
+        assert_eq!(sphere_tangent(Vec3::new(0.0, 1.0, 0.0)), Vec3::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn hit_should_have_uv_and_tangent() {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let sphere = Sphere::new(Point::new(0.0, 0.0, -3.0), 2.0, material);
+        // Hits the point of the sphere nearest the origin, its +z side
+        let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let hit = sphere.hit(ray, 0.001, f64::INFINITY).unwrap();
+        assert!((hit.u - 0.25).abs() < 1e-12 && (hit.v - 0.5).abs() < 1e-12);
+        assert_eq!(hit.tangent, Some(Vec3::new(1.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn ray_from_outside_should_hit_near_side() {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let sphere = Sphere::new(Point::new(0.0, 0.0, -1.0), 0.5, material);
+        let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let hit = sphere.hit(ray, 0.001, f64::INFINITY).unwrap();
+        assert_eq!(hit.t, 0.5);
+        assert_eq!(hit.normal, Vec3::new(0.0, 0.0, 1.0));
+        assert!(hit.front_face);
+        assert!(sphere.hit(ray, 0.001, 0.4).is_none());
+    }
+
+    #[test]
+    fn intervals_should_span_the_inside() {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let sphere = Sphere::new(Point::new(0.0, 0.0, -3.0), 1.0, material);
+        let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let intervals = sphere.intervals(ray, 0.001, f64::INFINITY);
+        assert_eq!(intervals.len(), 1);
+        let enter = intervals[0].enter.as_ref().unwrap();
+        let exit = intervals[0].exit.as_ref().unwrap();
+        assert_eq!((enter.t, exit.t), (2.0, 4.0));
+        assert!(enter.front_face && !exit.front_face);
+        // Starting inside, and the whole sphere behind the ray, which hit_sphere skips
+        let inside = sphere.intervals(ray, 3.0, f64::INFINITY);
+        assert!(inside[0].enter.is_none() && inside[0].exit.as_ref().unwrap().t == 4.0);
+        let behind = Ray::new(Point::new(0.0, 0.0, -10.0), Vec3::new(0.0, 0.0, -1.0));
+        assert_eq!(sphere.intervals(behind, f64::NEG_INFINITY, f64::INFINITY).len(), 1);
+        assert!(sphere.intervals(ray, 0.001, 1.0).is_empty());
+    }
+
+    #[test]
+    fn moving_sphere_should_be_hit_where_it_is_at_the_ray_time() {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let sphere = MovingSphere::new(Point::new(0.0, 0.0, -2.0), Point::new(2.0, 0.0, -2.0), 0.0, 1.0, 0.5, material);
+        let direction = Vec3::new(0.0, 0.0, -1.0);
+        let at = |x: f64, time: f64| sphere.hit(Ray::with_time(Point::new(x, 0.0, 0.0), direction, time), 0.001, f64::INFINITY);
+        assert_eq!(at(0.0, 0.0).unwrap().t, 1.5);
+        assert!(at(0.0, 1.0).is_none());
+        assert_eq!(at(1.0, 0.5).unwrap().normal, Vec3::new(0.0, 0.0, 1.0));
+        // Resting at the ends outside the interval
+        assert!(at(2.0, 3.0).is_some());
+        assert_eq!(sphere.center(-1.0), Point::new(0.0, 0.0, -2.0));
+
+        let bbox = sphere.bounding_box().unwrap();
+        assert_eq!(bbox.minimum, Point::new(-0.5, -0.5, -2.5));
+        assert_eq!(bbox.maximum, Point::new(2.5, 0.5, -1.5));
+    }
 }
@@ -0,0 +1,301 @@
+use std::sync::Arc;
+
+use crate::color::Color;
+use crate::noise::Perlin;
+use crate::png::RgbImage;
+use crate::point::Point;
+use crate::utils;
+
+// Color varying over a surface
+pub trait Texture: Send + Sync {
+    // u, v are the surface coordinates of the hit, p the point in space
+    fn value(&self, u: f64, v: f64, p: Point) -> Color;
+}
+
+pub struct SolidColor {
+    color: Color,
+}
+
+impl SolidColor {
+    pub fn new(color: Color) -> Self {
+        SolidColor { color }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _p: Point) -> Color {
+        self.color
+    }
+}
+
+// Cubes of side scale alternating between two textures, filling space
+// so that any surface cutting through gets a checker pattern
+pub struct Checker {
+    even: Arc<dyn Texture>,
+    odd: Arc<dyn Texture>,
+    scale: f64,
+}
+
+impl Checker {
+    pub fn new(even: Arc<dyn Texture>, odd: Arc<dyn Texture>, scale: f64) -> Self {
+        Checker { even, odd, scale }
+    }
+}
+
+impl Texture for Checker {
+    fn value(&self, u: f64, v: f64, p: Point) -> Color {
+        let cell = (p.x / self.scale).floor() + (p.y / self.scale).floor() + (p.z / self.scale).floor();
+        if (cell as i64).rem_euclid(2) == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+/* =========
+ * = NOISE =
+ * =========
+ * Noise textures are defined in space like the checker, and are deterministic for a seed.
+ * For turbulence, scale is the frequency of the noise, larger values give finer detail.
+ * For marble and wood it only sets how close together the bands and rings are; the veins
+ * and irregularities bending them keep features about one unit apart.
+ */
+
+// Color scaled by turbulence, e.g. smoke or clouds
+pub struct TurbulenceTexture {
+    noise: Perlin,
+    color: Color,
+    scale: f64,
+    octaves: u32,
+}
+
+impl TurbulenceTexture {
+    pub fn new(seed: u64, color: Color, scale: f64, octaves: u32) -> Self {
+        TurbulenceTexture { noise: Perlin::new(seed), color, scale, octaves }
+    }
+}
+
+impl Texture for TurbulenceTexture {
+    fn value(&self, _u: f64, _v: f64, p: Point) -> Color {
+        // Turbulence is below 2, keep the color within range
+        let amount = utils::clamp(self.noise.turbulence(self.scale * p, self.octaves), 0.0, 1.0);
+        amount * self.color
+    }
+}
+
+// Bands along z, bent by turbulence into veins
+pub struct Marble {
+    noise: Perlin,
+    light: Color,
+    dark: Color,
+    scale: f64, // Bands per 2 pi units along z
+    octaves: u32,
+}
+
+impl Marble {
+    pub fn new(seed: u64, light: Color, dark: Color, scale: f64, octaves: u32) -> Self {
+        Marble { noise: Perlin::new(seed), light, dark, scale, octaves }
+    }
+}
+
+impl Texture for Marble {
+    fn value(&self, _u: f64, _v: f64, p: Point) -> Color {
+        let phase = self.scale * p.z + 10.0 * self.noise.turbulence(p, self.octaves);
+        let t = 0.5 * (1.0 + phase.sin());
+        mix(self.dark, self.light, t)
+    }
+}
+
+// Growth rings around the y axis, made irregular with fBm
+pub struct Wood {
+    noise: Perlin,
+    light: Color,
+    dark: Color,
+    scale: f64, // Rings per unit of distance from the axis
+    octaves: u32,
+}
+
+impl Wood {
+    pub fn new(seed: u64, light: Color, dark: Color, scale: f64, octaves: u32) -> Self {
+        Wood { noise: Perlin::new(seed), light, dark, scale, octaves }
+    }
+}
+
+impl Texture for Wood {
+    fn value(&self, _u: f64, _v: f64, p: Point) -> Color {
+        let distance = (p.x * p.x + p.z * p.z).sqrt();
+        let rings = self.scale * distance + 0.5 * self.noise.fbm(p, self.octaves, 2.0, 0.5);
+        // Sharp dark edge at the end of each ring, fading back to light across it
+        let t = rings - rings.floor();
+        mix(self.dark, self.light, t.powf(0.5))
+    }
+}
+
+// a at t = 0, b at t = 1
+fn mix(a: Color, b: Color, t: f64) -> Color {
+    (1.0 - t) * a + t * b
+}
+
+// How texture coordinates outside [0, 1] are mapped onto the image
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum WrapMode {
+    Repeat,
+    Clamp,  // Edge pixels extend outwards
+    Mirror, // Every other repetition is flipped
+}
+
+impl WrapMode {
+    // Pixel index for any integer position along an axis of size pixels
+    fn wrap(self, position: i64, size: usize) -> usize {
+        let size = size as i64;
+        let wrapped = match self {
+            WrapMode::Repeat => position.rem_euclid(size),
+            WrapMode::Clamp => position.clamp(0, size - 1),
+            WrapMode::Mirror => {
+                let period = position.rem_euclid(2 * size);
+                if period < size { period } else { 2 * size - 1 - period }
+            }
+        };
+        wrapped as usize
+    }
+}
+
+// Image mapped with u going right and v going up, so (0, 0) is the bottom left corner
+// Pixels are stored in linear light, the same space the renderer works in.
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+    wrap: WrapMode,
+}
+
+impl ImageTexture {
+    pub fn new(image: &RgbImage, wrap: WrapMode) -> Self {
+        // Image files are gamma encoded, undo the gamma 2 the output is written with
+        let pixels = image
+            .pixels
+            .chunks(3)
+            .map(|rgb| {
+                let linear = |byte: u8| (byte as f64 / 255.0).powi(2);
+                Color::new(linear(rgb[0]), linear(rgb[1]), linear(rgb[2]))
+            })
+            .collect();
+        ImageTexture { width: image.width, height: image.height, pixels, wrap }
+    }
+
+    fn pixel(&self, x: i64, y: i64) -> Color {
+        let x = self.wrap.wrap(x, self.width);
+        let y = self.wrap.wrap(y, self.height);
+        self.pixels[y * self.width + x]
+    }
+}
+
+impl Texture for ImageTexture {
+    // Bilinear filtering between the four pixel centers around (u, v)
+    fn value(&self, u: f64, v: f64, _p: Point) -> Color {
+        if !u.is_finite() || !v.is_finite() {
+            return self.pixel(0, 0);
+        }
+        // Rows are stored from the top
+        let x = u * self.width as f64 - 0.5;
+        let y = (1.0 - v) * self.height as f64 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        let top = (1.0 - tx) * self.pixel(x0, y0) + tx * self.pixel(x0 + 1, y0);
+        let bottom = (1.0 - tx) * self.pixel(x0, y0 + 1) + tx * self.pixel(x0 + 1, y0 + 1);
+        (1.0 - ty) * top + ty * bottom
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(r: f64, g: f64, b: f64) -> Arc<dyn Texture> {
+        Arc::new(SolidColor::new(Color::new(r, g, b)))
+    }
+
+    // 2x2 image: black, white / white, black
+    fn image_texture(wrap: WrapMode) -> ImageTexture {
+        let image = RgbImage { width: 2, height: 2, pixels: vec![0, 0, 0, 255, 255, 255, 255, 255, 255, 0, 0, 0] };
+        ImageTexture::new(&image, wrap)
+    }
+
+    fn assert_close(actual: Color, expected: Color) {
+        assert!((actual - expected).length() < 1e-9, "{:?} != {:?}", actual, expected);
+    }
+
+    #[test]
+    fn checker_should_alternate_in_space() {
+        let checker = Checker::new(solid(1.0, 1.0, 1.0), solid(0.0, 0.0, 0.0), 0.5);
+        let origin = Point::new(0.1, 0.1, 0.1);
+        assert_eq!(checker.value(0.0, 0.0, origin), Color::new(1.0, 1.0, 1.0));
+        assert_eq!(checker.value(0.0, 0.0, Point::new(0.6, 0.1, 0.1)), Color::new(0.0, 0.0, 0.0));
+        assert_eq!(checker.value(0.0, 0.0, Point::new(0.6, 0.6, 0.1)), Color::new(1.0, 1.0, 1.0));
+        // Negative coordinates continue the pattern instead of mirroring it at 0
+        assert_eq!(checker.value(0.0, 0.0, Point::new(-0.1, 0.1, 0.1)), Color::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn image_should_be_sampled_at_pixel_centers() {
+        let texture = image_texture(WrapMode::Clamp);
+        // Bottom left pixel is white, top left black
+        assert_close(texture.value(0.25, 0.25, Point::new(0.0, 0.0, 0.0)), Color::new(1.0, 1.0, 1.0));
+        assert_close(texture.value(0.25, 0.75, Point::new(0.0, 0.0, 0.0)), Color::new(0.0, 0.0, 0.0));
+        // Halfway between all four pixels
+        assert_close(texture.value(0.5, 0.5, Point::new(0.0, 0.0, 0.0)), Color::new(0.5, 0.5, 0.5));
+        // Gamma is removed: 128 / 255 squared
+        let grey = RgbImage { width: 1, height: 1, pixels: vec![128, 128, 128] };
+        let value = ImageTexture::new(&grey, WrapMode::Clamp).value(0.5, 0.5, Point::new(0.0, 0.0, 0.0));
+        let linear = (128.0f64 / 255.0).powi(2);
+        assert_close(value, Color::new(linear, linear, linear));
+    }
+
+    #[test]
+    fn noise_textures_should_be_deterministic_and_in_range() {
+        let (light, dark) = (Color::new(0.9, 0.9, 0.9), Color::new(0.1, 0.1, 0.2));
+        let black = Color::new(0.0, 0.0, 0.0);
+        // Each with its twin from the same seed, and the darkest color it can give
+        type Case = (Box<dyn Texture>, Box<dyn Texture>, Color);
+        let textures: Vec<Case> = vec![
+            (Box::new(Marble::new(1, light, dark, 4.0, 7)), Box::new(Marble::new(1, light, dark, 4.0, 7)), dark),
+            (Box::new(Wood::new(1, light, dark, 6.0, 4)), Box::new(Wood::new(1, light, dark, 6.0, 4)), dark),
+            (
+                Box::new(TurbulenceTexture::new(1, light, 2.0, 7)),
+                Box::new(TurbulenceTexture::new(1, light, 2.0, 7)),
+                black,
+            ),
+        ];
+        for (texture, same_seed, darkest) in textures.iter() {
+            for i in 0..200 {
+                let p = Point::new(i as f64 * 0.173 - 10.0, (i as f64 * 0.31).sin(), i as f64 * 0.057);
+                let value = texture.value(0.0, 0.0, p);
+                assert_eq!(value, same_seed.value(0.0, 0.0, p));
+                // Mixes of the two colors stay between them
+                for axis in 0..3 {
+                    assert!(value[axis] >= darkest[axis] - 1e-12 && value[axis] <= light[axis] + 1e-12, "{:?}", value);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn wrap_modes_should_map_outside_coordinates() {
+        assert_eq!(WrapMode::Repeat.wrap(-1, 4), 3);
+        assert_eq!(WrapMode::Repeat.wrap(5, 4), 1);
+        assert_eq!(WrapMode::Clamp.wrap(-3, 4), 0);
+        assert_eq!(WrapMode::Clamp.wrap(9, 4), 3);
+        assert_eq!(WrapMode::Mirror.wrap(-1, 4), 0);
+        assert_eq!(WrapMode::Mirror.wrap(4, 4), 3);
+        assert_eq!(WrapMode::Mirror.wrap(9, 4), 1);
+
+        let p = Point::new(0.0, 0.0, 0.0);
+        // One pixel to the right of the image: repeats the left column, clamps to the right one
+        assert_close(image_texture(WrapMode::Repeat).value(1.25, 0.25, p), Color::new(1.0, 1.0, 1.0));
+        assert_close(image_texture(WrapMode::Clamp).value(1.25, 0.25, p), Color::new(0.0, 0.0, 0.0));
+        assert_close(image_texture(WrapMode::Mirror).value(1.25, 0.25, p), Color::new(0.0, 0.0, 0.0));
+    }
+}
@@ -0,0 +1,165 @@
+use crate::aabb::Aabb;
+use crate::point::Point;
+use crate::ray::Ray;
+use crate::utils;
+use crate::hittable::{HitRecord, Hittable};
+use crate::vec3::Vec3;
+use crate::material::Material;
+use crate::cylinder::{disk_box, Frame};
+use crate::sphere::{sphere_tangent, sphere_uv};
+
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+// Ring of tube radius minor_radius around a circle of radius major_radius
+// u is the angle around the axis, as on cylinders, and v the angle around the tube,
+// starting from the inside of the ring.
+pub struct Torus {
+    frame: Frame,
+    major_radius: f64,
+    minor_radius: f64,
+    material: Arc<dyn Material>,
+}
+
+impl Torus {
+    pub fn new(center: Point, axis: Vec3, major_radius: f64, minor_radius: f64, material: Arc<dyn Material>) -> Self {
+        Torus { frame: Frame::new(center, axis), major_radius, minor_radius, material }
+    }
+}
+
+impl Hittable for Torus {
+    // In the local frame, with the axis along y, the surface is
+    // (x^2 + y^2 + z^2 + R^2 - r^2)^2 = 4 R^2 (x^2 + z^2)
+    // Substituting p = o + s d for a unit d, with k = o.o + R^2 - r^2:
+    // s^4 + 4 (o.d) s^3 + (4 (o.d)^2 + 2 k - 4 R^2 (d.x^2 + d.z^2)) s^2
+    //     + (4 (o.d) k - 8 R^2 (o.x d.x + o.z d.z)) s + k^2 - 4 R^2 (o.x^2 + o.z^2) = 0
+    fn hit(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let local = self.frame.local_ray(ray);
+        let length = local.direction.length();
+        let d = local.direction / length;
+        let (big, small) = (self.major_radius, self.minor_radius);
+
+        // The coefficients lose precision far from the torus, so start the ray
+        // at the sphere around it
+        let start = f64::max(0.0, -local.origin.dot(d) - (big + small));
+        let o = local.origin + start * d;
+
+        let od = o.dot(d);
+        let k = o.dot(o) + big * big - small * small;
+        let four_r2 = 4.0 * big * big;
+        let roots = utils::quartic_solver(
+            1.0,
+            4.0 * od,
+            4.0 * od * od + 2.0 * k - four_r2 * (d.x * d.x + d.z * d.z),
+            4.0 * od * k - 2.0 * four_r2 * (o.x * d.x + o.z * d.z),
+            k * k - four_r2 * (o.x * o.x + o.z * o.z),
+        );
+        let t = roots
+            .into_iter()
+            .map(|s| (start + s) / length)
+            .find(|&t| t > t_min && t < t_max)?;
+
+        let p = local.at(t);
+        let around = Vec3::new(p.x, 0.0, p.z);
+        let distance = around.length();
+        // From the nearest point on the central circle
+        let ring = big / distance * around;
+        let normal = (p - ring).unit_vector();
+        let u = sphere_uv(around).0;
+        let v = (p.y.atan2(distance - big) + PI) / (2.0 * PI);
+
+        let mut hit = HitRecord::new(ray, t, self.frame.world_vector(normal), (u, v), self.material.clone());
+        hit.tangent = Some(self.frame.world_vector(sphere_tangent(around)));
+        Some(hit)
+    }
+
+    // The ring's disk, thickened by the tube along the axis
+    fn bounding_box(&self) -> Option<Aabb> {
+        let reach = self.major_radius + self.minor_radius;
+        let offset = self.minor_radius * self.frame.y;
+        let below = disk_box(self.frame.origin - offset, self.frame.y, reach);
+        let above = disk_box(self.frame.origin + offset, self.frame.y, reach);
+        Some(Aabb::surrounding_box(below, above))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::Lambertian;
+
+    fn torus() -> Torus {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        Torus::new(Point::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), 2.0, 0.5, material)
+    }
+
+    fn assert_close(actual: Vec3, expected: Vec3) {
+        assert!((actual - expected).length() < 1e-9, "{:?} != {:?}", actual, expected);
+    }
+
+    #[test]
+    fn ray_should_hit_the_near_side_of_the_ring() {
+        let torus = torus();
+        // Across the ring through the middle, first the outside then the inside of the tube
+        let ray = Ray::new(Point::new(-5.0, 0.0, 0.0), Vec3::new(2.0, 0.0, 0.0));
+        let hit = torus.hit(ray, 0.001, f64::INFINITY).unwrap();
+        assert!((hit.t - 1.25).abs() < 1e-9);
+        assert_close(hit.normal, Vec3::new(-1.0, 0.0, 0.0));
+        assert!((hit.v - 0.5).abs() < 1e-9);
+        let hit = torus.hit(ray, 1.3, f64::INFINITY).unwrap();
+        assert!((hit.t - 1.75).abs() < 1e-9);
+        assert_close(hit.normal, Vec3::new(1.0, 0.0, 0.0));
+        // Down through the hole
+        assert!(torus.hit(Ray::new(Point::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0)), 0.001, f64::INFINITY).is_none());
+        // Down onto the top of the tube
+        let hit = torus.hit(Ray::new(Point::new(0.0, 5.0, 2.0), Vec3::new(0.0, -1.0, 0.0)), 0.001, f64::INFINITY).unwrap();
+        assert!((hit.t - 4.5).abs() < 1e-9);
+        assert_close(hit.normal, Vec3::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn grazing_ray_should_touch_the_tube() {
+        let torus = torus();
+        // Along the top of the tube, tangent at two points
+        let ray = Ray::new(Point::new(-5.0, 0.5, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let hit = torus.hit(ray, 0.001, f64::INFINITY).unwrap();
+        assert!((hit.point.x + 2.0).abs() < 1e-4, "{:?}", hit.point);
+        assert!((hit.normal.y - 1.0).abs() < 1e-6);
+        // Just above it
+        let ray = Ray::new(Point::new(-5.0, 0.5 + 1e-6, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!(torus.hit(ray, 0.001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn far_away_hits_should_stay_on_the_surface() {
+        let torus = Torus::new(
+            Point::new(1.0, 2.0, 3.0),
+            Vec3::new(1.0, 1.0, 0.0),
+            2.0,
+            0.5,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        );
+        let bbox = torus.bounding_box().unwrap();
+        let mut hits = 0;
+        for i in 0..200 {
+            let angle = i as f64 * 0.61;
+            let target = Point::new(1.0 + 2.0 * angle.cos(), 2.0 + 0.3 * angle.sin(), 3.0 + 2.0 * (angle * 1.3).sin());
+            let origin = Point::new(-400.0, 300.0, 900.0);
+            if let Some(hit) = torus.hit(Ray::new(origin, target - origin), 0.001, f64::INFINITY) {
+                hits += 1;
+                // Distance from the central circle is the tube radius
+                let p = hit.point - Point::new(1.0, 2.0, 3.0);
+                let axis = Vec3::new(1.0, 1.0, 0.0).unit_vector();
+                let height = p.dot(axis);
+                let radial = (p - height * axis).length();
+                let distance = ((radial - 2.0).powi(2) + height * height).sqrt();
+                assert!((distance - 0.5).abs() < 1e-6, "{}", distance);
+                for axis in 0..3 {
+                    assert!(hit.point[axis] >= bbox.minimum[axis] - 1e-9 && hit.point[axis] <= bbox.maximum[axis] + 1e-9);
+                }
+            }
+        }
+        assert!(hits > 50);
+    }
+}
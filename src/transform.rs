@@ -0,0 +1,375 @@
+// Affine transforms of space, as 4x4 matrices acting on column vectors
+//
+// Points are (x, y, z, 1) and pick up the translation, vectors are (x, y, z, 0) and do not.
+// Normals stay perpendicular to the surface only when transformed by the inverse transpose,
+// so every transform keeps its inverse alongside.
+
+use crate::aabb::Aabb;
+use crate::point::Point;
+use crate::utils;
+use crate::vec3::Vec3;
+
+pub type Matrix = [[f64; 4]; 4];
+
+const IDENTITY: Matrix = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Transform {
+    matrix: Matrix,
+    inverse: Matrix,
+}
+
+impl Transform {
+    pub fn identity() -> Self {
+        Transform { matrix: IDENTITY, inverse: IDENTITY }
+    }
+
+    pub fn translation(offset: Vec3) -> Self {
+        let mut matrix = IDENTITY;
+        let mut inverse = IDENTITY;
+        for axis in 0..3 {
+            matrix[axis][3] = offset[axis];
+            inverse[axis][3] = -offset[axis];
+        }
+        Transform { matrix, inverse }
+    }
+
+    // None if any factor is 0, which would flatten space
+    pub fn scaling(factors: Vec3) -> Option<Self> {
+        if factors.x == 0.0 || factors.y == 0.0 || factors.z == 0.0 {
+            return None;
+        }
+        let mut matrix = IDENTITY;
+        let mut inverse = IDENTITY;
+        for axis in 0..3 {
+            matrix[axis][axis] = factors[axis];
+            inverse[axis][axis] = 1.0 / factors[axis];
+        }
+        Some(Transform { matrix, inverse })
+    }
+
+    // Counterclockwise looking down the axis towards the origin (right hand rule)
+    // Rodrigues' formula: R = cos I + sin [axis]x + (1 - cos) axis axis^T
+    pub fn rotation(axis: Vec3, degrees: f64) -> Self {
+        let a = axis.unit_vector();
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let k = 1.0 - cos;
+        let mut matrix = IDENTITY;
+        matrix[0][0] = cos + k * a.x * a.x;
+        matrix[0][1] = k * a.x * a.y - sin * a.z;
+        matrix[0][2] = k * a.x * a.z + sin * a.y;
+        matrix[1][0] = k * a.y * a.x + sin * a.z;
+        matrix[1][1] = cos + k * a.y * a.y;
+        matrix[1][2] = k * a.y * a.z - sin * a.x;
+        matrix[2][0] = k * a.z * a.x - sin * a.y;
+        matrix[2][1] = k * a.z * a.y + sin * a.x;
+        matrix[2][2] = cos + k * a.z * a.z;
+        // Rotations are orthogonal, the inverse is the transpose
+        Transform { matrix, inverse: transpose(&matrix) }
+    }
+
+    // Any invertible affine matrix, None if it is singular or projective
+    pub fn from_matrix(matrix: Matrix) -> Option<Self> {
+        if matrix[3] != [0.0, 0.0, 0.0, 1.0] {
+            return None;
+        }
+        let inverse = invert(&matrix)?;
+        Some(Transform { matrix, inverse })
+    }
+
+    // This transform followed by next
+    pub fn then(&self, next: &Transform) -> Transform {
+        Transform {
+            matrix: multiply(&next.matrix, &self.matrix),
+            inverse: multiply(&self.inverse, &next.inverse),
+        }
+    }
+
+    pub fn inverse(&self) -> Transform {
+        Transform { matrix: self.inverse, inverse: self.matrix }
+    }
+
+    pub fn point(&self, p: Point) -> Point {
+        apply(&self.matrix, p, 1.0)
+    }
+
+    pub fn vector(&self, v: Vec3) -> Vec3 {
+        apply(&self.matrix, v, 0.0)
+    }
+
+    // Not normalized, lengths change under scaling
+    pub fn normal(&self, n: Vec3) -> Vec3 {
+        apply(&transpose(&self.inverse), n, 0.0)
+    }
+
+    // Box around the transformed corners of a box
+    pub fn bounding_box(&self, bbox: Aabb) -> Aabb {
+        let mut result: Option<Aabb> = None;
+        for corner in 0..8 {
+            let pick = |bit: usize, axis: usize| if corner & bit == 0 { bbox.minimum[axis] } else { bbox.maximum[axis] };
+            let p = self.point(Point::new(pick(1, 0), pick(2, 1), pick(4, 2)));
+            let point_box = Aabb::new(p, p);
+            result = Some(match result {
+                Some(so_far) => Aabb::surrounding_box(so_far, point_box),
+                None => point_box,
+            });
+        }
+        result.unwrap()
+    }
+}
+
+// How a moving step's parameters change, from the first value to the second
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Change {
+    Translate(Vec3, Vec3),
+    Rotate(Vec3, f64, f64), // Axis, degrees
+    Scale(Vec3, Vec3),
+}
+
+impl Change {
+    // Scales passing through 0 on the way would flatten space
+    fn valid(&self) -> bool {
+        match self {
+            Change::Scale(from, to) => (0..3).all(|axis| from[axis] * to[axis] > 0.0),
+            _ => true,
+        }
+    }
+
+    fn at(&self, fraction: f64) -> Transform {
+        let lerp = |from: f64, to: f64| from + fraction * (to - from);
+        match *self {
+            Change::Translate(from, to) => Transform::translation(from + fraction * (to - from)),
+            Change::Rotate(axis, from, to) => Transform::rotation(axis, lerp(from, to)),
+            Change::Scale(from, to) => Transform::scaling(from + fraction * (to - from)).unwrap(),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Step {
+    Fixed(Transform),
+    // Changing linearly from time0 to time1, and holding still before and after
+    Moving { change: Change, time0: f64, time1: f64 },
+}
+
+// A transform changing over time, for motion blur, built from steps applied in order
+#[derive(Clone, Debug, PartialEq)]
+pub struct Motion {
+    steps: Vec<Step>,
+}
+
+impl Motion {
+    pub fn new() -> Self {
+        Motion { steps: Vec::new() }
+    }
+
+    pub fn then(mut self, next: &Transform) -> Self {
+        // Runs of fixed steps are combined
+        if let Some(Step::Fixed(last)) = self.steps.last_mut() {
+            *last = last.then(next);
+        } else {
+            self.steps.push(Step::Fixed(*next));
+        }
+        self
+    }
+
+    // None if the change is not a transform all the way, or time1 is before time0
+    pub fn then_moving(mut self, change: Change, time0: f64, time1: f64) -> Option<Self> {
+        if !change.valid() || time1 < time0 {
+            return None;
+        }
+        self.steps.push(Step::Moving { change, time0, time1 });
+        Some(self)
+    }
+
+    // The transform when nothing moves
+    pub fn fixed(&self) -> Option<Transform> {
+        let mut result = Transform::identity();
+        for step in self.steps.iter() {
+            match step {
+                Step::Fixed(transform) => result = result.then(transform),
+                Step::Moving { .. } => return None,
+            }
+        }
+        Some(result)
+    }
+
+    pub fn at(&self, time: f64) -> Transform {
+        let mut result = Transform::identity();
+        for step in self.steps.iter() {
+            let next = match *step {
+                Step::Fixed(transform) => transform,
+                Step::Moving { change, time0, time1 } => {
+                    let fraction = if time1 > time0 { (time - time0) / (time1 - time0) } else { 1.0 };
+                    change.at(utils::clamp(fraction, 0.0, 1.0))
+                }
+            };
+            result = result.then(&next);
+        }
+        result
+    }
+
+    // Times from the start of the first change to the end of the last, the transform is
+    // the same before and after
+    pub fn span(&self) -> (f64, f64) {
+        self.steps.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(start, end), step| match *step {
+            Step::Fixed(_) => (start, end),
+            Step::Moving { time0, time1, .. } => (start.min(time0), end.max(time1)),
+        })
+    }
+}
+
+fn apply(matrix: &Matrix, v: Vec3, w: f64) -> Vec3 {
+    let row = |i: usize| matrix[i][0] * v.x + matrix[i][1] * v.y + matrix[i][2] * v.z + matrix[i][3] * w;
+    Vec3::new(row(0), row(1), row(2))
+}
+
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut product = [[0.0; 4]; 4];
+    for (i, row) in product.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = (0..4).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    product
+}
+
+fn transpose(matrix: &Matrix) -> Matrix {
+    let mut transposed = [[0.0; 4]; 4];
+    for (i, row) in matrix.iter().enumerate() {
+        for (j, value) in row.iter().enumerate() {
+            transposed[j][i] = *value;
+        }
+    }
+    transposed
+}
+
+// Gauss-Jordan elimination with partial pivoting
+fn invert(matrix: &Matrix) -> Option<Matrix> {
+    let mut a = *matrix;
+    let mut inverse = IDENTITY;
+    let scale = matrix.iter().flatten().fold(0.0, |max: f64, value| max.max(value.abs()));
+    for column in 0..4 {
+        let pivot = (column..4).max_by(|&i, &j| a[i][column].abs().partial_cmp(&a[j][column].abs()).unwrap())?;
+        if a[pivot][column].abs() <= 1e-12 * scale {
+            return None;
+        }
+        a.swap(column, pivot);
+        inverse.swap(column, pivot);
+        let divisor = a[column][column];
+        for j in 0..4 {
+            a[column][j] /= divisor;
+            inverse[column][j] /= divisor;
+        }
+        for row in 0..4 {
+            if row != column {
+                let factor = a[row][column];
+                for j in 0..4 {
+                    a[row][j] -= factor * a[column][j];
+                    inverse[row][j] -= factor * inverse[column][j];
+                }
+            }
+        }
+    }
+    Some(inverse)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: Vec3, expected: Vec3) {
+        assert!((actual - expected).length() < 1e-9, "{:?} != {:?}", actual, expected);
+    }
+
+    #[test]
+    fn transforms_should_apply_in_order() {
+        let p = Point::new(1.0, 0.0, 0.0);
+        let rotate = Transform::rotation(Vec3::new(0.0, 0.0, 1.0), 90.0);
+        let translate = Transform::translation(Vec3::new(0.0, 0.0, 5.0));
+        assert_close(rotate.point(p), Point::new(0.0, 1.0, 0.0));
+        // Rotation about y takes x towards -z
+        assert_close(Transform::rotation(Vec3::new(0.0, 1.0, 0.0), 90.0).point(p), Point::new(0.0, 0.0, -1.0));
+        let both = Transform::scaling(Vec3::new(2.0, 2.0, 2.0)).unwrap().then(&rotate).then(&translate);
+        assert_close(both.point(p), Point::new(0.0, 2.0, 5.0));
+        // Vectors are not translated
+        assert_close(both.vector(p), Vec3::new(0.0, 2.0, 0.0));
+        assert_close(both.inverse().point(Point::new(0.0, 2.0, 5.0)), p);
+    }
+
+    #[test]
+    fn normals_should_stay_perpendicular() {
+        // Squash the plane x + y = 0, whose normal is (1, 1, 0)
+        let squash = Transform::scaling(Vec3::new(1.0, 0.25, 1.0)).unwrap();
+        let tangent = squash.vector(Vec3::new(1.0, -1.0, 0.0));
+        let normal = squash.normal(Vec3::new(1.0, 1.0, 0.0));
+        assert!(tangent.dot(normal).abs() < 1e-12);
+        assert!(squash.vector(Vec3::new(1.0, 1.0, 0.0)).dot(tangent).abs() > 0.1);
+    }
+
+    #[test]
+    fn general_matrices_should_invert() {
+        let transform = Transform::rotation(Vec3::new(1.0, 2.0, 3.0), 37.0)
+            .then(&Transform::scaling(Vec3::new(2.0, -1.0, 0.5)).unwrap())
+            .then(&Transform::translation(Vec3::new(3.0, -4.0, 1.0)));
+        let general = Transform::from_matrix(transform.matrix).unwrap();
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((general.inverse[i][j] - transform.inverse[i][j]).abs() < 1e-12);
+            }
+        }
+        let product = multiply(&general.matrix, &general.inverse);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((product[i][j] - IDENTITY[i][j]).abs() < 1e-12);
+            }
+        }
+
+        let mut singular = IDENTITY;
+        singular[2] = [1.0, 1.0, 0.0, 0.0];
+        assert!(Transform::from_matrix(singular).is_none());
+        let mut projective = IDENTITY;
+        projective[3][0] = 1.0;
+        assert!(Transform::from_matrix(projective).is_none());
+        assert!(Transform::scaling(Vec3::new(1.0, 0.0, 1.0)).is_none());
+    }
+
+    #[test]
+    fn motion_should_interpolate_between_times() {
+        let motion = Motion::new()
+            .then(&Transform::translation(Vec3::new(1.0, 0.0, 0.0)))
+            .then_moving(Change::Rotate(Vec3::new(0.0, 0.0, 1.0), 0.0, 90.0), 1.0, 3.0)
+            .unwrap()
+            .then(&Transform::translation(Vec3::new(0.0, 0.0, 2.0)));
+        let p = Point::new(0.0, 0.0, 0.0);
+        assert_close(motion.at(1.0).point(p), Point::new(1.0, 0.0, 2.0));
+        let half = std::f64::consts::FRAC_1_SQRT_2;
+        assert_close(motion.at(2.0).point(p), Point::new(half, half, 2.0));
+        assert_close(motion.at(3.0).point(p), Point::new(0.0, 1.0, 2.0));
+        // Still outside the interval
+        assert_close(motion.at(-5.0).point(p), Point::new(1.0, 0.0, 2.0));
+        assert_close(motion.at(10.0).point(p), Point::new(0.0, 1.0, 2.0));
+        assert_eq!(motion.span(), (1.0, 3.0));
+        assert!(motion.fixed().is_none());
+
+        let fixed = Motion::new().then(&Transform::translation(Vec3::new(1.0, 0.0, 0.0)));
+        assert_eq!(fixed.fixed(), Some(Transform::translation(Vec3::new(1.0, 0.0, 0.0))));
+        // Scaling through 0
+        let flip = Change::Scale(Vec3::new(1.0, 1.0, 1.0), Vec3::new(-1.0, 1.0, 1.0));
+        assert!(Motion::new().then_moving(flip, 0.0, 1.0).is_none());
+    }
+
+    #[test]
+    fn bounding_box_should_enclose_rotated_box() {
+        let bbox = Aabb::new(Point::new(-1.0, -1.0, -1.0), Point::new(1.0, 1.0, 1.0));
+        let rotated = Transform::rotation(Vec3::new(0.0, 1.0, 0.0), 45.0).bounding_box(bbox);
+        let half_diagonal = 2f64.sqrt();
+        assert_close(rotated.maximum, Point::new(half_diagonal, 1.0, half_diagonal));
+        assert_close(rotated.minimum, -rotated.maximum);
+    }
+}
@@ -0,0 +1,228 @@
+use crate::aabb::Aabb;
+use crate::point::Point;
+use crate::ray::Ray;
+use crate::hittable::{HitRecord, Hittable};
+use crate::vec3::Vec3;
+use crate::material::Material;
+
+use std::sync::Arc;
+
+// Thickness given to the bounding box of a triangle lying in an axis plane
+const MIN_BOX_EXTENT: f64 = 1e-4;
+
+#[derive(Clone)]
+pub struct Triangle {
+    vertices: [Point; 3],
+    // Per-vertex shading normals, None shades the triangle flat
+    normals: Option<[Vec3; 3]>,
+    uvs: [(f64, f64); 3],
+    material: Arc<dyn Material>,
+}
+
+impl Triangle {
+    // Vertices in counter-clockwise order seen from the front, which is the side the
+    // geometric normal (b - a) x (c - a) points to
+    pub fn new(a: Point, b: Point, c: Point, material: Arc<dyn Material>) -> Self {
+        Triangle {
+            vertices: [a, b, c],
+            normals: None,
+            uvs: [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)],
+            material,
+        }
+    }
+
+    pub fn with_normals(mut self, normals: [Vec3; 3]) -> Self {
+        self.normals = Some([normals[0].unit_vector(), normals[1].unit_vector(), normals[2].unit_vector()]);
+        self
+    }
+
+    pub fn with_uvs(mut self, uvs: [(f64, f64); 3]) -> Self {
+        self.uvs = uvs;
+        self
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        triangle_hit_record(self.vertices, self.normals, self.uvs, &self.material, ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(triangle_bounding_box(self.vertices))
+    }
+}
+
+pub fn triangle_bounding_box(vertices: [Point; 3]) -> Aabb {
+    let [a, b, c] = vertices;
+    let bbox = Aabb::surrounding_box(Aabb::new(a, a), Aabb::surrounding_box(Aabb::new(b, b), Aabb::new(c, c)));
+    bbox.padded(MIN_BOX_EXTENT)
+}
+
+// Shared by triangles holding their own vertices and triangles of a mesh
+// Normals must be unit vectors
+pub fn triangle_hit_record(
+    vertices: [Point; 3],
+    normals: Option<[Vec3; 3]>,
+    uvs: [(f64, f64); 3],
+    material: &Arc<dyn Material>,
+    ray: Ray,
+    t_min: f64,
+    t_max: f64,
+) -> Option<HitRecord> {
+    let (t, b1, b2) = hit_triangle(vertices, ray)?;
+    if t <= t_min || t >= t_max {
+        return None;
+    }
+    let b0 = 1.0 - b1 - b2;
+
+    let [uv0, uv1, uv2] = uvs;
+    let u = b0 * uv0.0 + b1 * uv1.0 + b2 * uv2.0;
+    let v = b0 * uv0.1 + b1 * uv1.1 + b2 * uv2.1;
+
+    // Which side was hit is decided by the geometry, the interpolated normal only shades
+    let [a, b, c] = vertices;
+    let geometric_normal = (b - a).cross(c - a).unit_vector();
+    let mut hit = HitRecord::new(ray, t, geometric_normal, (u, v), material.clone());
+    if let Some([n0, n1, n2]) = normals {
+        let shading_normal = (b0 * n0 + b1 * n1 + b2 * n2).unit_vector();
+        // Keep it on the outward side, vertex normals may disagree with the winding
+        hit.normal = if shading_normal.dot(geometric_normal) < 0.0 { -shading_normal } else { shading_normal };
+    }
+    Some(hit)
+}
+
+// Möller–Trumbore
+// A point in the triangle is P = A + b1 * (B - A) + b2 * (C - A), with b1, b2 >= 0 and b1 + b2 <= 1.
+// Setting it equal to the ray O + t * d gives a 3x3 linear system in (t, b1, b2):
+// -t * d + b1 * e1 + b2 * e2 = O - A
+// which is solved with Cramer's rule, written with cross products (scalar triple products).
+//
+// Returns (t, b1, b2) for any t, the caller checks the range.
+// Points on edges and vertices count as hits, so a ray through the shared edge of two
+// triangles is not lost between them.
+pub fn hit_triangle(vertices: [Point; 3], ray: Ray) -> Option<(f64, f64, f64)> {
+    let [a, b, c] = vertices;
+    let edge1 = b - a;
+    let edge2 = c - a;
+    let p = ray.direction.cross(edge2);
+    let determinant = edge1.dot(p);
+
+    // Ray parallel to the plane (or the triangle has no area)
+    // The tolerance is relative, so it does not depend on the size of the triangle
+    let scale = edge1.length() * edge2.length() * ray.direction.length();
+    if determinant.abs() <= 1e-12 * scale {
+        return None;
+    }
+    let inverse_determinant = 1.0 / determinant;
+
+    let s = ray.origin - a;
+    let b1 = s.dot(p) * inverse_determinant;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+    let q = s.cross(edge1);
+    let b2 = ray.direction.dot(q) * inverse_determinant;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+    let t = edge2.dot(q) * inverse_determinant;
+    Some((t, b1, b2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::Lambertian;
+
+    // Right triangle in the z = -1 plane, facing the origin
+    fn triangle() -> Triangle {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        Triangle::new(
+            Point::new(0.0, 0.0, -1.0),
+            Point::new(1.0, 0.0, -1.0),
+            Point::new(0.0, 1.0, -1.0),
+            material,
+        )
+    }
+
+    fn ray_towards(x: f64, y: f64) -> Ray {
+        Ray::new(Point::new(x, y, 0.0), Vec3::new(0.0, 0.0, -1.0))
+    }
+
+    #[test]
+    fn ray_should_hit_triangle() {
+        let hit = triangle().hit(ray_towards(0.25, 0.25), 0.001, f64::INFINITY).unwrap();
+        assert_eq!(hit.t, 1.0);
+        assert_eq!(hit.point, Point::new(0.25, 0.25, -1.0));
+        assert_eq!(hit.normal, Vec3::new(0.0, 0.0, 1.0));
+        assert!(hit.front_face);
+        assert_eq!((hit.u, hit.v), (0.25, 0.25));
+        assert!(triangle().hit(ray_towards(0.25, 0.25), 0.001, 0.5).is_none());
+    }
+
+    #[test]
+    fn ray_outside_should_miss_triangle() {
+        assert!(triangle().hit(ray_towards(0.6, 0.6), 0.001, f64::INFINITY).is_none());
+        assert!(triangle().hit(ray_towards(-0.1, 0.5), 0.001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn ray_on_edge_or_vertex_should_hit_triangle() {
+        // Hypotenuse, legs and corners
+        for (x, y) in [(0.5, 0.5), (0.5, 0.0), (0.0, 0.5), (0.0, 0.0), (1.0, 0.0), (0.0, 1.0)].iter() {
+            let hit = triangle().hit(ray_towards(*x, *y), 0.001, f64::INFINITY);
+            assert!(hit.is_some(), "({}, {}) should hit", x, y);
+        }
+    }
+
+    #[test]
+    fn parallel_ray_should_miss_triangle() {
+        // Above the plane, and lying in it
+        let above = Ray::new(Point::new(-1.0, 0.25, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!(triangle().hit(above, 0.001, f64::INFINITY).is_none());
+        let in_plane = Ray::new(Point::new(-1.0, 0.25, -1.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!(triangle().hit(in_plane, 0.001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn ray_from_behind_should_hit_back_face() {
+        let ray = Ray::new(Point::new(0.25, 0.25, -2.0), Vec3::new(0.0, 0.0, 1.0));
+        let hit = triangle().hit(ray, 0.001, f64::INFINITY).unwrap();
+        assert_eq!(hit.t, 1.0);
+        assert_eq!(hit.normal, Vec3::new(0.0, 0.0, 1.0));
+        assert!(!hit.front_face);
+
+        // Triangle behind the ray
+        let away = Ray::new(Point::new(0.25, 0.25, -2.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(triangle().hit(away, 0.001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn vertex_normals_and_uvs_should_be_interpolated() {
+        let triangle = triangle()
+            .with_normals([Vec3::new(-1.0, 0.0, 1.0), Vec3::new(1.0, 0.0, 1.0), Vec3::new(-1.0, 0.0, 1.0)])
+            .with_uvs([(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)]);
+
+        // Halfway along the bottom edge the two normals cancel out sideways
+        let hit = triangle.hit(ray_towards(0.5, 0.0), 0.001, f64::INFINITY).unwrap();
+        assert!((hit.normal - Vec3::new(0.0, 0.0, 1.0)).length() < 1e-12);
+        assert_eq!((hit.u, hit.v), (0.5, 0.0));
+
+        let hit = triangle.hit(ray_towards(0.0, 1.0), 0.001, f64::INFINITY).unwrap();
+        assert!((hit.normal - Vec3::new(-1.0, 0.0, 1.0).unit_vector()).length() < 1e-12);
+        assert_eq!((hit.u, hit.v), (1.0, 1.0));
+
+        // Normals pointing against the winding are flipped outwards
+        let flipped = self::triangle().with_normals([Vec3::new(0.0, 0.0, -1.0); 3]);
+        let hit = flipped.hit(ray_towards(0.25, 0.25), 0.001, f64::INFINITY).unwrap();
+        assert_eq!(hit.normal, Vec3::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn flat_triangle_should_have_hittable_bounding_box() {
+        let bbox = triangle().bounding_box().unwrap();
+        assert!(bbox.maximum.z > bbox.minimum.z);
+        assert!(bbox.hit(ray_towards(0.25, 0.25), 0.001, f64::INFINITY));
+    }
+}
@@ -1,5 +1,7 @@
 extern crate rand;
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore, SeedableRng};
+use std::cell::RefCell;
 
 pub fn quadratic_solver(a: f64, b: f64, c: f64) -> Option<(f64, f64)> {
     // b^2 - 4ac
@@ -9,17 +11,204 @@ pub fn quadratic_solver(a: f64, b: f64, c: f64) -> Option<(f64, f64)> {
     }
 
     let common = discriminant.sqrt();
-    return Some(((-b - common) / (2.0 * a), (-b + common) / (2.0 * a)));
+    Some(((-b - common) / (2.0 * a), (-b + common) / (2.0 * a)))
+}
+
+// Real roots of a x^4 + b x^3 + c x^2 + d x + e = 0 in increasing order, repeated roots once
+//
+// The closed form (Ferrari) loses most of its precision on the near-repeated roots that
+// grazing rays produce, so roots are isolated instead: between two neighbouring roots of the
+// derivative the polynomial is monotonic, so it has at most one root there, found by Newton's
+// method safeguarded with bisection. A root of the derivative where the polynomial is zero
+// is a repeated root.
+pub fn quartic_solver(a: f64, b: f64, c: f64, d: f64, e: f64) -> Vec<f64> {
+    polynomial_roots(&[a, b, c, d, e])
+}
+
+// Real roots of the polynomial with the given coefficients, highest power first
+pub fn polynomial_roots(coefficients: &[f64]) -> Vec<f64> {
+    // Leading zeros lower the degree
+    let start = coefficients.iter().position(|&c| c != 0.0).unwrap_or(coefficients.len());
+    let coefficients = &coefficients[start..];
+    if coefficients.len() < 2 {
+        return vec![];
+    }
+    if coefficients.len() == 2 {
+        return vec![-coefficients[1] / coefficients[0]];
+    }
+
+    let degree = coefficients.len() - 1;
+    let derivative: Vec<f64> = coefficients[..degree]
+        .iter()
+        .enumerate()
+        .map(|(i, c)| c * (degree - i) as f64)
+        .collect();
+    // Every root lies within the Cauchy bound
+    let bound = 1.0 + coefficients[1..].iter().map(|c| (c / coefficients[0]).abs()).fold(0.0, f64::max);
+    let mut breaks = vec![-bound];
+    breaks.extend(polynomial_roots(&derivative).into_iter().filter(|x| x.abs() < bound));
+    breaks.push(bound);
+
+    let evaluate = |x: f64| coefficients.iter().fold(0.0, |sum, c| sum * x + c);
+    // Values this close to 0 are within the rounding error of evaluating the polynomial
+    let is_zero = |x: f64| {
+        let scale = coefficients.iter().fold(0.0, |sum, c| sum * x.abs() + c.abs());
+        evaluate(x).abs() <= 16.0 * f64::EPSILON * scale
+    };
+
+    let mut roots = vec![];
+    for (i, pair) in breaks.windows(2).enumerate() {
+        let (low, high) = (pair[0], pair[1]);
+        // Interior breaks are extrema
+        if i > 0 && is_zero(low) {
+            if roots.last() != Some(&low) {
+                roots.push(low);
+            }
+            continue;
+        }
+        if i + 2 < breaks.len() && is_zero(high) {
+            continue;
+        }
+        let (low_value, high_value) = (evaluate(low), evaluate(high));
+        if (low_value < 0.0) != (high_value < 0.0) {
+            roots.push(monotonic_root(&evaluate, &derivative, low, high, low_value < 0.0));
+        }
+    }
+    roots
+}
+
+// Root of a polynomial known to cross 0 once between low and high
+fn monotonic_root(evaluate: &impl Fn(f64) -> f64, derivative: &[f64], mut low: f64, mut high: f64, increasing: bool) -> f64 {
+    let slope = |x: f64| derivative.iter().fold(0.0, |sum, c| sum * x + c);
+    let mut x = 0.5 * (low + high);
+    for _ in 0..100 {
+        let value = evaluate(x);
+        if value == 0.0 {
+            return x;
+        }
+        // Keep the root bracketed
+        if (value < 0.0) == increasing {
+            low = x;
+        } else {
+            high = x;
+        }
+        let newton = x - value / slope(x);
+        let next = if newton > low && newton < high { newton } else { 0.5 * (low + high) };
+        if next == x || high - low <= f64::EPSILON * x.abs() {
+            return next;
+        }
+        x = next;
+    }
+    x
 }
 
 pub fn clamp(color_value: f64, min: f64, max: f64) -> f64 {
     if color_value < min { return min };
     if color_value > max { return max };
     if f64::is_nan(color_value) { return max };
-    return color_value;
+    color_value
 }
 
 pub fn random_probability() -> f64 {
-    let mut rng = rand::thread_rng();
+    let mut rng = rng();
     rng.gen_range(0.0, 1.0)
 }
+
+// Every thread owns a seedable generator, so that a render can be reproduced from a seed
+// no matter how the work is split between threads.
+// Seeded from entropy until seed_rng is called.
+thread_local! {
+    static RNG: RefCell<StdRng> = RefCell::new(StdRng::from_entropy());
+}
+
+// Handle to the current thread's generator, used in place of rand::thread_rng()
+#[derive(Copy, Clone)]
+pub struct LocalRng;
+
+pub fn rng() -> LocalRng {
+    LocalRng
+}
+
+// Restart the current thread's generator from the given seed
+pub fn seed_rng(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = StdRng::seed_from_u64(seed));
+}
+
+impl RngCore for LocalRng {
+    fn next_u32(&mut self) -> u32 {
+        RNG.with(|rng| rng.borrow_mut().next_u32())
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        RNG.with(|rng| rng.borrow_mut().next_u64())
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        RNG.with(|rng| rng.borrow_mut().fill_bytes(dest))
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        RNG.with(|rng| rng.borrow_mut().try_fill_bytes(dest))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Coefficients of the quartic with the given roots, highest power first
+    fn expand(roots: [f64; 4]) -> [f64; 5] {
+        let mut coefficients = [1.0, 0.0, 0.0, 0.0, 0.0];
+        for (degree, root) in roots.iter().enumerate() {
+            for i in (1..=degree + 1).rev() {
+                coefficients[i] -= root * coefficients[i - 1];
+            }
+        }
+        coefficients
+    }
+
+    fn solve(coefficients: [f64; 5]) -> Vec<f64> {
+        let [a, b, c, d, e] = coefficients;
+        quartic_solver(a, b, c, d, e)
+    }
+
+    fn assert_roots(actual: Vec<f64>, expected: &[f64], tolerance: f64) {
+        assert_eq!(actual.len(), expected.len(), "{:?} != {:?}", actual, expected);
+        for (actual, expected) in actual.iter().zip(expected.iter()) {
+            assert!((actual - expected).abs() <= tolerance, "{} != {}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn quartic_should_find_distinct_roots() {
+        assert_roots(solve(expand([-3.0, -0.5, 1.0, 7.0])), &[-3.0, -0.5, 1.0, 7.0], 1e-12);
+        // (x^2 + 1) (x^2 - 4) has two real roots
+        assert_roots(quartic_solver(1.0, 0.0, -3.0, 0.0, -4.0), &[-2.0, 2.0], 1e-12);
+        // x^4 + 1 has none
+        assert_roots(quartic_solver(1.0, 0.0, 0.0, 0.0, 1.0), &[], 0.0);
+        // Far from 0 the coefficients cancel, and the roots are only as precise as the polynomial
+        assert_roots(solve(expand([1000.0, 1000.5, 1002.0, 1004.0])), &[1000.0, 1000.5, 1002.0, 1004.0], 1e-4);
+        assert_roots(solve(expand([1e-3, 2e-3, 3e-3, 4e-3])), &[1e-3, 2e-3, 3e-3, 4e-3], 1e-12);
+    }
+
+    #[test]
+    fn quartic_should_find_repeated_roots_once() {
+        assert_roots(solve(expand([2.0, 2.0, -1.0, 3.0])), &[-1.0, 2.0, 3.0], 1e-9);
+        assert_roots(solve(expand([2.0, 2.0, 5.0, 5.0])), &[2.0, 5.0], 1e-9);
+        // (x - 1)^4, only found through the derivatives
+        assert_roots(solve(expand([1.0, 1.0, 1.0, 1.0])), &[1.0], 1e-3);
+        // Tangent to the x axis only: x^2 (x^2 + 1)
+        assert_roots(quartic_solver(1.0, 0.0, 1.0, 0.0, 0.0), &[0.0], 1e-12);
+    }
+
+    #[test]
+    fn quartic_should_separate_near_degenerate_roots() {
+        assert_roots(solve(expand([1.0, 1.0 + 1e-4, -2.0, 5.0])), &[-2.0, 1.0, 1.0 + 1e-4, 5.0], 1e-9);
+        // Closer than the rounding error, merged into one root between them
+        let roots = solve(expand([1.0, 1.0 + 1e-10, -2.0, 5.0]));
+        assert_eq!(roots.len(), 3, "{:?}", roots);
+        assert!((roots[1] - 1.0).abs() < 1e-6);
+        // Leading coefficient 0 falls back to lower degrees
+        assert_roots(quartic_solver(0.0, 0.0, 1.0, -3.0, 2.0), &[1.0, 2.0], 1e-12);
+    }
+}
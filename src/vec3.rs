@@ -1,11 +1,15 @@
 extern crate rand;
 use rand::Rng;
 
+use crate::utils;
+
 use std::ops::Neg;
 use std::ops::Mul;
 use std::ops::Add;
 use std::ops::Sub;
 use std::ops::Div;
+use std::ops::Index;
+use std::ops::IndexMut;
 
 // Common interface for Vec3
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -57,7 +61,7 @@ impl Vec3 {
 
     // Generate a random unit vector, taking the object as a lambertian surface
     pub fn random_unit_vector() -> Self {
-        let mut rng = rand::thread_rng();
+        let mut rng = utils::rng();
         // z-axis component of the incident light ray
         let z: f64 = rng.gen_range(-1.0, 1.0);
 
@@ -68,7 +72,7 @@ impl Vec3 {
         let angle = rng.gen_range(0.0, 2.0 * std::f64::consts::PI);
         let x = xy * angle.cos();
         let y = xy * angle.sin();
-        return Self::new(x, y, z);
+        Self::new(x, y, z)
     }
 
     pub fn random_point_in_unit_sphere() -> Self {
@@ -81,7 +85,7 @@ impl Vec3 {
             break;
         }
         // return the point
-        return point;
+        point
     }
 
     // Generate diffuse rays with no dependence on the normal
@@ -90,11 +94,11 @@ impl Vec3 {
         if in_unit_sphere.dot(normal) > 0.0 {
             return in_unit_sphere;
         }
-        return -in_unit_sphere;
+        -in_unit_sphere
     }
 
     pub fn reflect(self, normal: Vec3) -> Self {
-        return self - 2.0 * self.dot(normal) * normal;
+        self - 2.0 * self.dot(normal) * normal
     }
 
     // let first_medium_indice = incident ray region's refractive indice
@@ -105,25 +109,24 @@ impl Vec3 {
         let cos_theta = -normalized_self.dot(normal);
         let r_out_parallel = refractive_index * (normalized_self + cos_theta * normal);
         let r_out_perpendicular = -((1.0 - r_out_parallel.length_squared()).sqrt()) * normal;
-        let r_out = r_out_parallel + r_out_perpendicular;
-        r_out
+        r_out_parallel + r_out_perpendicular
     }
 
     pub fn random_in_unit_disk() -> Vec3 {
         let mut p: Vec3;
-        let mut rng = rand::thread_rng();
+        let mut rng = utils::rng();
         loop {
             p = Vec3::new(rng.gen_range(-1.0, 1.0), rng.gen_range(-1.0, 1.0), 0.0);
             if p.length_squared() >= 1.0 { continue; }
             break;
         }
-        return p;
+        p
     }
 
     // Generate a random vector within specified bounds
     pub fn bound_random(min: f64, max: f64) -> Self {
-        let mut rng = rand::thread_rng();
-        return Self::new(rng.gen_range(min, max), rng.gen_range(min, max), rng.gen_range(min, max));
+        let mut rng = utils::rng();
+        Self::new(rng.gen_range(min, max), rng.gen_range(min, max), rng.gen_range(min, max))
     }
 }
 
@@ -212,6 +215,30 @@ impl Div<f64> for Vec3 {
     }
 }
 
+// vector[axis], where axis 0, 1, 2 is x, y, z
+impl Index<usize> for Vec3 {
+    type Output = f64;
+    fn index(&self, axis: usize) -> &Self::Output {
+        match axis {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("Vec3 axis out of range: {}", axis),
+        }
+    }
+}
+
+impl IndexMut<usize> for Vec3 {
+    fn index_mut(&mut self, axis: usize) -> &mut Self::Output {
+        match axis {
+            0 => &mut self.x,
+            1 => &mut self.y,
+            2 => &mut self.z,
+            _ => panic!("Vec3 axis out of range: {}", axis),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
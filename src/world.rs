@@ -0,0 +1,52 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::bvh::BvhNode;
+use crate::hittable::{HitRecord, Hittable};
+use crate::ray::Ray;
+
+// Objects within the world struct should have the same lifetime as the world
+pub struct World {
+    // Objects with a bounding box, organised for fast lookup
+    bvh: Option<BvhNode>,
+
+    // Objects without a bounding box, which have to be checked on every ray
+    unbounded: Vec<Arc<dyn Hittable>>,
+}
+
+// World is hittable
+impl Hittable for World {
+
+    // Nearest hit among all hittable objects
+    // Each object only needs to beat the closest hit found so far
+    fn hit(&self, ray: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let mut nearest = match &self.bvh {
+            Some(bvh) => bvh.hit(ray, t_min, t_max),
+            None => None,
+        };
+        for object in self.unbounded.iter() {
+            let closest_so_far = nearest.as_ref().map_or(t_max, |hit| hit.t);
+            if let Some(hit) = object.hit(ray, t_min, closest_so_far) {
+                nearest = Some(hit);
+            }
+        }
+        nearest
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        if !self.unbounded.is_empty() {
+            return None;
+        }
+        self.bvh.as_ref()?.bounding_box()
+    }
+}
+
+impl World {
+    pub fn new(objects: Vec<Arc<dyn Hittable>>) -> Self {
+        let (bounded, unbounded): (Vec<_>, Vec<_>) = objects
+            .into_iter()
+            .partition(|object| object.bounding_box().is_some());
+        let bvh = if bounded.is_empty() { None } else { Some(BvhNode::new(bounded)) };
+        World { bvh, unbounded }
+    }
+}
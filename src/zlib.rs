@@ -0,0 +1,538 @@
+// zlib streams (RFC 1950) wrapping DEFLATE data (RFC 1951), as used inside PNG files
+//
+// The compressor finds repeats with LZ77 over hash chains and encodes them with the
+// fixed Huffman codes, which keeps it short while still shrinking rendered images well.
+// The decompressor accepts every block type, so it can read files written by other tools.
+
+use std::fmt;
+
+const WINDOW_SIZE: usize = 32 * 1024;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const MAX_CHAIN: usize = 64; // Candidates examined per position, trades speed for ratio
+const HASH_BITS: usize = 15;
+
+// Base value and number of extra bits for length codes 257..285
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115,
+    131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+
+// Base value and number of extra bits for distance codes 0..29
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+// Order in which code length code lengths are stored in a dynamic block header
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+#[derive(Debug, PartialEq)]
+pub struct InflateError(pub String);
+
+impl fmt::Display for InflateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid zlib data: {}", self.0)
+    }
+}
+
+fn error<T>(message: &str) -> Result<T, InflateError> {
+    Err(InflateError(message.to_string()))
+}
+
+/* ==============
+ * = CHECKSUMS =
+ * ==============
+ */
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 { 0xEDB8_8320 ^ (c >> 1) } else { c >> 1 };
+            k += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+    table
+}
+
+// CRC-32 as used by PNG chunks (polynomial 0xEDB88320, reflected)
+pub fn crc32(bytes: &[u8]) -> u32 {
+    crc32_update(0, bytes)
+}
+
+// Continue a CRC over more bytes, starting from the CRC of the bytes before them
+pub fn crc32_update(crc: u32, bytes: &[u8]) -> u32 {
+    let mut crc = !crc;
+    for &byte in bytes {
+        crc = CRC_TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+pub fn adler32(bytes: &[u8]) -> u32 {
+    const MOD_ADLER: u32 = 65521;
+    let mut a: u32 = 1;
+    let mut b: u32 = 0;
+    // 5552 is the most bytes which can be summed before b could overflow
+    for chunk in bytes.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= MOD_ADLER;
+        b %= MOD_ADLER;
+    }
+    (b << 16) | a
+}
+
+/* ============
+ * = COMPRESS =
+ * ============
+ */
+
+// Bits are packed starting from the least significant bit of each byte
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    count: u32,
+}
+
+impl BitWriter {
+    fn write_bits(&mut self, value: u32, count: u32) {
+        self.buffer |= value << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    // Huffman codes are stored most significant bit first
+    fn write_code(&mut self, code: u32, length: u32) {
+        let reversed = code.reverse_bits() >> (32 - length);
+        self.write_bits(reversed, length);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+// Fixed Huffman code for a literal/length symbol (RFC 1951, 3.2.6)
+fn write_fixed_literal(writer: &mut BitWriter, symbol: u32) {
+    match symbol {
+        0..=143 => writer.write_code(0x30 + symbol, 8),
+        144..=255 => writer.write_code(0x190 + symbol - 144, 9),
+        256..=279 => writer.write_code(symbol - 256, 7),
+        _ => writer.write_code(0xC0 + symbol - 280, 8),
+    }
+}
+
+fn write_match(writer: &mut BitWriter, length: usize, distance: usize) {
+    let length_code = LENGTH_BASE.iter().rposition(|&base| base as usize <= length).unwrap();
+    write_fixed_literal(writer, 257 + length_code as u32);
+    writer.write_bits((length - LENGTH_BASE[length_code] as usize) as u32, LENGTH_EXTRA[length_code] as u32);
+
+    let distance_code = DISTANCE_BASE.iter().rposition(|&base| base as usize <= distance).unwrap();
+    writer.write_code(distance_code as u32, 5);
+    writer.write_bits(
+        (distance - DISTANCE_BASE[distance_code] as usize) as u32,
+        DISTANCE_EXTRA[distance_code] as u32,
+    );
+}
+
+fn hash(bytes: &[u8]) -> usize {
+    let key = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+    (key.wrapping_mul(2_654_435_761) >> (32 - HASH_BITS)) as usize
+}
+
+// Wraps the data in a zlib stream made of a single fixed Huffman block
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    // CMF: deflate with a 32K window, FLG: default level, check bits make the header a multiple of 31
+    let mut writer = BitWriter { bytes: vec![0x78, 0x9C], buffer: 0, count: 0 };
+
+    writer.write_bits(1, 1); // Final block
+    writer.write_bits(1, 2); // Fixed Huffman codes
+
+    // head[hash] is the latest position with that hash, previous[position % WINDOW_SIZE] the one
+    // before it. Matches only reach back a window, so older links can be overwritten.
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut previous = vec![usize::MAX; WINDOW_SIZE];
+
+    let mut position = 0;
+    while position < data.len() {
+        let (mut best_length, mut best_distance) = (0, 0);
+        if position + MIN_MATCH <= data.len() {
+            let hash = hash(&data[position..]);
+            let max_length = usize::min(MAX_MATCH, data.len() - position);
+            let mut candidate = head[hash];
+            let mut chain = 0;
+            while candidate != usize::MAX && position - candidate <= WINDOW_SIZE && chain < MAX_CHAIN {
+                let length = data[candidate..]
+                    .iter()
+                    .zip(data[position..position + max_length].iter())
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if length > best_length {
+                    best_length = length;
+                    best_distance = position - candidate;
+                    if length == max_length {
+                        break;
+                    }
+                }
+                candidate = previous[candidate % WINDOW_SIZE];
+                chain += 1;
+            }
+        }
+
+        let advance = if best_length >= MIN_MATCH {
+            write_match(&mut writer, best_length, best_distance);
+            best_length
+        } else {
+            write_fixed_literal(&mut writer, data[position] as u32);
+            1
+        };
+
+        // Every position covered is inserted, so later matches can start inside this one
+        for inserted in position..position + advance {
+            if inserted + MIN_MATCH <= data.len() {
+                let hash = hash(&data[inserted..]);
+                previous[inserted % WINDOW_SIZE] = head[hash];
+                head[hash] = inserted;
+            }
+        }
+        position += advance;
+    }
+    write_fixed_literal(&mut writer, 256); // End of block
+
+    let mut bytes = writer.finish();
+    bytes.extend_from_slice(&adler32(data).to_be_bytes());
+    bytes
+}
+
+/* ==============
+ * = DECOMPRESS =
+ * ==============
+ */
+
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    position: usize, // Byte position
+    buffer: u32,
+    count: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn read_bits(&mut self, count: u32) -> Result<u32, InflateError> {
+        while self.count < count {
+            let byte = match self.bytes.get(self.position) {
+                Some(&byte) => byte,
+                None => return error("unexpected end of data"),
+            };
+            self.position += 1;
+            self.buffer |= (byte as u32) << self.count;
+            self.count += 8;
+        }
+        let value = self.buffer & ((1u64 << count) - 1) as u32;
+        self.buffer >>= count;
+        self.count -= count;
+        Ok(value)
+    }
+
+    // Stored blocks start on a byte boundary
+    fn align_to_byte(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+    }
+}
+
+// Canonical Huffman code, decoded one bit at a time
+struct Huffman {
+    counts: [u16; 16],   // Number of codes of each length
+    symbols: Vec<u16>,   // Symbols ordered by code
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Self, InflateError> {
+        let mut counts = [0u16; 16];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+
+        // Reject over-subscribed codes, which cannot be decoded unambiguously
+        let mut left: i32 = 1;
+        for &count in counts[1..].iter() {
+            left = left * 2 - count as i32;
+            if left < 0 {
+                return error("over-subscribed Huffman code");
+            }
+        }
+
+        let mut offsets = [0u16; 16];
+        for length in 1..15 {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+        Ok(Huffman { counts, symbols })
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, InflateError> {
+        let mut code: i32 = 0; // Code bits read so far
+        let mut first: i32 = 0; // First code of the current length
+        let mut index: i32 = 0; // Index of the first code of the current length in symbols
+        for length in 1..16 {
+            code |= reader.read_bits(1)? as i32;
+            let count = self.counts[length] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        error("invalid Huffman code")
+    }
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    for (symbol, length) in lengths.iter_mut().enumerate() {
+        *length = match symbol {
+            0..=143 => 8,
+            144..=255 => 9,
+            256..=279 => 7,
+            _ => 8,
+        };
+    }
+    let literals = Huffman::new(&lengths).unwrap();
+    let distances = Huffman::new(&[5; 30]).unwrap();
+    (literals, distances)
+}
+
+fn dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman), InflateError> {
+    let literal_count = reader.read_bits(5)? as usize + 257;
+    let distance_count = reader.read_bits(5)? as usize + 1;
+    let code_length_count = reader.read_bits(4)? as usize + 4;
+    if literal_count > 286 || distance_count > 30 {
+        return error("too many length or distance codes");
+    }
+
+    let mut code_length_lengths = [0u8; 19];
+    for &index in CODE_LENGTH_ORDER[..code_length_count].iter() {
+        code_length_lengths[index] = reader.read_bits(3)? as u8;
+    }
+    let code_length_code = Huffman::new(&code_length_lengths)?;
+
+    // Literal/length and distance code lengths are run-length encoded together
+    let mut lengths = vec![];
+    while lengths.len() < literal_count + distance_count {
+        let symbol = code_length_code.decode(reader)?;
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => match lengths.last() {
+                Some(&previous) => (previous, 3 + reader.read_bits(2)?),
+                None => return error("repeated code length with no previous length"),
+            },
+            17 => (0, 3 + reader.read_bits(3)?),
+            _ => (0, 11 + reader.read_bits(7)?),
+        };
+        for _ in 0..repeat {
+            lengths.push(value);
+        }
+    }
+    if lengths.len() > literal_count + distance_count {
+        return error("code lengths overrun");
+    }
+    if lengths[256] == 0 {
+        return error("missing end of block code");
+    }
+
+    let literals = Huffman::new(&lengths[..literal_count])?;
+    let distances = Huffman::new(&lengths[literal_count..])?;
+    Ok((literals, distances))
+}
+
+fn inflate_block(reader: &mut BitReader, output: &mut Vec<u8>, literals: &Huffman, distances: &Huffman) -> Result<(), InflateError> {
+    loop {
+        let symbol = literals.decode(reader)? as usize;
+        match symbol {
+            0..=255 => output.push(symbol as u8),
+            256 => return Ok(()),
+            257..=285 => {
+                let length_code = symbol - 257;
+                let length = LENGTH_BASE[length_code] as usize
+                    + reader.read_bits(LENGTH_EXTRA[length_code] as u32)? as usize;
+                let distance_code = distances.decode(reader)? as usize;
+                if distance_code >= 30 {
+                    return error("invalid distance code");
+                }
+                let distance = DISTANCE_BASE[distance_code] as usize
+                    + reader.read_bits(DISTANCE_EXTRA[distance_code] as u32)? as usize;
+                if distance > output.len() {
+                    return error("distance too far back");
+                }
+                // Copied byte by byte, since the match may overlap the bytes it produces
+                let start = output.len() - distance;
+                for offset in 0..length {
+                    output.push(output[start + offset]);
+                }
+            }
+            _ => return error("invalid literal/length code"),
+        }
+    }
+}
+
+// Unwraps a zlib stream, checking its header and checksum
+// is_multiple_of would need Rust 1.87 for the header check
+#[allow(clippy::manual_is_multiple_of)]
+pub fn decompress(bytes: &[u8]) -> Result<Vec<u8>, InflateError> {
+    if bytes.len() < 6 {
+        return error("stream too short");
+    }
+    let (cmf, flg) = (bytes[0], bytes[1]);
+    if cmf & 0x0F != 8 || cmf >> 4 > 7 {
+        return error("unsupported compression method");
+    }
+    if ((cmf as u16) << 8 | flg as u16) % 31 != 0 {
+        return error("header check failed");
+    }
+    if flg & 0x20 != 0 {
+        return error("preset dictionaries are not supported");
+    }
+
+    let mut reader = BitReader { bytes, position: 2, buffer: 0, count: 0 };
+    let mut output = vec![];
+    loop {
+        let is_final = reader.read_bits(1)? == 1;
+        match reader.read_bits(2)? {
+            0 => {
+                reader.align_to_byte();
+                let header = bytes.get(reader.position..reader.position + 4);
+                let header = match header {
+                    Some(header) => header,
+                    None => return error("unexpected end of data"),
+                };
+                let length = u16::from_le_bytes([header[0], header[1]]) as usize;
+                let complement = u16::from_le_bytes([header[2], header[3]]) as usize;
+                if length != !complement & 0xFFFF {
+                    return error("stored block length mismatch");
+                }
+                let start = reader.position + 4;
+                match bytes.get(start..start + length) {
+                    Some(stored) => output.extend_from_slice(stored),
+                    None => return error("unexpected end of data"),
+                }
+                reader.position = start + length;
+            }
+            1 => {
+                let (literals, distances) = fixed_codes();
+                inflate_block(&mut reader, &mut output, &literals, &distances)?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_codes(&mut reader)?;
+                inflate_block(&mut reader, &mut output, &literals, &distances)?;
+            }
+            _ => return error("invalid block type"),
+        }
+        if is_final {
+            break;
+        }
+    }
+
+    // Unused bits of the last byte are padding, the checksum follows
+    let checksum = match bytes.get(reader.position..reader.position + 4) {
+        Some(checksum) => u32::from_be_bytes([checksum[0], checksum[1], checksum[2], checksum[3]]),
+        None => return error("missing checksum"),
+    };
+    if checksum != adler32(&output) {
+        return error("checksum mismatch");
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksums_should_match_reference_values() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32_update(crc32(b"1234"), b"56789"), 0xCBF4_3926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+    }
+
+    #[test]
+    fn compressed_data_should_round_trip() {
+        let mut rng = crate::utils::rng();
+        let random: Vec<u8> = (0..10_000).map(|_| rand::Rng::gen::<u8>(&mut rng)).collect();
+        let repetitive: Vec<u8> = b"abcabcabcd".iter().cycle().take(100_000).cloned().collect();
+        // Repeats from just within and just beyond the window, where hash chain links are reused
+        let block: Vec<u8> = (0..WINDOW_SIZE + 1000).map(|_| rand::Rng::gen::<u8>(&mut rng)).collect();
+        let near = [&block[..WINDOW_SIZE - 10], &block[..WINDOW_SIZE - 10]].concat();
+        let far = [&block[..], &block[..]].concat();
+        for data in [vec![], vec![42], random, repetitive.clone(), near.clone(), far].iter() {
+            assert_eq!(&decompress(&compress(data)).unwrap(), data);
+        }
+        assert!(compress(&near).len() < near.len() * 3 / 4);
+        assert!(compress(&repetitive).len() < repetitive.len() / 50);
+    }
+
+    #[test]
+    fn stream_from_reference_zlib_should_decompress() {
+        // Dynamic Huffman block, from Python's zlib.compress(text, 9)
+        let text = b"A raytracer follows rays of light backwards from the camera into the scene, \
+bouncing them off spheres until they escape to the sky.";
+        let compressed = [
+            0x78, 0xda, 0x35, 0xcc, 0xd1, 0x09, 0xc3, 0x30, 0x0c, 0x84, 0xe1, 0x55, 0x6e, 0x80, 0xd2, 0x1d,
+            0x3a, 0x8a, 0xa2, 0xca, 0xb1, 0x89, 0x2d, 0x05, 0x49, 0x21, 0x78, 0xfb, 0xd6, 0x85, 0x3e, 0xde,
+            0x07, 0xf7, 0xbf, 0xe0, 0x34, 0xd3, 0x89, 0xc5, 0x51, 0xac, 0x77, 0xbb, 0x63, 0x49, 0xc0, 0x0a,
+            0x7a, 0xdb, 0x6b, 0x62, 0x23, 0x3e, 0x6e, 0xf2, 0x77, 0xa0, 0xb8, 0x0d, 0x64, 0x15, 0x30, 0x0d,
+            0x71, 0x42, 0xd3, 0xb4, 0xdf, 0x0e, 0x16, 0x95, 0x07, 0x36, 0xbb, 0x94, 0x9b, 0xee, 0xcb, 0xc6,
+            0x37, 0x50, 0x10, 0x67, 0x15, 0x97, 0xc0, 0xa5, 0xd9, 0xfa, 0xe2, 0x09, 0x09, 0xa6, 0x53, 0xf0,
+            0x7f, 0x1e, 0xf3, 0xf9, 0x01, 0x72, 0x3f, 0x2f, 0xfe,
+        ];
+        assert_eq!(decompress(&compressed).unwrap(), text.to_vec());
+
+        // Stored block: zlib.compress(b"stored", 0)
+        let stored = [
+            0x78, 0x01, 0x01, 0x06, 0x00, 0xf9, 0xff, 0x73, 0x74, 0x6f, 0x72, 0x65, 0x64, 0x09,
+            0x3c, 0x02, 0x92,
+        ];
+        assert_eq!(decompress(&stored).unwrap(), b"stored");
+    }
+
+    #[test]
+    fn corrupt_stream_should_be_rejected() {
+        let mut compressed = compress(b"some data to corrupt");
+        let last = compressed.len() - 1;
+        compressed[last] ^= 0xFF;
+        assert!(decompress(&compressed).is_err());
+        assert!(decompress(&[0x78, 0x9C]).is_err());
+    }
+}